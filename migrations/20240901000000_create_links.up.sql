@@ -0,0 +1,13 @@
+-- Short links created through /api/shorten
+CREATE TABLE IF NOT EXISTS links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    original_url TEXT NOT NULL,
+    short_code VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,
+    password_hash TEXT,
+    click_limit INTEGER,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE INDEX IF NOT EXISTS links_created_at_idx ON links (created_at DESC);
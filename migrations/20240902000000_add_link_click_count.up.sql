@@ -0,0 +1,2 @@
+-- Clicks consumed against links.click_limit
+ALTER TABLE links ADD COLUMN click_count INTEGER NOT NULL DEFAULT 0;
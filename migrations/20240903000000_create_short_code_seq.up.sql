@@ -0,0 +1,2 @@
+-- Counter behind the sequential and hashids short code strategies
+CREATE SEQUENCE IF NOT EXISTS short_code_seq START WITH 1;
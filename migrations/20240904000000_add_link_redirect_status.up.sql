@@ -0,0 +1,2 @@
+-- HTTP status of the redirect; NULL uses the deployment default
+ALTER TABLE links ADD COLUMN redirect_status SMALLINT;
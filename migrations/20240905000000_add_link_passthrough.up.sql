@@ -0,0 +1,2 @@
+-- Parts of the request URL forwarded to the destination: none, query or path_and_query
+ALTER TABLE links ADD COLUMN passthrough TEXT NOT NULL DEFAULT 'none';
@@ -0,0 +1,2 @@
+-- Ordered routing rules evaluated before falling back to original_url
+ALTER TABLE links ADD COLUMN rules JSONB NOT NULL DEFAULT '[]';
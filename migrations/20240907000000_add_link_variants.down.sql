@@ -0,0 +1,2 @@
+DROP TABLE link_variant_clicks;
+ALTER TABLE links DROP COLUMN variants;
@@ -0,0 +1,10 @@
+-- Weighted destinations splitting a link's visitors
+ALTER TABLE links ADD COLUMN variants JSONB NOT NULL DEFAULT '[]';
+
+-- Redirects counted per variant of a link's split
+CREATE TABLE link_variant_clicks (
+    link_id UUID NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+    variant TEXT NOT NULL,
+    clicks BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (link_id, variant)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE links DROP COLUMN fallback_url;
+ALTER TABLE links DROP COLUMN starts_at;
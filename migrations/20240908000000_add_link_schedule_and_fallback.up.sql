@@ -0,0 +1,3 @@
+-- Links redirect from starts_at on; fallback_url serves visitors of unavailable links
+ALTER TABLE links ADD COLUMN starts_at TIMESTAMPTZ;
+ALTER TABLE links ADD COLUMN fallback_url TEXT;
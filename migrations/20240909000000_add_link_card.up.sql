@@ -0,0 +1,2 @@
+-- Open Graph/Twitter card served to unfurl bots; JSON null when unset
+ALTER TABLE links ADD COLUMN card JSONB NOT NULL DEFAULT 'null';
@@ -0,0 +1,15 @@
+-- One row per redirect, written in batches by the click recorder. Not tied to
+-- `links` by a foreign key so clicks can be recorded whatever the link store.
+CREATE TABLE clicks (
+    id BIGSERIAL PRIMARY KEY,
+    link_id UUID NOT NULL,
+    short_code TEXT NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash TEXT,
+    country TEXT,
+    variant TEXT
+);
+
+CREATE INDEX clicks_link_id_occurred_at_idx ON clicks (link_id, occurred_at);
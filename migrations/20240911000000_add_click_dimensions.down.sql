@@ -0,0 +1,5 @@
+ALTER TABLE clicks
+    DROP COLUMN referrer_host,
+    DROP COLUMN browser,
+    DROP COLUMN os,
+    DROP COLUMN device;
@@ -0,0 +1,7 @@
+-- Attributes derived from the referrer and User-Agent when a click is stored,
+-- so stats can group by them
+ALTER TABLE clicks
+    ADD COLUMN referrer_host TEXT,
+    ADD COLUMN browser TEXT,
+    ADD COLUMN os TEXT,
+    ADD COLUMN device TEXT;
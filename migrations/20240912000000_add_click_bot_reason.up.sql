@@ -0,0 +1,2 @@
+-- Why a click was classified as a bot's; NULL for people
+ALTER TABLE clicks ADD COLUMN bot_reason TEXT;
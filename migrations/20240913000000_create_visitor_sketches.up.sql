@@ -0,0 +1,9 @@
+-- Daily HyperLogLog sketches of the distinct visitors of each link, copied from
+-- Redis so they survive eviction there.
+CREATE TABLE visitor_sketches (
+    link_id UUID NOT NULL,
+    day DATE NOT NULL,
+    sketch BYTEA NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (link_id, day)
+);
@@ -0,0 +1,4 @@
+DROP TABLE job_watermarks;
+DROP INDEX clicks_occurred_at_idx;
+DROP TABLE click_rollups_daily;
+DROP TABLE click_rollups_hourly;
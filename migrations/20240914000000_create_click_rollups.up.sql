@@ -0,0 +1,38 @@
+-- Clicks per link, bucket and attribute value, rolled up from `clicks` by a
+-- background job. The `total` dimension has a single empty value counting every
+-- click of the bucket.
+CREATE TABLE click_rollups_hourly (
+    link_id UUID NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    is_bot BOOLEAN NOT NULL,
+    dimension TEXT NOT NULL,
+    value TEXT NOT NULL,
+    clicks BIGINT NOT NULL,
+    unique_clicks BIGINT NOT NULL,
+    PRIMARY KEY (link_id, dimension, bucket, is_bot, value)
+);
+
+CREATE INDEX click_rollups_hourly_bucket_idx ON click_rollups_hourly (bucket);
+
+CREATE TABLE click_rollups_daily (
+    link_id UUID NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    is_bot BOOLEAN NOT NULL,
+    dimension TEXT NOT NULL,
+    value TEXT NOT NULL,
+    clicks BIGINT NOT NULL,
+    unique_clicks BIGINT NOT NULL,
+    PRIMARY KEY (link_id, dimension, bucket, is_bot, value)
+);
+
+CREATE INDEX click_rollups_daily_bucket_idx ON click_rollups_daily (bucket);
+
+-- Rollup jobs read clicks by time alone
+CREATE INDEX clicks_occurred_at_idx ON clicks (occurred_at);
+
+-- How far background jobs working through data in time order have come
+CREATE TABLE job_watermarks (
+    job TEXT PRIMARY KEY,
+    watermark TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
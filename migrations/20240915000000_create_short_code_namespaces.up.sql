@@ -0,0 +1,6 @@
+-- Characters added to generated codes of each namespace, shared by all instances.
+-- Raised by the code allocator when random codes collide too often.
+CREATE TABLE short_code_namespaces (
+    name TEXT PRIMARY KEY,
+    extra_length INTEGER NOT NULL DEFAULT 0
+);
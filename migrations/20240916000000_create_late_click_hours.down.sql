@@ -0,0 +1 @@
+DROP TABLE IF EXISTS late_click_hours;
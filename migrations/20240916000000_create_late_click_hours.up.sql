@@ -0,0 +1,5 @@
+-- Hours before the rollup watermark that clicks were stored in afterwards, to be
+-- rolled up again by the next run of the rollup job.
+CREATE TABLE late_click_hours (
+    hour TIMESTAMPTZ PRIMARY KEY
+);
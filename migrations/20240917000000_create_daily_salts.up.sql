@@ -0,0 +1,6 @@
+-- Random salt of the visitor hashes of each UTC day, shared by all instances.
+-- Deleted once the day is over, so its hashes can't be computed again.
+CREATE TABLE daily_salts (
+    day DATE PRIMARY KEY,
+    salt BYTEA NOT NULL
+);
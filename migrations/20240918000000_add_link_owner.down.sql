@@ -0,0 +1,2 @@
+DROP INDEX links_owner_idx;
+ALTER TABLE links DROP COLUMN owner;
@@ -0,0 +1,5 @@
+-- Account that created the link with its access token; NULL for links created
+-- without one
+ALTER TABLE links ADD COLUMN owner TEXT;
+
+CREATE INDEX links_owner_idx ON links (owner, created_at);
@@ -0,0 +1,2 @@
+DROP INDEX links_tags_idx;
+ALTER TABLE links DROP COLUMN tags;
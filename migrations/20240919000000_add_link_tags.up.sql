@@ -0,0 +1,4 @@
+-- Labels grouping links, such as a campaign, for exports
+ALTER TABLE links ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX links_tags_idx ON links USING GIN (tags);
@@ -1,5 +1,6 @@
 // src/config/mod.rs
 
+use crate::utils::short_code::BASE62_ALPHABET;
 use dotenvy::dotenv;
 use std::env;
 
@@ -8,6 +9,80 @@ pub struct Config {
     pub redis_url: String,
     pub host_url: String,
     pub log_level: String,
+    /// Link store backend: `postgres` (with a Redis cache), `redis` or `memory`.
+    pub link_store: String,
+    /// Maximum time in seconds a link stays in the Redis cache.
+    pub cache_ttl_seconds: usize,
+    /// Key signing unlock cookies of password-protected links; random per process if unset.
+    pub unlock_secret: Option<String>,
+    /// Time in seconds an unlocked link stays unlocked for a visitor.
+    pub unlock_ttl_seconds: i64,
+    /// Short code strategy: `random`, `sequential`, `hashids` or `content_hash`.
+    pub short_code_strategy: String,
+    /// Characters short codes are made of.
+    pub short_code_alphabet: String,
+    /// Length of random and content hash codes, and minimum length of hashids codes.
+    pub short_code_length: usize,
+    /// Salt of the hashids strategy.
+    pub short_code_salt: String,
+    /// Candidate codes tried per link before allocation fails.
+    pub short_code_max_attempts: u32,
+    /// Collision rate, between 0 and 1, above which random codes grow by one character.
+    pub short_code_growth_threshold: f64,
+    /// Characters custom aliases may contain.
+    pub alias_charset: String,
+    /// Minimum length of custom aliases.
+    pub alias_min_length: usize,
+    /// Maximum length of custom aliases.
+    pub alias_max_length: usize,
+    /// Words that can't be claimed as aliases, in addition to the paths of registered routes.
+    pub alias_reserved_words: Vec<String>,
+    /// Fold custom aliases and looked up codes to lower case, so codes differing only in
+    /// case are the same link, and generate codes from the lower case letters of the
+    /// alphabet. Links already stored with upper case letters have to be renamed before
+    /// turning it on.
+    pub alias_case_insensitive: bool,
+    /// Status of links without their own redirect status: 301, 302, 307 or 308. A
+    /// temporary 302 by default, as browsers keep permanent redirects and stop
+    /// following destination edits and counting clicks.
+    pub redirect_status: i16,
+    /// Longest time in seconds clients may cache a permanent redirect.
+    pub redirect_cache_max_age: u64,
+    /// MaxMind-format database resolving visitor countries for routing rules.
+    pub geoip_database: Option<String>,
+    /// Clicks queued for storage before new ones are dropped.
+    pub click_queue_capacity: usize,
+    /// Most clicks stored in one batch.
+    pub click_batch_size: usize,
+    /// Longest time in milliseconds a click waits for its batch to fill.
+    pub click_flush_interval_ms: u64,
+    /// Key hashing visitor IP addresses of clicks; random per process if unset.
+    pub click_ip_secret: Option<String>,
+    /// How IP addresses identify the visitors of clicks: `drop`, `truncate`, `hash` or `daily_hash`.
+    pub click_ip_mode: String,
+    /// Store clicks sending `DNT: 1` or `Sec-GPC: 1` without their visitor, referrer and
+    /// User-Agent, nor the country, browser, OS, device and referrer host derived from them.
+    pub click_honor_do_not_track: bool,
+    /// Days stored clicks are kept before being purged, their counts staying in the rollups; forever if unset.
+    pub click_retention_days: Option<u64>,
+    /// Interval in seconds at which clicks past the retention period are purged.
+    pub click_retention_interval_seconds: u64,
+    /// Accounts as `account:token` entries, separated by commas. Links created with a
+    /// token are owned by its account, which alone may watch, export and delete their
+    /// clicks; nobody may if no account is configured.
+    pub api_tokens: Vec<String>,
+    /// Redis pub/sub channel fanning the click stream out to all instances.
+    pub click_stream_channel: String,
+    /// Time in seconds a day's visitor sketch stays in Redis after its last visitor.
+    pub visitor_sketch_ttl_seconds: usize,
+    /// Interval in seconds at which changed visitor sketches are saved to PostgreSQL.
+    pub visitor_sketch_save_interval_seconds: u64,
+    /// Interval in seconds at which stored clicks are rolled up.
+    pub rollup_interval_seconds: u64,
+    /// Time in seconds after the end of an hour before its clicks are rolled up.
+    pub rollup_settle_seconds: u64,
+    /// Apply pending database migrations on startup.
+    pub run_migrations: bool,
 }
 
 impl Config {
@@ -18,6 +93,96 @@ impl Config {
             redis_url: env::var("REDIS_URL").expect("REDIS_URL must be set"),
             host_url: env::var("HOST_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
             log_level: env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string()),
+            link_store: env::var("LINK_STORE").unwrap_or_else(|_| "postgres".to_string()),
+            cache_ttl_seconds: env::var("CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7 * 24 * 60 * 60), // 7 days in seconds
+            unlock_secret: env::var("UNLOCK_COOKIE_SECRET").ok(),
+            unlock_ttl_seconds: env::var("UNLOCK_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15 * 60), // 15 minutes in seconds
+            short_code_strategy: env::var("SHORT_CODE_STRATEGY").unwrap_or_else(|_| "random".to_string()),
+            short_code_alphabet: env::var("SHORT_CODE_ALPHABET")
+                .unwrap_or_else(|_| BASE62_ALPHABET.to_string()),
+            short_code_length: env::var("SHORT_CODE_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(6),
+            short_code_salt: env::var("SHORT_CODE_SALT").unwrap_or_default(),
+            short_code_max_attempts: env::var("SHORT_CODE_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            short_code_growth_threshold: env::var("SHORT_CODE_GROWTH_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.1),
+            alias_charset: env::var("ALIAS_CHARSET")
+                .unwrap_or_else(|_| format!("{}-_", BASE62_ALPHABET)),
+            alias_min_length: env::var("ALIAS_MIN_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            alias_max_length: env::var("ALIAS_MAX_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(64),
+            alias_reserved_words: env::var("ALIAS_RESERVED_WORDS")
+                .map(|v| v.split(',').map(|word| word.trim().to_string()).collect())
+                .unwrap_or_default(),
+            alias_case_insensitive: env::var("ALIAS_CASE_INSENSITIVE").map(|v| v == "true").unwrap_or(false),
+            redirect_status: env::var("REDIRECT_STATUS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(302),
+            redirect_cache_max_age: env::var("REDIRECT_CACHE_MAX_AGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(24 * 60 * 60), // 1 day in seconds
+            geoip_database: env::var("GEOIP_DATABASE").ok(),
+            click_queue_capacity: env::var("CLICK_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_000),
+            click_batch_size: env::var("CLICK_BATCH_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+            click_flush_interval_ms: env::var("CLICK_FLUSH_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+            click_ip_secret: env::var("CLICK_IP_SECRET").ok(),
+            click_ip_mode: env::var("CLICK_IP_MODE").unwrap_or_else(|_| "hash".to_string()),
+            click_honor_do_not_track: env::var("CLICK_HONOR_DO_NOT_TRACK").map(|v| v == "true").unwrap_or(false),
+            click_retention_days: env::var("CLICK_RETENTION_DAYS").ok().and_then(|v| v.parse().ok()),
+            click_retention_interval_seconds: env::var("CLICK_RETENTION_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60 * 60), // 1 hour in seconds
+            api_tokens: env::var("API_TOKENS")
+                .map(|v| v.split(',').filter(|entry| !entry.trim().is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            click_stream_channel: env::var("CLICK_STREAM_CHANNEL").unwrap_or_else(|_| "click_stream".to_string()),
+            visitor_sketch_ttl_seconds: env::var("VISITOR_SKETCH_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7 * 24 * 60 * 60), // 7 days in seconds
+            visitor_sketch_save_interval_seconds: env::var("VISITOR_SKETCH_SAVE_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 60), // 5 minutes in seconds
+            rollup_interval_seconds: env::var("ROLLUP_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            rollup_settle_seconds: env::var("ROLLUP_SETTLE_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 60), // 5 minutes in seconds
+            run_migrations: env::var("RUN_MIGRATIONS").map(|v| v == "true").unwrap_or(false),
         }
     }
 }
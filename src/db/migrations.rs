@@ -0,0 +1,57 @@
+// src/db/migrations.rs
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+
+/// SQL migrations from `migrations/`, embedded in the binary at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// State of a single embedded migration.
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Applies all pending migrations.
+pub async fn run_pending(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Reverts the most recently applied migration.
+///
+/// Returns the reverted version, or `None` if nothing was applied.
+pub async fn revert_latest(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let applied = applied_versions(pool).await?;
+    let Some(latest) = applied.iter().copied().max() else {
+        return Ok(None);
+    };
+
+    // Undo down to the newest applied version below the latest one
+    let target = applied.iter().copied().filter(|v| *v < latest).max().unwrap_or(0);
+    MIGRATOR.undo(pool, target).await?;
+    Ok(Some(latest))
+}
+
+/// Lists every embedded migration and whether it has been applied.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let applied = applied_versions(pool).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect())
+}
+
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+    Ok(applied.into_iter().map(|m| m.version).collect())
+}
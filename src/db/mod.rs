@@ -1 +1,4 @@
-pub mod pool;
\ No newline at end of file
+// src/db/mod.rs
+
+pub mod migrations;
+pub mod pool;
@@ -1,9 +1,9 @@
 // src/errors.rs
 
+use crate::services::alias::AliasViolation;
 use actix_web::{HttpResponse, ResponseError};
-use serde::{Deserialize, Serialize};
-use std::{fmt, io};
-use apistos::ApiErrorComponent;
+use serde::Serialize;
+use std::fmt;
 
 /// Struct for error responses.
 #[derive(Debug, Serialize)]
@@ -16,6 +16,14 @@ pub struct ErrorResponse {
 pub enum ServiceError {
     RedisError(redis::RedisError),
     UrlParseError(url::ParseError),
+    DatabaseError(sqlx::Error),
+    SerializationError(serde_json::Error),
+    Conflict(String),
+    NotFound(String),
+    ShortCodeUnavailable(String),
+    InvalidAlias(AliasViolation),
+    BadRequest(String),
+    Unauthorized(String),
     // Add other error variants as needed
 }
 
@@ -24,10 +32,20 @@ impl fmt::Display for ServiceError {
         match self {
             ServiceError::RedisError(e) => write!(f, "Redis error: {}", e),
             ServiceError::UrlParseError(e) => write!(f, "URL parse error: {}", e),
+            ServiceError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            ServiceError::SerializationError(e) => write!(f, "Serialization error: {}", e),
+            ServiceError::Conflict(message) => write!(f, "{}", message),
+            ServiceError::NotFound(message) => write!(f, "{}", message),
+            ServiceError::ShortCodeUnavailable(message) => write!(f, "{}", message),
+            ServiceError::InvalidAlias(violation) => write!(f, "{}", violation),
+            ServiceError::BadRequest(message) => write!(f, "{}", message),
+            ServiceError::Unauthorized(message) => write!(f, "{}", message),
         }
     }
 }
 
+impl std::error::Error for ServiceError {}
+
 impl ResponseError for ServiceError {
     fn error_response(&self) -> HttpResponse {
         let error_response = ErrorResponse {
@@ -36,6 +54,16 @@ impl ResponseError for ServiceError {
         match self {
             ServiceError::RedisError(_) => HttpResponse::InternalServerError().json(error_response),
             ServiceError::UrlParseError(_) => HttpResponse::BadRequest().json(error_response),
+            ServiceError::DatabaseError(_) => HttpResponse::InternalServerError().json(error_response),
+            ServiceError::SerializationError(_) => HttpResponse::InternalServerError().json(error_response),
+            ServiceError::Conflict(_) => HttpResponse::Conflict().json(error_response),
+            ServiceError::NotFound(_) => HttpResponse::NotFound().json(error_response),
+            ServiceError::ShortCodeUnavailable(_) => HttpResponse::ServiceUnavailable().json(error_response),
+            ServiceError::InvalidAlias(violation) => HttpResponse::UnprocessableEntity().json(violation),
+            ServiceError::BadRequest(_) => HttpResponse::BadRequest().json(error_response),
+            ServiceError::Unauthorized(_) => HttpResponse::Unauthorized()
+                .insert_header(("WWW-Authenticate", "Bearer"))
+                .json(error_response),
         }
     }
 }
@@ -51,3 +79,21 @@ impl From<url::ParseError> for ServiceError {
         ServiceError::UrlParseError(error)
     }
 }
+
+impl From<sqlx::Error> for ServiceError {
+    fn from(error: sqlx::Error) -> Self {
+        ServiceError::DatabaseError(error)
+    }
+}
+
+impl From<serde_json::Error> for ServiceError {
+    fn from(error: serde_json::Error) -> Self {
+        ServiceError::SerializationError(error)
+    }
+}
+
+impl From<AliasViolation> for ServiceError {
+    fn from(violation: AliasViolation) -> Self {
+        ServiceError::InvalidAlias(violation)
+    }
+}
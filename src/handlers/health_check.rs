@@ -2,7 +2,7 @@
 
 use actix_web::{HttpResponse, Responder};
 use log::debug;
-use apistos::{api_operation, ApiComponent};
+use apistos::api_operation;
 /// Handler for the `/health` endpoint.
 /// Responds with a 200 OK status and body "OK".
 
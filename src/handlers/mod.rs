@@ -3,7 +3,11 @@
 pub mod shorten;
 pub mod redirect;
 pub mod health_check;
+pub mod unlock;
+pub mod preview;
 
 pub use shorten::shorten_url;
-pub use redirect::redirect;
-pub use health_check::health_check;
\ No newline at end of file
+pub use redirect::{redirect, redirect_with_path};
+pub use health_check::health_check;
+pub use unlock::{unlock, unlock_form};
+pub use preview::preview;
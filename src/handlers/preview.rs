@@ -0,0 +1,89 @@
+// src/handlers/preview.rs
+
+use actix_web::http::header::{self, CacheControl, CacheDirective};
+use actix_web::{web, HttpRequest, HttpResponse, Error as ActixError};
+use crate::handlers::unlock::is_unlocked;
+use crate::models::link::Link;
+use crate::services::UnlockTokens;
+use crate::store::DynLinkStore;
+use crate::utils::html;
+use log::info;
+use apistos::api_operation;
+
+/// Shows where a short link leads without following it.
+///
+/// Served at `/{short_id}/preview` and `/{short_id}+`. The destination of a
+/// password-protected link stays hidden until the visitor unlocks it.
+#[api_operation(
+    tag = "api",
+    summary = "Preview page of a short link",
+    skip_args = "req"
+)]
+pub async fn preview(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    tokens: web::Data<UnlockTokens>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let short_id = path.into_inner();
+    info!("Received preview request for short ID: {}", short_id);
+
+    let Some(link) = store.get_by_code(&short_id).await? else {
+        return Ok(HttpResponse::NotFound().body("Short URL not found"));
+    };
+
+    let body = preview_body(&link, is_unlocked(&req, &link, &tokens));
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        // The page never loads anything, so nothing in it can run or leak the visit
+        .insert_header((header::CONTENT_SECURITY_POLICY, "default-src 'none'"))
+        .insert_header((header::REFERRER_POLICY, "no-referrer"))
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+        .body(html::page("Link preview", &body)))
+}
+
+fn preview_body(link: &Link, unlocked: bool) -> String {
+    let short_code = html::escape(&link.short_code);
+    let mut body = format!("<h1>Preview of /{}</h1>\n<dl>\n", short_code);
+
+    if unlocked {
+        let domain = url::Url::parse(&link.original_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        body.push_str(&format!(
+            "<dt>Destination</dt><dd><code>{}</code></dd>\n<dt>Domain</dt><dd><strong>{}</strong></dd>\n",
+            html::escape(&link.original_url),
+            html::escape(&domain)
+        ));
+    } else {
+        body.push_str("<dt>Destination</dt><dd>Hidden until the link is unlocked</dd>\n");
+    }
+
+    body.push_str(&format!(
+        "<dt>Created</dt><dd>{}</dd>\n<dt>Password protected</dt><dd>{}</dd>\n<dt>Status</dt><dd>{}</dd>\n</dl>\n",
+        link.created_at.format("%Y-%m-%d %H:%M UTC"),
+        if link.password_hash.is_some() { "Yes" } else { "No" },
+        status(link)
+    ));
+
+    if unlocked && (!link.rules.is_empty() || !link.variants.is_empty()) {
+        body.push_str("<p>Some visitors are sent to other destinations.</p>\n");
+    }
+    body.push_str(&format!("<p><a href=\"/{}\" rel=\"noreferrer\">Follow the link</a></p>", short_code));
+    body
+}
+
+fn status(link: &Link) -> &'static str {
+    if !link.is_active {
+        "Deactivated"
+    } else if link.is_expired() {
+        "Expired"
+    } else if link.is_scheduled() {
+        "Not started yet"
+    } else if link.is_exhausted() {
+        "Reached its click limit"
+    } else {
+        "Active"
+    }
+}
@@ -1,36 +1,161 @@
 // src/handlers/redirect.rs
 
-use actix_web::{web, HttpResponse, Responder, Error as ActixError};
-use crate::services::UrlService;
-use crate::errors::ServiceError;
-use log::{info, debug, error};
-use apistos::{api_operation, ApiComponent};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder, Error as ActixError};
+use crate::errors::ErrorResponse;
+use crate::handlers::unlock::{is_unlocked, locked};
+use crate::models::link::Link;
+use crate::services::{ClickRecorder, GeoIp, RedirectPolicy, UnlockTokens};
+use crate::store::DynLinkStore;
+use crate::utils::visitor::Visitor;
+use crate::utils::{bot, card, html, passthrough, split};
+use log::{info, debug, error, warn};
+use apistos::api_operation;
 
 #[api_operation(
     tag = "api",
     summary = "Redirector",
     description = r###"Redirects you to the
   Plop"###,
-    error_code = 200
+    error_code = 200,
+    skip_args = "req"
 )]
 pub async fn redirect(
-    url_service: web::Data<UrlService>,
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    tokens: web::Data<UnlockTokens>,
+    policy: web::Data<RedirectPolicy>,
+    geoip: web::Data<GeoIp>,
+    clicks: web::Data<ClickRecorder>,
     path: web::Path<String>,
 ) -> Result<HttpResponse, ActixError> {
-    let short_id = path.into_inner();
+    resolve(&req, &store, &tokens, &policy, &geoip, &clicks, &path.into_inner(), None).await
+}
+
+/// Redirects links forwarding their path, appending everything after the short code
+/// to the destination.
+#[api_operation(
+    tag = "api",
+    summary = "Redirector forwarding extra path segments",
+    error_code = 200,
+    skip_args = "req"
+)]
+pub async fn redirect_with_path(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    tokens: web::Data<UnlockTokens>,
+    policy: web::Data<RedirectPolicy>,
+    geoip: web::Data<GeoIp>,
+    clicks: web::Data<ClickRecorder>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ActixError> {
+    let (short_id, extra_path) = path.into_inner();
+    resolve(&req, &store, &tokens, &policy, &geoip, &clicks, &short_id, Some(&extra_path)).await
+}
+
+/// Looks up the short ID and answers with the redirect, or why there is none
+#[allow(clippy::too_many_arguments)]
+async fn resolve(
+    req: &HttpRequest,
+    store: &DynLinkStore,
+    tokens: &UnlockTokens,
+    policy: &RedirectPolicy,
+    geoip: &GeoIp,
+    clicks: &ClickRecorder,
+    short_id: &str,
+    extra_path: Option<&str>,
+) -> Result<HttpResponse, ActixError> {
     info!("Received redirect request for short ID: {}", short_id);
     debug!("Looking up original URL for short ID: {}", short_id);
 
-    match url_service.get_original_url(&short_id).await {
-        Ok(Some(original_url)) => {
-            info!("Redirecting to original URL: {}", original_url);
-            Ok(HttpResponse::MovedPermanently()
-                .append_header(("Location", original_url))
-                .finish())
+    match store.get_by_code(short_id).await {
+        Ok(Some(link)) if link.is_active && link.is_expired() => {
+            info!("Short ID expired: {}", short_id);
+            Ok(unavailable(policy, &link, gone(req, "This short link has expired")))
+        },
+        Ok(Some(link)) if link.is_active && link.is_scheduled() => {
+            info!("Short ID not started yet: {}", short_id);
+            Ok(unavailable(policy, &link, not_found()))
+        },
+        Ok(Some(link)) if link.is_active => {
+            // Unfurl bots get the link's own card rather than the destination's
+            if let Some(social_card) = link.card.as_ref().filter(|_| card::from_unfurl_bot(req)) {
+                info!("Serving card of short ID to unfurl bot: {}", short_id);
+                return Ok(HttpResponse::Ok()
+                    .content_type("text/html; charset=utf-8")
+                    .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+                    .body(card::page(social_card)));
+            }
+
+            // Only links with rules need to know who is visiting; visitors no rule
+            // matched are split between the variants, if any
+            let rule = if link.rules.is_empty() {
+                None
+            } else {
+                link.rule_for(&Visitor::from_request(req, geoip))
+            };
+            let assignment = match rule {
+                Some(_) => None,
+                None => split::assign(req, &link),
+            };
+            let destination = match (rule, assignment) {
+                (Some(rule), _) => rule.destination.as_str(),
+                (None, Some((variant, _))) => variant.destination.as_str(),
+                (None, None) => link.original_url.as_str(),
+            };
+            let Some(location) =
+                passthrough::destination(destination, link.passthrough, extra_path, req.query_string())
+            else {
+                info!("Short ID does not forward paths: {}", short_id);
+                return Ok(not_found());
+            };
+
+            if !is_unlocked(req, &link, tokens) {
+                info!("Short ID is locked: {}", short_id);
+                return Ok(locked(req, &link));
+            }
+
+            // Bots don't use up clicks, so they aren't sent on from links with a limit
+            // either: they would get to the destination however many clicks are left.
+            // They still get a success, as browsers send HEADs and prefetches too
+            let bot = bot::classify(req);
+            let within_limit = match (link.click_limit, bot) {
+                (None, _) => true,
+                (Some(_), Some(signal)) => {
+                    info!("Holding back bot ({}) on short ID with a click limit: {}", signal, short_id);
+                    return Ok(held_back(req));
+                }
+                (Some(_), None) => store.consume_click(&link.short_code).await?,
+            };
+            if !within_limit {
+                info!("Short ID exhausted its click limit: {}", short_id);
+                let exhausted = gone(req, "This short link has reached its click limit");
+                return Ok(unavailable(policy, &link, exhausted));
+            }
+
+            let mut response = policy.redirect(&link, &location);
+            if let Some((variant, new)) = assignment {
+                if bot.is_none() {
+                    if let Err(e) = store.record_variant_click(&link.short_code, &variant.name).await {
+                        warn!("Failed to count variant {} of {}: {}", variant.name, short_id, e);
+                    }
+                }
+                if new {
+                    response.add_cookie(&split::cookie(&link, variant))?;
+                }
+            }
+            clicks.record(req, &link, assignment.map(|(variant, _)| variant.name.as_str()), bot);
+
+            info!("Redirecting to: {}", location);
+            Ok(response)
+        },
+        Ok(Some(link)) => {
+            info!("Short ID deactivated: {}", short_id);
+            Ok(unavailable(policy, &link, not_found()))
         },
         Ok(None) => {
             info!("Short ID not found: {}", short_id);
-            Ok(HttpResponse::NotFound().body("Short URL not found"))
+            Ok(not_found())
         },
         Err(e) => {
             error!("Error retrieving original URL for {}: {}", short_id, e);
@@ -38,3 +163,51 @@ pub async fn redirect(
         }
     }
 }
+
+/// Sends visitors of a link that can't be followed to its fallback URL, if it has one
+fn unavailable(policy: &RedirectPolicy, link: &Link, otherwise: HttpResponse) -> HttpResponse {
+    match &link.fallback_url {
+        Some(fallback_url) => policy.fallback(fallback_url),
+        None => otherwise,
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().body("Short URL not found")
+}
+
+/// Builds a `410 Gone` response, as HTML for browsers and JSON otherwise
+fn gone(req: &HttpRequest, message: &str) -> HttpResponse {
+    unavailable_page(req, HttpResponse::Gone(), message)
+}
+
+/// Builds a `200 OK` response that doesn't reveal the destination, for bots on links
+/// whose clicks are counted; it is never cached, so the next visit is redirected
+fn held_back(req: &HttpRequest) -> HttpResponse {
+    let message = "Open this short link in a browser to continue";
+    let mut builder = HttpResponse::Ok();
+    builder.insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoStore]));
+    if html::accepts_html(req) {
+        let body = format!("<h1>Short link</h1>\n<p>{}</p>", html::escape(message));
+        builder
+            .content_type("text/html; charset=utf-8")
+            .body(html::page("Short link", &body))
+    } else {
+        builder.json(ErrorResponse {
+            message: message.to_string(),
+        })
+    }
+}
+
+fn unavailable_page(req: &HttpRequest, mut builder: HttpResponseBuilder, message: &str) -> HttpResponse {
+    if html::accepts_html(req) {
+        let body = format!("<h1>Link unavailable</h1>\n<p>{}</p>", html::escape(message));
+        builder
+            .content_type("text/html; charset=utf-8")
+            .body(html::page("Link unavailable", &body))
+    } else {
+        builder.json(ErrorResponse {
+            message: message.to_string(),
+        })
+    }
+}
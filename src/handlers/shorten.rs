@@ -1,36 +1,63 @@
 // src/handlers/shorten.rs
 
-use actix_web::{web, HttpResponse, Responder, Error};
+use actix_web::{web, Error};
 use actix_web::web::Json;
+use crate::errors::ServiceError;
+use crate::models::link::{NewLink, Passthrough};
 use crate::models::ShortenRequest;
-use crate::services::UrlService;
 use crate::models::ShortenResponse;
-use crate::errors::ServiceError;
+use crate::services::CodeAllocator;
+use crate::store::DynLinkStore;
 use log::{info, debug, error};
-use apistos::{api_operation, ApiComponent};
+use apistos::api_operation;
 
 
 #[api_operation(summary = "Shorten a URL")]
 pub async fn shorten_url(
-    url_service: web::Data<UrlService>,
+    store: web::Data<DynLinkStore>,
+    allocator: web::Data<CodeAllocator>,
     req: Json<ShortenRequest>,
     host: web::Data<String>,
 ) -> Result<Json<ShortenResponse>, Error> {
     let original_url = req.url.trim();
     info!("Received request to shorten URL: {}", original_url);
 
-    // Shorten the URL
-    let short_id = match url_service.shorten_url(original_url).await {
-        Ok(id) => {
-            debug!("Generated short ID: {}", id);
-            id
-        },
-        Err(e) => {
+    // Validate the URL format
+    if let Err(e) = url::Url::parse(original_url) {
+        error!("{}", e);
+        return Err(Error::from(ServiceError::from(e)));
+    }
+
+    // Store the link under a generated short ID
+    let link = allocator
+        .create(
+            store.as_ref().as_ref(),
+            NewLink {
+                original_url: original_url.to_string(),
+                short_code: String::new(),
+                starts_at: None,
+                expires_at: None,
+                fallback_url: None,
+                password_hash: None,
+                click_limit: None,
+                redirect_status: None,
+                passthrough: Passthrough::None,
+                rules: Vec::new(),
+                variants: Vec::new(),
+                card: None,
+                is_active: true,
+                owner: None,
+                tags: Vec::new(),
+            },
+        )
+        .await
+        .map_err(|e| {
             error!("{}", e);
-            return Err(Error::from(e))
-        }
-    };
-    let short_url = format!("{}/{}", host.get_ref(), short_id);
+            Error::from(e)
+        })?;
+    debug!("Generated short ID: {}", link.short_code);
+
+    let short_url = format!("{}/{}", host.get_ref(), link.short_code);
     info!("Short URL created: {}", short_url);
 
     Ok(Json(ShortenResponse { short_url }))
@@ -0,0 +1,124 @@
+// src/handlers/unlock.rs
+
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, Error as ActixError};
+use crate::errors::ErrorResponse;
+use crate::models::link::{Link, UnlockForm};
+use crate::services::unlock::{UnlockTokens, PASSWORD_HEADER, UNLOCK_COOKIE};
+use crate::store::DynLinkStore;
+use crate::utils::html;
+use crate::utils::password::verify_password;
+use log::info;
+use apistos::api_operation;
+
+/// Renders the password form of a protected link.
+#[api_operation(tag = "api", summary = "Unlock page of a password-protected link")]
+pub async fn unlock_form(
+    store: web::Data<DynLinkStore>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let short_id = path.into_inner();
+
+    match store.get_by_code(&short_id).await? {
+        Some(link) if link.is_active && link.password_hash.is_some() => {
+            Ok(unlock_page(&link, HttpResponse::Ok(), None))
+        }
+        _ => Ok(HttpResponse::NotFound().body("Short URL not found")),
+    }
+}
+
+/// Verifies the submitted password and, on success, sets a cookie unlocking the link.
+#[api_operation(
+    tag = "api",
+    summary = "Unlock a password-protected link",
+    skip_args = "req"
+)]
+pub async fn unlock(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    tokens: web::Data<UnlockTokens>,
+    path: web::Path<String>,
+    form: web::Form<UnlockForm>,
+) -> Result<HttpResponse, ActixError> {
+    let short_id = path.into_inner();
+
+    let link = match store.get_by_code(&short_id).await? {
+        Some(link) if link.is_active && link.password_hash.is_some() => link,
+        _ => return Ok(HttpResponse::NotFound().body("Short URL not found")),
+    };
+
+    if !link
+        .password_hash
+        .as_deref()
+        .is_some_and(|hash| verify_password(&form.password, hash))
+    {
+        info!("Wrong password submitted for short ID: {}", short_id);
+        return Ok(unlock_page(&link, HttpResponse::Unauthorized(), Some("Wrong password")));
+    }
+
+    info!("Unlocked short ID: {}", short_id);
+    let cookie = Cookie::build(UNLOCK_COOKIE, tokens.issue(&link))
+        .path(format!("/{}", link.short_code))
+        .http_only(true)
+        .secure(req.connection_info().scheme() == "https")
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(tokens.ttl_seconds()))
+        .finish();
+
+    Ok(HttpResponse::SeeOther()
+        .cookie(cookie)
+        .append_header((header::LOCATION, format!("/{}", link.short_code)))
+        .finish())
+}
+
+/// Returns true if the request carries a valid unlock cookie or the correct password header
+pub fn is_unlocked(req: &HttpRequest, link: &Link, tokens: &UnlockTokens) -> bool {
+    let Some(password_hash) = link.password_hash.as_deref() else {
+        return true;
+    };
+
+    if req
+        .cookie(UNLOCK_COOKIE)
+        .is_some_and(|cookie| tokens.verify(link, cookie.value()))
+    {
+        return true;
+    }
+
+    req.headers()
+        .get(PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|password| verify_password(password, password_hash))
+}
+
+/// Builds the `401 Unauthorized` response for a locked link: the unlock form for
+/// browsers and a JSON error otherwise
+pub fn locked(req: &HttpRequest, link: &Link) -> HttpResponse {
+    if html::accepts_html(req) {
+        unlock_page(link, HttpResponse::Unauthorized(), None)
+    } else {
+        HttpResponse::Unauthorized().json(ErrorResponse {
+            message: format!("This short link is password protected; send the password in the {} header", PASSWORD_HEADER),
+        })
+    }
+}
+
+fn unlock_page(
+    link: &Link,
+    mut builder: actix_web::HttpResponseBuilder,
+    error: Option<&str>,
+) -> HttpResponse {
+    let error = error
+        .map(|message| format!("<p role=\"alert\">{}</p>\n", html::escape(message)))
+        .unwrap_or_default();
+    let body = format!(
+        "<h1>This link is password protected</h1>\n{}<form method=\"post\" action=\"/{}/unlock\">\n<label>Password <input type=\"password\" name=\"password\" autofocus required></label>\n<button type=\"submit\">Unlock</button>\n</form>",
+        error,
+        html::escape(&link.short_code)
+    );
+
+    builder
+        .content_type("text/html; charset=utf-8")
+        .body(html::page("Password required", &body))
+}
@@ -1,9 +1,11 @@
 // src/lib.rs
 
 pub mod config;
+pub mod db;
 pub mod models;
 pub mod handlers;
 pub mod services;
+pub mod store;
 pub mod routes;
 pub mod utils;
 pub mod errors;
@@ -1,44 +1,50 @@
 // src/main.rs
 
-mod config;
-mod models;
-
-use crate::models::*;
-
-mod handlers;
-mod services;
-mod routes;
-mod utils;
-mod errors;
-
-use crate::handlers::{
-    health_check,
-    redirect,
-    shorten_url,
-};
 use actix_web::middleware::Logger as ActixLogger;
 use actix_web::{web, App, HttpServer};
 use apistos::app::OpenApiWrapper;
 use apistos::info::{Contact, Info, License};
-use apistos::paths::{ExternalDocumentation, PathItem};
+use apistos::paths::ExternalDocumentation;
 use apistos::server::Server;
 use apistos::spec::Spec;
 use apistos::tag::Tag;
-use apistos::web::{get, post, resource, scope};
-use apistos::OpenApi;
 use apistos::SwaggerUIConfig;
-use config::Config;
 use dotenvy::dotenv;
-use log::{error, info};
+use log::{error, info, warn};
 use redis::aio::MultiplexedConnection;
-use services::UrlService;
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use url_shortener::config::Config;
+use url_shortener::models::export::ExportQuery;
+use url_shortener::routes;
+use url_shortener::services::{
+    Accounts, AliasPolicy, ClickPrivacy, ClickRecorder, ClickRetentionJob, ClickRollupJob, ClickStream,
+    CodeAllocator, Export, GeoIp, IpMode, Job, JobRunner, RedirectPolicy, SketchSaveJob, UnlockTokens,
+};
+use url_shortener::utils::short_code::{
+    fold_alphabet, parse_alphabet, ContentHashGenerator, DynShortCodeGenerator,
+    HashidsGenerator, RandomGenerator, Sequence, SequentialGenerator,
+};
+use url_shortener::store::{
+    CachedLinkStore, CaseFoldingLinkStore, DynClickStore, DynLinkStore, DynVisitorStore,
+    MemoryClickStore, MemoryLinkStore, MemoryVisitorStore, PgClickRollups, PgClickStore,
+    PgLinkStore, PgSketchArchive, RedisLinkStore, RedisVisitorStore,
+};
 use sqlx::PgPool;
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 // Import PgPool from SQLx
 
 use std::error::Error;
+use url_shortener::db::migrations;
+
+const USAGE: &str = "Usage: url_shortener [migrate <up|down|status>]
+       url_shortener export [--link <short_code> | --account <account> [--tag <tag>]]
+                            [--from <time>] [--to <time>]
+                            [--data <clicks|hourly_rollups|daily_rollups>] [--format <csv|ndjson>]";
 
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -48,39 +54,113 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Initialize the logger
     env_logger::init();
 
+    // Handle subcommands before loading the server configuration
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        return match command.as_str() {
+            "migrate" => run_migrate_command(args.get(1).map(String::as_str)).await,
+            "export" => run_export_command(&args[1..]).await,
+            _ => {
+                eprintln!("Unknown command: {}", command);
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        };
+    }
+
     // Load configuration from environment variables
     let config = Config::from_env();
 
-    // Establish Redis connection
-    let redis_conn = match establish_redis_connection(&config.redis_url).await {
-        Ok(conn) => Arc::new(Mutex::new(conn)),
+    // Establish PostgreSQL connection pool when the link store lives there
+    let pg_pool = match config.link_store.as_str() {
+        "postgres" => Some(connect_postgres(&config).await),
+        _ => None,
+    };
+
+    // Build the link store selected in the configuration
+    let link_store = build_link_store(&config, pg_pool.clone()).await;
+
+    // Build the short code strategy selected in the configuration
+    let short_code_generator = match build_short_code_generator(&config, pg_pool.clone()) {
+        Ok(generator) => generator,
         Err(e) => {
-            error!("Failed to connect to Redis: {}", e);
+            error!("Invalid short code configuration: {}", e);
             std::process::exit(1);
         }
     };
-    info!("Connected to Redis: {}", config.redis_url);
 
-    // Initialize UrlService with Redis connection, key prefix, and expiration time
-    let url_service = UrlService::new(
-        redis_conn.clone(),
-        "short_url:".to_string(),
-        7 * 24 * 60 * 60, // 7 days in seconds
+    // Allocate generated codes through the store's unique constraint
+    let code_allocator = CodeAllocator::new(
+        short_code_generator,
+        config.short_code_max_attempts,
+        config.short_code_growth_threshold,
+        100, // insert attempts per collision rate measurement
     );
 
-    // Establish PostgreSQL connection pool
-    let pg_pool = match establish_postgres_connection().await {
-        Ok(pool) => Arc::new(pool),
+    // Validate custom aliases, keeping them clear of the registered routes
+    let alias_policy = match build_alias_policy(&config) {
+        Ok(policy) => policy,
         Err(e) => {
-            error!("Failed to connect to PostgreSQL: {}", e);
+            error!("Invalid alias configuration: {}", e);
             std::process::exit(1);
         }
     };
-    info!("Connected to PostgreSQL database");
 
-    // Clone pool and other shared data for use in the closure
-    let pg_pool_clone = pg_pool.clone();
-    let url_service_clone = url_service.clone();
+    // Pick the redirect status and caching headers of each link
+    let redirect_policy =
+        match RedirectPolicy::new(config.redirect_status, config.redirect_cache_max_age) {
+            Ok(policy) => policy,
+            Err(e) => {
+                error!("Invalid redirect configuration: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+    // Load the GeoIP database resolving visitor countries for routing rules and clicks
+    let geoip = build_geoip(&config);
+
+    // Sign unlock cookies of password-protected links
+    let unlock_tokens = build_unlock_tokens(&config);
+
+    // Store clicks in PostgreSQL, or in memory next to an in-memory link store
+    let click_store = build_click_store(&config, pg_pool.clone());
+
+    // Count distinct visitors in daily Redis sketches saved to PostgreSQL
+    let (visitor_store, sketch_store) = build_visitor_store(&config, pg_pool.clone()).await;
+
+    // Roll up and purge clicks and save visitor sketches in the background
+    let job_runner = JobRunner::start(build_jobs(&config, pg_pool.clone(), sketch_store.clone()));
+
+    // Authenticate the accounts owning links
+    let accounts = build_accounts(&config);
+
+    // Stream clicks live to the subscribers of every instance
+    let click_stream = build_click_stream(&config).await;
+
+    // Record a click event for every redirect in the background
+    let (click_recorder, click_worker) = build_click_recorder(
+        &config,
+        click_store.clone(),
+        visitor_store.clone(),
+        click_stream.clone(),
+        &geoip,
+    );
+
+    // Without a click store nothing is recorded, so stats are always empty
+    let click_store = click_store.unwrap_or_else(|| Arc::new(MemoryClickStore::new()));
+
+    // Clone shared data for use in the closure
+    let link_store_clone = link_store.clone();
+    let unlock_tokens_clone = unlock_tokens.clone();
+    let code_allocator_clone = code_allocator.clone();
+    let alias_policy_clone = alias_policy.clone();
+    let redirect_policy_clone = redirect_policy.clone();
+    let geoip_clone = geoip.clone();
+    let click_recorder_clone = click_recorder.clone();
+    let click_store_clone = click_store.clone();
+    let visitor_store_clone = visitor_store.clone();
+    let click_stream_clone = click_stream.clone();
+    let accounts_clone = accounts.clone();
     let host_url = config.host_url.clone();
 
     // Start HTTP server
@@ -126,10 +206,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
         App::new()
             .document(spec)
-            // Add PgPool to application data
-            .app_data(web::Data::new(pg_pool_clone.clone()))
-            // Add UrlService to application data
-            .app_data(web::Data::new(url_service_clone.clone()))
+            // Add the link store to application data
+            .app_data(web::Data::new(link_store_clone.clone()))
+            // Add the unlock token signer to application data
+            .app_data(web::Data::new(unlock_tokens_clone.clone()))
+            // Add the short code allocator to application data
+            .app_data(web::Data::new(code_allocator_clone.clone()))
+            // Add the custom alias rules to application data
+            .app_data(web::Data::new(alias_policy_clone.clone()))
+            // Add the redirect policy to application data
+            .app_data(web::Data::new(redirect_policy_clone.clone()))
+            // Add the GeoIP database to application data
+            .app_data(web::Data::new(geoip_clone.clone()))
+            // Add the click recorder to application data
+            .app_data(web::Data::new(click_recorder_clone.clone()))
+            // Add the click store to application data
+            .app_data(web::Data::new(click_store_clone.clone()))
+            // Add the visitor store to application data
+            .app_data(web::Data::new(visitor_store_clone.clone()))
+            // Add the click stream to application data
+            .app_data(web::Data::new(click_stream_clone.clone()))
+            // Add the accounts to application data
+            .app_data(web::Data::new(accounts_clone.clone()))
             // Add host_url to application data
             .app_data(web::Data::new(host_url.clone()))
             // Configure routes
@@ -139,18 +237,476 @@ async fn main() -> Result<(), Box<dyn Error>> {
             // Add Actix Web Logger middleware
             .wrap(ActixLogger::default())
             .build_with(
-                "/openapi.json/",
+                routes::OPENAPI_PATH,
                 apistos::app::BuildConfig::default()
-                    .with(SwaggerUIConfig::new(&"/docs/")),
+                    .with(SwaggerUIConfig::new(&routes::DOCS_PATH)),
             )
     })
         .bind("0.0.0.0:8080")?
         .run()
         .await?;
 
+    // Store the clicks still queued once the stopped workers let go of the recorder
+    drop(click_recorder);
+    if let Some(click_worker) = click_worker {
+        match tokio::time::timeout(Duration::from_secs(10), click_worker).await {
+            Ok(Ok(())) => info!("Stored the remaining clicks"),
+            Ok(Err(e)) => error!("Click worker failed: {}", e),
+            Err(_) => warn!("Gave up waiting for the remaining clicks to be stored"),
+        }
+    }
+
+    // Let the runs in progress finish, then save the visitor sketches changed since
+    // the last periodic save
+    job_runner.shutdown().await;
+    if let Some(store) = sketch_store {
+        match store.save_sketches().await {
+            Ok(saved) => info!("Saved {} visitor sketches", saved),
+            Err(e) => error!("Failed to save visitor sketches: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the link store selected by `Config::link_store`, folding short codes to
+/// lower case when `Config::alias_case_insensitive` is set.
+/// Exits the process if a backing service cannot be reached.
+async fn build_link_store(config: &Config, pg_pool: Option<Arc<PgPool>>) -> DynLinkStore {
+    let store: DynLinkStore = match (config.link_store.as_str(), pg_pool) {
+        ("memory", _) => {
+            info!("Using in-memory link store");
+            Arc::new(MemoryLinkStore::new())
+        }
+        ("redis", _) => {
+            let redis_conn = connect_redis(&config.redis_url).await;
+            Arc::new(RedisLinkStore::new(redis_conn, "link:".to_string(), None))
+        }
+        ("postgres", Some(pg_pool)) => {
+            let redis_conn = connect_redis(&config.redis_url).await;
+
+            // Resolve links from Postgres, using Redis as a read-through cache whose
+            // entries also expire together with the link
+            let cache = RedisLinkStore::new(
+                redis_conn,
+                "short_url:".to_string(),
+                Some(config.cache_ttl_seconds),
+            );
+            Arc::new(CachedLinkStore::new(Arc::new(PgLinkStore::new(pg_pool)), cache))
+        }
+        (other, _) => {
+            error!("Unknown link store: {}", other);
+            std::process::exit(1);
+        }
+    };
+
+    if config.alias_case_insensitive {
+        info!("Treating short codes differing only in case as the same code");
+        return Arc::new(CaseFoldingLinkStore::new(store));
+    }
+    store
+}
+
+/// Builds the short code generator selected by `Config::short_code_strategy`.
+/// Counter based strategies use the Postgres sequence when a pool is available.
+/// With `Config::alias_case_insensitive` set, codes are drawn from the lower case
+/// letters of the alphabet, as the link store folds them anyway.
+fn build_short_code_generator(
+    config: &Config,
+    pg_pool: Option<Arc<PgPool>>,
+) -> Result<DynShortCodeGenerator, String> {
+    let mut alphabet = parse_alphabet(&config.short_code_alphabet)?;
+    if config.alias_case_insensitive {
+        alphabet = fold_alphabet(&alphabet);
+        if alphabet.len() < 2 {
+            return Err("SHORT_CODE_ALPHABET needs two characters differing in more than case".to_string());
+        }
+    }
+    let length = config.short_code_length;
+    if length == 0 {
+        return Err("SHORT_CODE_LENGTH must be positive".to_string());
+    }
+
+    let sequence = || match pg_pool.clone() {
+        Some(pool) => Sequence::Postgres(pool),
+        None => {
+            warn!("No PostgreSQL pool, counting short codes in process memory");
+            Sequence::memory(1)
+        }
+    };
+
+    let generator: DynShortCodeGenerator = match config.short_code_strategy.as_str() {
+        "random" => Arc::new(RandomGenerator::new(alphabet, length)),
+        "sequential" => Arc::new(SequentialGenerator::new(sequence(), alphabet)),
+        "hashids" => Arc::new(HashidsGenerator::new(
+            sequence(),
+            alphabet,
+            &config.short_code_salt,
+            length,
+        )),
+        "content_hash" => Arc::new(ContentHashGenerator::new(alphabet, length)),
+        other => return Err(format!("unknown short code strategy: {}", other)),
+    };
+    info!("Using {} short codes", config.short_code_strategy);
+    Ok(generator)
+}
+
+/// Builds the custom alias rules, reserving the paths of registered routes
+/// together with `Config::alias_reserved_words`.
+fn build_alias_policy(config: &Config) -> Result<AliasPolicy, String> {
+    let reserved = routes::reserved_words()
+        .into_iter()
+        .chain(config.alias_reserved_words.iter().cloned());
+    AliasPolicy::new(
+        &config.alias_charset,
+        config.alias_min_length,
+        config.alias_max_length,
+        reserved,
+        config.alias_case_insensitive,
+    )
+}
+
+/// Loads the GeoIP database from `Config::geoip_database`, exiting the process if it
+/// cannot be read. Without one, country conditions of routing rules never match.
+fn build_geoip(config: &Config) -> GeoIp {
+    let Some(path) = &config.geoip_database else {
+        info!("GEOIP_DATABASE is not set, country routing rules are disabled");
+        return GeoIp::disabled();
+    };
+    match GeoIp::open(path) {
+        Ok(geoip) => {
+            info!("Loaded GeoIP database: {}", path);
+            geoip
+        }
+        Err(e) => {
+            error!("Failed to load GeoIP database {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds the click store: the `clicks` table when a PostgreSQL pool is available,
+/// process memory next to the in-memory link store, and none otherwise.
+fn build_click_store(config: &Config, pg_pool: Option<Arc<PgPool>>) -> Option<DynClickStore> {
+    match (config.link_store.as_str(), pg_pool) {
+        (_, Some(pg_pool)) => Some(Arc::new(PgClickStore::new(pg_pool))),
+        ("memory", None) => {
+            info!("Using in-memory click store");
+            Some(Arc::new(MemoryClickStore::new()))
+        }
+        _ => {
+            info!("No PostgreSQL pool, clicks are not recorded");
+            None
+        }
+    }
+}
+
+/// Builds the visitor store: HyperLogLog sketches in Redis, saved to PostgreSQL,
+/// when a pool is available and exact counts in process memory otherwise. The Redis
+/// store is also returned on its own to save its sketches.
+async fn build_visitor_store(
+    config: &Config,
+    pg_pool: Option<Arc<PgPool>>,
+) -> (DynVisitorStore, Option<RedisVisitorStore>) {
+    match pg_pool {
+        Some(pg_pool) => {
+            let redis_conn = connect_redis(&config.redis_url).await;
+            let store = RedisVisitorStore::new(
+                redis_conn,
+                "short_url:".to_string(),
+                config.visitor_sketch_ttl_seconds,
+                PgSketchArchive::new(pg_pool),
+            );
+            (Arc::new(store.clone()), Some(store))
+        }
+        None => (Arc::new(MemoryVisitorStore::new()), None),
+    }
+}
+
+/// Builds the background jobs: rolling up clicks when they are stored in PostgreSQL,
+/// purging them after `Config::click_retention_days` if set, and saving the sketches
+/// of the Redis visitor store.
+fn build_jobs(
+    config: &Config,
+    pg_pool: Option<Arc<PgPool>>,
+    sketch_store: Option<RedisVisitorStore>,
+) -> Vec<Arc<dyn Job>> {
+    let mut jobs: Vec<Arc<dyn Job>> = Vec::new();
+    if let Some(pg_pool) = pg_pool {
+        jobs.push(Arc::new(ClickRollupJob::new(
+            PgClickRollups::new(pg_pool.clone()),
+            Duration::from_secs(config.rollup_settle_seconds),
+            Duration::from_secs(config.rollup_interval_seconds),
+        )));
+        if let Some(days) = config.click_retention_days {
+            info!("Purging clicks after {} days", days);
+            jobs.push(Arc::new(ClickRetentionJob::new(
+                PgClickRollups::new(pg_pool),
+                Duration::from_secs(days * 24 * 60 * 60),
+                Duration::from_secs(config.click_retention_interval_seconds),
+            )));
+        }
+    }
+    if let Some(store) = sketch_store {
+        jobs.push(Arc::new(SketchSaveJob::new(
+            store,
+            Duration::from_secs(config.visitor_sketch_save_interval_seconds),
+        )));
+    }
+    jobs
+}
+
+/// Builds the accounts from their access tokens, exiting the process if they are invalid
+fn build_accounts(config: &Config) -> Accounts {
+    match Accounts::new(&config.api_tokens) {
+        Ok(accounts) => {
+            if accounts.is_empty() {
+                warn!("API_TOKENS is not set, nobody can watch, export or delete clicks");
+            }
+            accounts
+        }
+        Err(e) => {
+            error!("Invalid API_TOKENS: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds the click stream, fanned out through Redis unless links are kept in process
+/// memory. Exits the process if Redis cannot be reached.
+async fn build_click_stream(config: &Config) -> ClickStream {
+    let stream = ClickStream::new();
+    if config.link_store == "memory" {
+        return stream;
+    }
+
+    match stream.with_redis(&config.redis_url, config.click_stream_channel.clone()).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to connect the click stream to Redis: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Starts the click recorder writing to the click and visitor stores with the
+/// configured privacy settings, exiting the process if its configuration is invalid.
+/// Without a click store, clicks are not recorded.
+fn build_click_recorder(
+    config: &Config,
+    click_store: Option<DynClickStore>,
+    visitor_store: DynVisitorStore,
+    click_stream: ClickStream,
+    geoip: &GeoIp,
+) -> (ClickRecorder, Option<JoinHandle<()>>) {
+    let Some(click_store) = click_store else {
+        return (ClickRecorder::disabled(), None);
+    };
+
+    let ip_mode: IpMode = match config.click_ip_mode.parse() {
+        Ok(mode) => mode,
+        Err(e) => {
+            error!("Invalid click configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let ip_secret = match &config.click_ip_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            warn!("CLICK_IP_SECRET is not set, IP hashes will differ after a restart");
+            rand::random::<[u8; 32]>().to_vec()
+        }
+    };
+
+    match ClickRecorder::start(
+        click_store,
+        visitor_store,
+        click_stream,
+        geoip.clone(),
+        ClickPrivacy::new(ip_mode, &ip_secret, config.click_honor_do_not_track),
+        config.click_queue_capacity,
+        config.click_batch_size,
+        Duration::from_millis(config.click_flush_interval_ms),
+    ) {
+        Ok((recorder, worker)) => (recorder, Some(worker)),
+        Err(e) => {
+            error!("Invalid click configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Connects to PostgreSQL and applies migrations if configured, exiting the process on failure.
+async fn connect_postgres(config: &Config) -> Arc<PgPool> {
+    let pg_pool = match establish_postgres_connection().await {
+        Ok(pool) => Arc::new(pool),
+        Err(e) => {
+            error!("Failed to connect to PostgreSQL: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Connected to PostgreSQL database");
+
+    if config.run_migrations {
+        if let Err(e) = migrations::run_pending(&pg_pool).await {
+            error!("Failed to apply migrations: {}", e);
+            std::process::exit(1);
+        }
+        info!("Applied pending migrations");
+    }
+
+    pg_pool
+}
+
+/// Builds the unlock token signer from `Config::unlock_secret`.
+/// Without a configured secret, a random one is used and unlocks do not survive
+/// restarts or carry over to other instances.
+fn build_unlock_tokens(config: &Config) -> UnlockTokens {
+    match &config.unlock_secret {
+        Some(secret) => UnlockTokens::new(secret.as_bytes(), config.unlock_ttl_seconds),
+        None => {
+            warn!("UNLOCK_COOKIE_SECRET is not set, using a random secret");
+            let secret: [u8; 32] = rand::random();
+            UnlockTokens::new(&secret, config.unlock_ttl_seconds)
+        }
+    }
+}
+
+/// Runs `url_shortener migrate <up|down|status>` against DATABASE_URL.
+async fn run_migrate_command(action: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let pg_pool = establish_postgres_connection().await?;
+
+    match action {
+        Some("up") => {
+            migrations::run_pending(&pg_pool).await?;
+            println!("Applied all pending migrations");
+        }
+        Some("down") => match migrations::revert_latest(&pg_pool).await? {
+            Some(version) => println!("Reverted migration {}", version),
+            None => println!("No applied migrations to revert"),
+        },
+        Some("status") => {
+            for migration in migrations::status(&pg_pool).await? {
+                let state = if migration.applied { "applied" } else { "pending" };
+                println!("{} {:<8} {}", migration.version, state, migration.description);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
+/// Links the export command covers
+enum ExportLinks {
+    All,
+    Link(String),
+    /// Links of the account, only those carrying the tag if one is given
+    Account { account: String, tag: Option<String> },
+}
+
+/// Writes the clicks or rollup rows of a link, of an account's links, possibly only
+/// those with a tag, or of all links, from PostgreSQL to standard output as CSV or
+/// NDJSON, page by page. Links are looked up through the link store the server is
+/// configured with, so it resolves the same codes.
+async fn run_export_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (links, query) = match parse_export_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let config = Config::from_env();
+    let pg_pool = Arc::new(establish_postgres_connection().await?);
+    let link_store = build_link_store(&config, Some(pg_pool.clone())).await;
+
+    let links = match &links {
+        ExportLinks::All => None,
+        ExportLinks::Link(short_code) => match link_store.get_by_code(short_code).await? {
+            Some(link) => Some(vec![link.id]),
+            None => {
+                eprintln!("Short link not found: {}", short_code);
+                std::process::exit(1);
+            }
+        },
+        ExportLinks::Account { account, tag } => {
+            let links = link_store.list_owned(account, tag.as_deref()).await?;
+            Some(links.into_iter().map(|link| link.id).collect())
+        }
+    };
+    let export = Export::from_query(&query, links)?;
+
+    let mut chunks = Box::pin(export.stream(Arc::new(PgClickStore::new(pg_pool))));
+    let mut stdout = tokio::io::stdout();
+    while let Some(chunk) = chunks.next().await {
+        stdout.write_all(&chunk?).await?;
+    }
+    stdout.flush().await?;
     Ok(())
 }
 
+/// Parses the options of the export command into the links it covers and the query
+/// of the export
+fn parse_export_args(args: &[String]) -> Result<(ExportLinks, ExportQuery), String> {
+    let (mut short_code, mut account, mut tag) = (None, None, None);
+    let mut query = ExportQuery { from: None, to: None, data: None, format: None };
+    let mut args = args.iter();
+    while let Some(option) = args.next() {
+        let value = args.next().ok_or_else(|| format!("Missing value of {}", option))?;
+        let invalid = |e: &dyn std::fmt::Display| format!("Invalid value of {}: {}", option, e);
+        match option.as_str() {
+            "--link" => short_code = Some(value.clone()),
+            "--account" => account = Some(value.clone()),
+            "--tag" => tag = Some(value.clone()),
+            "--from" | "--to" => {
+                let time = chrono::DateTime::parse_from_rfc3339(value)
+                    .map_err(|e| invalid(&e))?
+                    .with_timezone(&chrono::Utc);
+                if option == "--from" {
+                    query.from = Some(time);
+                } else {
+                    query.to = Some(time);
+                }
+            }
+            // Named as in the query parameters of the export endpoints
+            "--data" => {
+                query.data = Some(serde_json::from_value(value.as_str().into()).map_err(|e| invalid(&e))?)
+            }
+            "--format" => {
+                query.format = Some(serde_json::from_value(value.as_str().into()).map_err(|e| invalid(&e))?)
+            }
+            _ => return Err(format!("Unknown option: {}", option)),
+        }
+    }
+    let links = match (short_code, account, tag) {
+        (None, None, None) => ExportLinks::All,
+        (Some(short_code), None, None) => ExportLinks::Link(short_code),
+        (None, Some(account), tag) => ExportLinks::Account { account, tag },
+        (Some(_), _, _) => return Err("--link can't be combined with --account or --tag".to_string()),
+        (None, None, Some(_)) => return Err("--tag needs the --account whose links it selects".to_string()),
+    };
+    Ok((links, query))
+}
+
+/// Connects to Redis, exiting the process on failure.
+async fn connect_redis(redis_url: &str) -> Arc<Mutex<MultiplexedConnection>> {
+    match establish_redis_connection(redis_url).await {
+        Ok(conn) => {
+            info!("Connected to Redis: {}", redis_url);
+            Arc::new(Mutex::new(conn))
+        }
+        Err(e) => {
+            error!("Failed to connect to Redis: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Establishes a Redis connection using the provided Redis URL.
 /// Returns a MultiplexedConnection on success.
 async fn establish_redis_connection(redis_url: &str) -> redis::RedisResult<MultiplexedConnection> {
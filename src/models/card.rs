@@ -0,0 +1,51 @@
+// src/models/card.rs
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Longest title and description shown in a link's unfurl
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+/// Open Graph and Twitter card metadata served to unfurl bots instead of the
+/// destination's own
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SocialCard {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Absolute http(s) URL of the preview image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl SocialCard {
+    /// Checks the lengths of the texts and the image URL
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.is_none() && self.description.is_none() && self.image.is_none() {
+            return Err("A card needs a title, description or image".to_string());
+        }
+        if self.title.as_ref().is_some_and(|title| title.chars().count() > MAX_TITLE_LENGTH) {
+            return Err(format!("Card titles are limited to {} characters", MAX_TITLE_LENGTH));
+        }
+        if self
+            .description
+            .as_ref()
+            .is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH)
+        {
+            return Err(format!(
+                "Card descriptions are limited to {} characters",
+                MAX_DESCRIPTION_LENGTH
+            ));
+        }
+        if let Some(image) = &self.image {
+            let url = url::Url::parse(image)
+                .map_err(|e| format!("Invalid card image {}: {}", image, e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!("Card images must be http(s) URLs: {}", image));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+// src/models/click.rs
+
+use apistos::ApiComponent;
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// One redirect of a short link, as stored for analytics
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema)]
+pub struct ClickEvent {
+    /// Link the click belongs to; a short code deleted and created again gets a new ID
+    pub link_id: Uuid,
+    pub short_code: String,
+    pub occurred_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    /// Host of the referrer, such as `news.ycombinator.com`
+    pub referrer_host: Option<String>,
+    pub user_agent: Option<String>,
+    /// Browser family derived from the User-Agent, such as `Chrome`
+    pub browser: Option<String>,
+    /// Operating system derived from the User-Agent, such as `ios`
+    pub os: Option<String>,
+    /// Kind of device derived from the User-Agent, such as `mobile`
+    pub device: Option<String>,
+    /// Identifies the visitor as the IP mode allows: a keyed hash of their IP address
+    /// or its network; the address itself is never stored
+    pub ip_hash: Option<String>,
+    /// ISO 3166-1 alpha-2 code of the visitor's country
+    pub country: Option<String>,
+    /// Variant of the link's split the visitor was sent to
+    pub variant: Option<String>,
+    /// Why the click was classified as a bot's, such as `user_agent:googlebot`;
+    /// `None` for people
+    pub bot_reason: Option<String>,
+}
+
+impl ClickEvent {
+    pub fn is_bot(&self) -> bool {
+        self.bot_reason.is_some()
+    }
+}
+
+/// A click as sent to subscribers of the click stream, without the fields that
+/// identify its visitor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StreamedClick {
+    pub link_id: Uuid,
+    pub short_code: String,
+    pub occurred_at: DateTime<Utc>,
+    pub referrer_host: Option<String>,
+    pub browser: Option<String>,
+    pub os: Option<String>,
+    pub device: Option<String>,
+    pub country: Option<String>,
+    pub variant: Option<String>,
+    pub bot_reason: Option<String>,
+    /// Account owning the link, the only one the click is streamed to
+    pub owner: Option<String>,
+}
+
+impl StreamedClick {
+    /// The click of a link owned by the given account
+    pub fn new(click: &ClickEvent, owner: Option<String>) -> Self {
+        Self {
+            link_id: click.link_id,
+            short_code: click.short_code.clone(),
+            occurred_at: click.occurred_at,
+            referrer_host: click.referrer_host.clone(),
+            browser: click.browser.clone(),
+            os: click.os.clone(),
+            device: click.device.clone(),
+            country: click.country.clone(),
+            variant: click.variant.clone(),
+            bot_reason: click.bot_reason.clone(),
+            owner,
+        }
+    }
+}
+
+/// Which clicks stats count, by their bot classification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BotFilter {
+    /// Only clicks of people
+    #[default]
+    Exclude,
+    /// Clicks of people and bots
+    Include,
+    /// Only clicks of bots
+    Only,
+}
+
+impl BotFilter {
+    /// Returns true if the click is counted
+    pub fn matches(&self, click: &ClickEvent) -> bool {
+        match self {
+            BotFilter::Exclude => !click.is_bot(),
+            BotFilter::Include => true,
+            BotFilter::Only => click.is_bot(),
+        }
+    }
+}
+
+/// Clicks of a link from `from` (inclusive) to `to` (exclusive)
+#[derive(Debug, Clone, Copy)]
+pub struct ClickQuery {
+    pub link_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub bots: BotFilter,
+}
+
+impl ClickQuery {
+    /// Returns true if the click is part of the query
+    pub fn matches(&self, click: &ClickEvent) -> bool {
+        click.link_id == self.link_id
+            && self.from <= click.occurred_at
+            && click.occurred_at < self.to
+            && self.bots.matches(click)
+    }
+}
+
+/// Width of the buckets of a click time series
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    Hour,
+    #[default]
+    Day,
+    /// Weeks starting on Monday
+    Week,
+}
+
+impl Interval {
+    /// Field name understood by PostgreSQL's `date_trunc`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::Hour => "hour",
+            Interval::Day => "day",
+            Interval::Week => "week",
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            Interval::Hour => Duration::hours(1),
+            Interval::Day => Duration::days(1),
+            Interval::Week => Duration::weeks(1),
+        }
+    }
+
+    /// Start of the bucket the time falls into, in UTC
+    pub fn truncate(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Interval::Hour => time.duration_trunc(Duration::hours(1)).unwrap_or(time),
+            Interval::Day => time.duration_trunc(Duration::days(1)).unwrap_or(time),
+            Interval::Week => {
+                let day = time.duration_trunc(Duration::days(1)).unwrap_or(time);
+                day - Duration::days(i64::from(day.weekday().num_days_from_monday()))
+            }
+        }
+    }
+}
+
+/// Click attribute the top values are counted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickDimension {
+    ReferrerHost,
+    Country,
+    Browser,
+    Os,
+    Device,
+    Variant,
+}
+
+impl ClickDimension {
+    pub const ALL: [ClickDimension; 6] = [
+        ClickDimension::ReferrerHost,
+        ClickDimension::Country,
+        ClickDimension::Browser,
+        ClickDimension::Os,
+        ClickDimension::Device,
+        ClickDimension::Variant,
+    ];
+
+    /// Column of the `clicks` table holding the attribute
+    pub fn column(&self) -> &'static str {
+        match self {
+            ClickDimension::ReferrerHost => "referrer_host",
+            ClickDimension::Country => "country",
+            ClickDimension::Browser => "browser",
+            ClickDimension::Os => "os",
+            ClickDimension::Device => "device",
+            ClickDimension::Variant => "variant",
+        }
+    }
+
+    /// Value of the attribute for the click
+    pub fn value_of<'a>(&self, click: &'a ClickEvent) -> Option<&'a str> {
+        match self {
+            ClickDimension::ReferrerHost => click.referrer_host.as_deref(),
+            ClickDimension::Country => click.country.as_deref(),
+            ClickDimension::Browser => click.browser.as_deref(),
+            ClickDimension::Os => click.os.as_deref(),
+            ClickDimension::Device => click.device.as_deref(),
+            ClickDimension::Variant => click.variant.as_deref(),
+        }
+    }
+}
+
+/// Clicks and distinct visitors, counted by IP address hash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ClickCount {
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+/// Clicks of one bucket of a time series
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ClickBucket {
+    /// Start of the bucket, in UTC
+    pub start: DateTime<Utc>,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+/// A value of a click attribute and how many clicks had it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TopValue {
+    pub value: String,
+    pub clicks: i64,
+}
+
+/// Serialized HyperLogLog sketch of the distinct visitors of a link on one UTC day
+#[derive(Debug, Clone, FromRow)]
+pub struct VisitorSketch {
+    pub link_id: Uuid,
+    pub day: NaiveDate,
+    pub sketch: Vec<u8>,
+}
+
+/// UTC days from the one `from` falls on to the one just before `to`
+pub fn days_between(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<NaiveDate> {
+    let last = (to - Duration::nanoseconds(1)).date_naive();
+    from.date_naive().iter_days().take_while(|day| *day <= last).collect()
+}
+
+/// Query parameters of a link's stats
+#[derive(Debug, Deserialize, JsonSchema, ApiComponent)]
+pub struct StatsQuery {
+    /// Start of the range, inclusive; 30 days before `to` if omitted
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive; now if omitted
+    pub to: Option<DateTime<Utc>>,
+    /// Width of the time series buckets; `day` if omitted
+    pub interval: Option<Interval>,
+    /// Entries of each top list, at most 100; 10 if omitted
+    pub limit: Option<i64>,
+    /// Whether clicks of bots are counted; `exclude` if omitted
+    pub bots: Option<BotFilter>,
+}
+
+/// Query parameters of the click stream of all links
+#[derive(Debug, Deserialize, JsonSchema, ApiComponent)]
+pub struct ClickStreamQuery {
+    /// Comma-separated short codes of the links to stream; all links if omitted
+    pub links: Option<String>,
+}
+
+/// Clicks of a short link over a time range
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct LinkStats {
+    pub short_code: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub interval: Interval,
+    pub bots: BotFilter,
+    pub total_clicks: i64,
+    /// Distinct visitors, told apart by their hashed IP address; clicks purged after
+    /// the retention period are left out
+    pub unique_clicks: i64,
+    /// Estimated distinct people over the whole UTC days the range touches, merged
+    /// from daily sketches; bots are never counted
+    pub unique_visitors: u64,
+    /// Clicks per bucket, including empty ones, oldest first
+    pub series: Vec<ClickBucket>,
+    /// Hosts of the referring pages; clicks without a referrer are left out
+    pub referrers: Vec<TopValue>,
+    pub countries: Vec<TopValue>,
+    pub browsers: Vec<TopValue>,
+    pub os: Vec<TopValue>,
+    pub devices: Vec<TopValue>,
+    /// Variants of the link's split the clicks were sent to
+    pub variants: Vec<TopValue>,
+}
+
+/// Click events deleted on request
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct DeletedClicks {
+    pub clicks: u64,
+}
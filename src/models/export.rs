@@ -0,0 +1,110 @@
+// src/models/export.rs
+
+use crate::models::click::Interval;
+use apistos::ApiComponent;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// What an export is made of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportData {
+    /// Stored click events, one row each
+    #[default]
+    Clicks,
+    /// Rollup rows with hourly buckets
+    HourlyRollups,
+    /// Rollup rows with daily buckets
+    DailyRollups,
+}
+
+impl ExportData {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportData::Clicks => "clicks",
+            ExportData::HourlyRollups => "hourly_rollups",
+            ExportData::DailyRollups => "daily_rollups",
+        }
+    }
+
+    /// Width of the rollup buckets, if the export is made of rollups
+    pub fn granularity(&self) -> Option<Interval> {
+        match self {
+            ExportData::Clicks => None,
+            ExportData::HourlyRollups => Some(Interval::Hour),
+            ExportData::DailyRollups => Some(Interval::Day),
+        }
+    }
+}
+
+/// How exported rows are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row; missing values are empty
+    #[default]
+    Csv,
+    /// One JSON object per line; missing values are `null`
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Query parameters of an export
+#[derive(Debug, Deserialize, JsonSchema, ApiComponent)]
+pub struct ExportQuery {
+    /// Start of the range, inclusive; 30 days before `to` if omitted
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive; now if omitted
+    pub to: Option<DateTime<Utc>>,
+    /// `clicks`, `hourly_rollups` or `daily_rollups`; `clicks` if omitted
+    pub data: Option<ExportData>,
+    /// `csv` or `ndjson`; `csv` if omitted
+    pub format: Option<ExportFormat>,
+}
+
+/// Clicks or rollup rows an export covers
+#[derive(Debug, Clone)]
+pub struct ExportScope {
+    /// Links exported, or every link if `None`
+    pub links: Option<Vec<Uuid>>,
+    /// Start of the range, inclusive; rollups are exported by the start of their bucket
+    pub from: DateTime<Utc>,
+    /// End of the range, exclusive
+    pub to: DateTime<Utc>,
+}
+
+/// Position of a click in the order clicks are exported in: its time, then the
+/// order it was stored in
+pub type ClickPosition = (DateTime<Utc>, i64);
+
+/// Clicks of a link in one bucket, as kept in the rollup tables
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ClickRollup {
+    pub link_id: Uuid,
+    pub bucket: DateTime<Utc>,
+    pub is_bot: bool,
+    /// Attribute the clicks are counted by, such as `country`, or `total` for all of them
+    pub dimension: String,
+    /// Value of the attribute; empty for `total`
+    pub value: String,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
@@ -1,20 +1,218 @@
+use crate::models::card::SocialCard;
+use crate::models::rule::RoutingRule;
+use crate::models::variant::Variant;
+use crate::utils::visitor::Visitor;
 use apistos::ApiComponent;
 use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
+
+/// HTTP statuses a link may redirect with
+pub const REDIRECT_STATUSES: [i16; 4] = [301, 302, 307, 308];
+
+/// What a redirect forwards from the request URL to the destination
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Passthrough {
+    /// The destination is used as is
+    #[default]
+    None,
+    /// The query string is merged into the destination's
+    Query,
+    /// Path segments after the short code are appended to the destination's path,
+    /// and the query string is merged
+    PathAndQuery,
+}
+
 /// Represents a shortened URL
-#[derive(Debug, Serialize, Deserialize, FromRow, JsonSchema, ApiComponent)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema, ApiComponent)]
 pub struct Link {
     pub id: Uuid,
     pub original_url: String,
     pub short_code: String,
     pub created_at: DateTime<Utc>,
+    /// Time the link starts redirecting; always if unset
+    #[serde(default)]
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Destination while the link is not yet started, expired, deactivated or exhausted
+    #[serde(default)]
+    pub fallback_url: Option<String>,
+    pub password_hash: Option<String>,
+    pub click_limit: Option<i32>,
+    /// Clicks consumed so far; only tracked for links with a `click_limit`
+    #[serde(default)]
+    pub click_count: i32,
+    /// Status of the redirect; `None` uses the deployment default
+    #[serde(default)]
+    pub redirect_status: Option<i16>,
+    #[serde(default)]
+    pub passthrough: Passthrough,
+    /// Rules sending matching visitors elsewhere, evaluated in order
+    #[serde(default)]
+    #[sqlx(json)]
+    pub rules: Vec<RoutingRule>,
+    /// Weighted destinations splitting visitors no rule matched
+    #[serde(default)]
+    #[sqlx(json)]
+    pub variants: Vec<Variant>,
+    /// Metadata shown when the link is unfurled, instead of the destination's
+    #[serde(default)]
+    #[sqlx(json)]
+    pub card: Option<SocialCard>,
+    pub is_active: bool,
+    /// Account that created the link with its access token; `None` for links created
+    /// without one, which no account can watch or export
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Labels grouping the link with others, such as a campaign
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Link {
+    /// Returns true while the link's `starts_at` is in the future
+    pub fn is_scheduled(&self) -> bool {
+        self.starts_at.is_some_and(|starts_at| starts_at > Utc::now())
+    }
+
+    /// Returns true once the link's `expires_at` has passed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    /// Returns true once all clicks allowed by `click_limit` have been consumed
+    pub fn is_exhausted(&self) -> bool {
+        self.click_limit.is_some_and(|limit| self.click_count >= limit)
+    }
+
+    /// Returns true if the link carries the tag
+    pub fn is_tagged(&self, tag: &str) -> bool {
+        self.tags.iter().any(|link_tag| link_tag == tag)
+    }
+
+    /// First rule matching the visitor, if any
+    pub fn rule_for(&self, visitor: &Visitor) -> Option<&RoutingRule> {
+        self.rules.iter().find(|rule| rule.matches(visitor))
+    }
+
+    /// Variant of the split with the given name, if it still receives visitors
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants
+            .iter()
+            .find(|variant| variant.name == name && variant.weight > 0)
+    }
+}
+
+/// A link that has not been stored yet
+#[derive(Debug, Clone)]
+pub struct NewLink {
+    pub original_url: String,
+    pub short_code: String,
+    pub starts_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub fallback_url: Option<String>,
     pub password_hash: Option<String>,
     pub click_limit: Option<i32>,
+    pub redirect_status: Option<i16>,
+    pub passthrough: Passthrough,
+    pub rules: Vec<RoutingRule>,
+    pub variants: Vec<Variant>,
+    pub card: Option<SocialCard>,
     pub is_active: bool,
+    pub owner: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl NewLink {
+    /// Returns true if the stored link has the destination and settings of this one,
+    /// so it can stand in for it. Password-protected links never match, as passwords
+    /// are hashed with a random salt.
+    pub fn is_stored_as(&self, link: &Link) -> bool {
+        self.original_url == link.original_url
+            && self.password_hash.is_none()
+            && link.password_hash.is_none()
+            && self.starts_at == link.starts_at
+            && self.expires_at == link.expires_at
+            && self.fallback_url == link.fallback_url
+            && self.click_limit == link.click_limit
+            && self.redirect_status == link.redirect_status
+            && self.passthrough == link.passthrough
+            && same_json(&self.rules, &link.rules)
+            && same_json(&self.variants, &link.variants)
+            && same_json(&self.card, &link.card)
+            && self.is_active == link.is_active
+            && self.owner == link.owner
+            && self.tags == link.tags
+    }
+}
+
+/// Compares values without an equality of their own by their JSON form
+fn same_json<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Changes to the mutable fields of a stored link; `None` leaves a field as it is
+/// and `Some(None)` clears it
+#[derive(Debug, Clone, Default)]
+pub struct LinkChanges {
+    pub original_url: Option<String>,
+    pub starts_at: Option<Option<DateTime<Utc>>>,
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub fallback_url: Option<Option<String>>,
+    pub password_hash: Option<Option<String>>,
+    pub click_limit: Option<Option<i32>>,
+    pub redirect_status: Option<Option<i16>>,
+    pub passthrough: Option<Passthrough>,
+    pub rules: Option<Vec<RoutingRule>>,
+    pub variants: Option<Vec<Variant>>,
+    pub card: Option<Option<SocialCard>>,
+    pub is_active: Option<bool>,
+}
+
+impl LinkChanges {
+    /// Sets the changed fields of the link
+    pub fn apply_to(&self, link: &mut Link) {
+        if let Some(original_url) = &self.original_url {
+            link.original_url = original_url.clone();
+        }
+        if let Some(starts_at) = self.starts_at {
+            link.starts_at = starts_at;
+        }
+        if let Some(expires_at) = self.expires_at {
+            link.expires_at = expires_at;
+        }
+        if let Some(fallback_url) = &self.fallback_url {
+            link.fallback_url = fallback_url.clone();
+        }
+        if let Some(password_hash) = &self.password_hash {
+            link.password_hash = password_hash.clone();
+        }
+        if let Some(click_limit) = self.click_limit {
+            link.click_limit = click_limit;
+        }
+        if let Some(redirect_status) = self.redirect_status {
+            link.redirect_status = redirect_status;
+        }
+        if let Some(passthrough) = self.passthrough {
+            link.passthrough = passthrough;
+        }
+        if let Some(rules) = &self.rules {
+            link.rules = rules.clone();
+        }
+        if let Some(variants) = &self.variants {
+            link.variants = variants.clone();
+        }
+        if let Some(card) = &self.card {
+            link.card = card.clone();
+        }
+        if let Some(is_active) = self.is_active {
+            link.is_active = is_active;
+        }
+    }
 }
 
 /// Payload for creating a new short link
@@ -22,9 +220,28 @@ pub struct Link {
 pub struct CreateLinkRequest {
     pub original_url: String,
     pub custom_alias: Option<String>,
+    /// Time the link starts redirecting, for links created ahead of time
+    pub starts_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// Destination while the link is not yet started, expired, deactivated or exhausted
+    pub fallback_url: Option<String>,
     pub password: Option<String>,
     pub click_limit: Option<i32>,
+    /// Redirect status: 301, 302, 307 or 308; the deployment default if omitted
+    pub redirect_status: Option<i16>,
+    /// Parts of the request URL forwarded to the destination; `none` if omitted
+    pub passthrough: Option<Passthrough>,
+    /// Rules sending matching visitors elsewhere; the first matching rule wins and
+    /// `original_url` serves everyone else
+    pub rules: Option<Vec<RoutingRule>>,
+    /// Weighted destinations splitting visitors no rule matched; each visitor keeps
+    /// their variant
+    pub variants: Option<Vec<Variant>>,
+    /// Title, description and image shown when the link is unfurled in chats and
+    /// social networks
+    pub card: Option<SocialCard>,
+    /// Labels grouping the link with others, whose clicks can then be exported together
+    pub tags: Option<Vec<String>>,
 }
 
 /// Response after creating a new short link
@@ -32,4 +249,123 @@ pub struct CreateLinkRequest {
 pub struct CreateLinkResponse {
     pub short_url: String,
     pub expires_at: Option<DateTime<Utc>>,
-}
\ No newline at end of file
+}
+
+/// Payload for updating a short link; omitted fields are left unchanged and
+/// `null` clears the schedule, expiry, fallback, click limit, password, redirect status or card
+#[derive(Debug, Default, Deserialize, JsonSchema, ApiComponent)]
+pub struct UpdateLinkRequest {
+    pub original_url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub starts_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub fallback_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub click_limit: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub password: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub redirect_status: Option<Option<i16>>,
+    pub passthrough: Option<Passthrough>,
+    /// Replaces all routing rules; an empty list removes them
+    pub rules: Option<Vec<RoutingRule>>,
+    /// Replaces the split; an empty list ends it
+    pub variants: Option<Vec<Variant>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub card: Option<Option<SocialCard>>,
+    pub is_active: Option<bool>,
+}
+
+/// Query parameters for listing short links
+#[derive(Debug, Deserialize, JsonSchema, ApiComponent)]
+pub struct ListLinksQuery {
+    /// Page number, starting at 1
+    pub page: Option<i64>,
+    /// Links per page, at most 100
+    pub per_page: Option<i64>,
+}
+
+/// Details of a short link returned by the management API
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct LinkInfo {
+    pub short_code: String,
+    pub short_url: String,
+    /// Left out of password-protected links, whose destination is only revealed to
+    /// visitors unlocking them
+    pub original_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub fallback_url: Option<String>,
+    pub click_limit: Option<i32>,
+    pub click_count: i32,
+    pub redirect_status: Option<i16>,
+    pub passthrough: Passthrough,
+    /// Left out of password-protected links, like `original_url`
+    pub rules: Option<Vec<RoutingRule>>,
+    /// Left out of password-protected links, like `original_url`
+    pub variants: Option<Vec<Variant>>,
+    pub card: Option<SocialCard>,
+    pub tags: Vec<String>,
+    pub is_active: bool,
+    pub password_protected: bool,
+    pub scheduled: bool,
+    pub expired: bool,
+    pub exhausted: bool,
+}
+
+impl LinkInfo {
+    /// Describes the link, which is served under `short_url`, without the
+    /// destinations of password-protected links
+    pub fn new(link: Link, short_url: String) -> Self {
+        let protected = link.password_hash.is_some();
+        Self {
+            scheduled: link.is_scheduled(),
+            expired: link.is_expired(),
+            exhausted: link.is_exhausted(),
+            password_protected: protected,
+            short_code: link.short_code,
+            short_url,
+            original_url: Some(link.original_url).filter(|_| !protected),
+            created_at: link.created_at,
+            starts_at: link.starts_at,
+            expires_at: link.expires_at,
+            fallback_url: link.fallback_url,
+            click_limit: link.click_limit,
+            click_count: link.click_count,
+            redirect_status: link.redirect_status,
+            passthrough: link.passthrough,
+            rules: Some(link.rules).filter(|_| !protected),
+            variants: Some(link.variants).filter(|_| !protected),
+            card: link.card,
+            tags: link.tags,
+            is_active: link.is_active,
+        }
+    }
+}
+
+/// A page of short links, newest first
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct LinkList {
+    pub items: Vec<LinkInfo>,
+    pub page: i64,
+    pub per_page: i64,
+    pub has_more: bool,
+}
+
+/// Form submitted from the unlock page of a password-protected link
+#[derive(Debug, Deserialize, JsonSchema, ApiComponent)]
+pub struct UnlockForm {
+    pub password: String,
+}
+
+/// Distinguishes an explicit `null` (`Some(None)`) from an omitted field (`None`)
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
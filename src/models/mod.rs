@@ -2,5 +2,10 @@
 
 pub mod url;
 pub mod link;
+pub mod rule;
+pub mod variant;
+pub mod card;
+pub mod click;
+pub mod export;
 
 pub use url::{ShortenRequest, ShortenResponse};
\ No newline at end of file
@@ -0,0 +1,167 @@
+// src/models/rule.rs
+
+use crate::utils::visitor::Visitor;
+use chrono::{Datelike, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Operating system of a visitor, derived from the User-Agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Os {
+    Ios,
+    Android,
+    Windows,
+    Macos,
+    Linux,
+    Chromeos,
+}
+
+impl Os {
+    /// Name of the operating system as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Os::Ios => "ios",
+            Os::Android => "android",
+            Os::Windows => "windows",
+            Os::Macos => "macos",
+            Os::Linux => "linux",
+            Os::Chromeos => "chromeos",
+        }
+    }
+}
+
+/// Kind of device of a visitor, derived from the User-Agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Mobile,
+    Tablet,
+    Desktop,
+    Bot,
+    Other,
+}
+
+impl Device {
+    /// Name of the kind of device as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Device::Mobile => "mobile",
+            Device::Tablet => "tablet",
+            Device::Desktop => "desktop",
+            Device::Bot => "bot",
+            Device::Other => "other",
+        }
+    }
+}
+
+/// Time of day, in a time zone, during which a rule applies.
+///
+/// A window whose `end` is before its `start` runs past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimeWindow {
+    /// IANA time zone, such as `Europe/Berlin`
+    pub timezone: String,
+    /// Local time the window opens, such as `18:00`
+    pub start: NaiveTime,
+    /// Local time the window closes
+    pub end: NaiveTime,
+    /// Local days the window applies on, every day if empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+}
+
+impl TimeWindow {
+    /// Returns true if the visit falls inside the window
+    fn contains(&self, visitor: &Visitor) -> bool {
+        let Ok(timezone) = self.timezone.parse::<Tz>() else {
+            return false;
+        };
+        let local = visitor.time.with_timezone(&timezone);
+        if !self.days.is_empty() && !self.days.contains(&local.weekday()) {
+            return false;
+        }
+
+        let time = local.time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Sends visitors matching every condition to another destination.
+///
+/// Empty conditions match everyone; a condition listing several values matches
+/// any of them.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingRule {
+    /// Destination of matching visitors
+    pub destination: String,
+    /// ISO 3166-1 alpha-2 country codes, such as `DE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub countries: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<Os>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<Device>,
+    /// Language ranges matched against the visitor's preferred language; `de`
+    /// also matches `de-AT`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeWindow>,
+}
+
+impl RoutingRule {
+    /// Returns true if the visitor meets every condition of the rule
+    pub fn matches(&self, visitor: &Visitor) -> bool {
+        let country = self.countries.is_empty()
+            || visitor.country.as_deref().is_some_and(|country| {
+                self.countries.iter().any(|c| c.eq_ignore_ascii_case(country))
+            });
+        let os = self.os.is_empty() || visitor.os.is_some_and(|os| self.os.contains(&os));
+        let device = self.devices.is_empty() || self.devices.contains(&visitor.device);
+        let language = self.languages.is_empty()
+            || visitor.language.as_deref().is_some_and(|language| {
+                self.languages.iter().any(|range| language_matches(range, language))
+            });
+        let time = match &self.time {
+            Some(window) => window.contains(visitor),
+            None => true,
+        };
+
+        country && os && device && language && time
+    }
+
+    /// Checks the destination, country codes and time zone of the rule
+    pub fn validate(&self) -> Result<(), String> {
+        url::Url::parse(&self.destination)
+            .map_err(|e| format!("Invalid rule destination {}: {}", self.destination, e))?;
+        if let Some(country) = self
+            .countries
+            .iter()
+            .find(|c| c.len() != 2 || !c.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            return Err(format!("Invalid country code: {}", country));
+        }
+        if let Some(window) = &self.time {
+            window
+                .timezone
+                .parse::<Tz>()
+                .map_err(|_| format!("Unknown time zone: {}", window.timezone))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the language tag falls under the range, ignoring case:
+/// `de` matches `de` and `de-AT`, but not `del`
+fn language_matches(range: &str, tag: &str) -> bool {
+    let range = range.trim();
+    tag.get(..range.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(range))
+        && matches!(tag.as_bytes().get(range.len()), None | Some(b'-'))
+}
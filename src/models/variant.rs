@@ -0,0 +1,80 @@
+// src/models/variant.rs
+
+use apistos::ApiComponent;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// One destination of a link's split, receiving visitors in proportion to its weight
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Variant {
+    /// Name identifying the variant in the assignment cookie and the stats
+    pub name: String,
+    pub destination: String,
+    /// Share of visitors relative to the other variants' weights
+    pub weight: u32,
+}
+
+/// Checks the names, destinations and weights of a split
+pub fn validate_variants(variants: &[Variant]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for variant in variants {
+        let valid_name = !variant.name.is_empty()
+            && variant
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(format!("Invalid variant name: {:?}", variant.name));
+        }
+        if !names.insert(variant.name.as_str()) {
+            return Err(format!("Duplicate variant name: {}", variant.name));
+        }
+        url::Url::parse(&variant.destination)
+            .map_err(|e| format!("Invalid variant destination {}: {}", variant.destination, e))?;
+    }
+    if !variants.is_empty() && variants.iter().all(|variant| variant.weight == 0) {
+        return Err("At least one variant needs a positive weight".to_string());
+    }
+    Ok(())
+}
+
+/// Picks a variant at random, in proportion to the weights
+pub fn pick_weighted<'a, R: Rng>(variants: &'a [Variant], rng: &mut R) -> Option<&'a Variant> {
+    let total: u64 = variants.iter().map(|variant| u64::from(variant.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = rng.gen_range(0..total);
+    variants.iter().find(|variant| {
+        let weight = u64::from(variant.weight);
+        if point < weight {
+            true
+        } else {
+            point -= weight;
+            false
+        }
+    })
+}
+
+/// Redirects counted for one variant of a split
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct VariantStats {
+    pub name: String,
+    /// Left out of password-protected links
+    pub destination: Option<String>,
+    pub weight: u32,
+    pub clicks: i64,
+    /// Fraction of the experiment's clicks that went to this variant
+    pub share: f64,
+}
+
+/// Results of a link's split
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct ExperimentStats {
+    pub short_code: String,
+    pub total_clicks: i64,
+    pub variants: Vec<VariantStats>,
+}
@@ -0,0 +1,308 @@
+// src/routes/clicks.rs
+
+use crate::errors::ServiceError;
+use crate::models::click::{
+    days_between, ClickBucket, ClickDimension, ClickQuery, ClickStreamQuery, DeletedClicks,
+    LinkStats, StatsQuery,
+};
+use crate::models::export::ExportQuery;
+use crate::models::link::Link;
+use crate::services::clicks::{ClickPipelineStats, ClickRecorder};
+use crate::services::{Accounts, ClickStream, Export};
+use crate::store::{DynClickStore, DynLinkStore, DynVisitorStore};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentDisposition};
+use actix_web::web;
+use actix_web::web::Json;
+use actix_web::web::Bytes;
+use actix_web::Error as ActixError;
+use actix_web::{HttpRequest, HttpResponse};
+use apistos::api_operation;
+use chrono::{Duration, Utc};
+use futures_util::Stream;
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+
+/// Default range of the stats, ending now
+const DEFAULT_RANGE_DAYS: i64 = 30;
+/// Default and maximum entries of each top list
+const DEFAULT_TOP_LIMIT: i64 = 10;
+const MAX_TOP_LIMIT: i64 = 100;
+/// Most buckets a time series may have
+const MAX_BUCKETS: i64 = 10_000;
+
+/// Handler to report how many clicks were queued, stored and dropped
+#[api_operation(tag = "api", summary = "Get the counters of the click pipeline")]
+pub async fn get_click_pipeline(clicks: web::Data<ClickRecorder>) -> Json<ClickPipelineStats> {
+    Json(clicks.stats())
+}
+
+/// Handler to report the clicks of a short link: totals, a time series and the top
+/// referrers, countries, browsers, operating systems, devices and variants
+///
+/// Clicks of bots are left out unless asked for. Unique visitors are estimated from
+/// daily sketches rather than counted from the clicks.
+#[api_operation(tag = "api", summary = "Get the click stats of a short link")]
+pub async fn get_link_stats(
+    store: web::Data<DynLinkStore>,
+    clicks: web::Data<DynClickStore>,
+    visitors: web::Data<DynVisitorStore>,
+    path: web::Path<String>,
+    query: web::Query<StatsQuery>,
+) -> Result<Json<LinkStats>, ActixError> {
+    let short_code = path.into_inner();
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_RANGE_DAYS));
+    let interval = query.interval.unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_LIMIT).clamp(1, MAX_TOP_LIMIT);
+    let bots = query.bots.unwrap_or_default();
+
+    if from >= to {
+        return Err(ServiceError::BadRequest("from must be before to".to_string()).into());
+    }
+    let first_bucket = interval.truncate(from);
+    if (to - first_bucket).num_seconds() / interval.duration().num_seconds() >= MAX_BUCKETS {
+        return Err(ServiceError::BadRequest(format!(
+            "The range spans more than {} buckets; use a wider interval",
+            MAX_BUCKETS
+        ))
+        .into());
+    }
+
+    let link = store
+        .get_by_code(&short_code)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Short link not found: {}", short_code)))?;
+
+    let click_query = ClickQuery {
+        link_id: link.id,
+        from,
+        to,
+        bots,
+    };
+    let days = days_between(from, to);
+    let top = |dimension| clicks.top_values(&click_query, dimension, limit);
+    let (count, unique_visitors, buckets, referrers, countries, browsers, os, devices, variants) = tokio::try_join!(
+        clicks.count_clicks(&click_query),
+        visitors.count_visitors(link.id, &days),
+        clicks.click_series(&click_query, interval),
+        top(ClickDimension::ReferrerHost),
+        top(ClickDimension::Country),
+        top(ClickDimension::Browser),
+        top(ClickDimension::Os),
+        top(ClickDimension::Device),
+        top(ClickDimension::Variant),
+    )?;
+
+    // Fill in the buckets without clicks
+    let mut buckets: HashMap<_, _> = buckets.into_iter().map(|bucket| (bucket.start, bucket)).collect();
+    let mut series = Vec::new();
+    let mut start = first_bucket;
+    while start < to {
+        series.push(buckets.remove(&start).unwrap_or(ClickBucket {
+            start,
+            clicks: 0,
+            unique_clicks: 0,
+        }));
+        start += interval.duration();
+    }
+
+    Ok(Json(LinkStats {
+        short_code: link.short_code,
+        from,
+        to,
+        interval,
+        bots,
+        total_clicks: count.clicks,
+        unique_clicks: count.unique_clicks,
+        unique_visitors,
+        series,
+        referrers,
+        countries,
+        browsers,
+        os,
+        devices,
+        variants,
+    }))
+}
+
+/// Handler to delete the clicks of a short link together with their rollups and
+/// visitor sketches. Takes the access token of the link's owner.
+#[api_operation(tag = "api", summary = "Delete the click stats of a short link", skip_args = "req")]
+pub async fn delete_link_stats(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    accounts: web::Data<Accounts>,
+    clicks: web::Data<DynClickStore>,
+    visitors: web::Data<DynVisitorStore>,
+    path: web::Path<String>,
+) -> Result<Json<DeletedClicks>, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let short_code = path.into_inner();
+    let link = owned_link(&store, &accounts, &account, &short_code).await?;
+
+    let deleted = clicks.delete_link_clicks(link.id).await?;
+    visitors.delete_link(link.id).await?;
+    info!("Deleted {} clicks of short link: {}", deleted, short_code);
+
+    Ok(Json(DeletedClicks { clicks: deleted }))
+}
+
+/// Handler to delete the clicks of a visitor, identified by the `ip_hash` of their
+/// click events, on the short links of the account
+///
+/// Rollups and visitor sketches only count visitors, so they are kept.
+#[api_operation(tag = "api", summary = "Delete the clicks of a visitor", skip_args = "req")]
+pub async fn delete_visitor_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    accounts: web::Data<Accounts>,
+    clicks: web::Data<DynClickStore>,
+    path: web::Path<String>,
+) -> Result<Json<DeletedClicks>, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let links: Vec<_> = store.list_owned(&account, None).await?.into_iter().map(|link| link.id).collect();
+    let deleted = clicks.delete_visitor_clicks(&path.into_inner(), &links).await?;
+    info!("Deleted {} clicks of a visitor", deleted);
+
+    Ok(Json(DeletedClicks { clicks: deleted }))
+}
+
+/// Handler streaming the clicks of a short link as Server-Sent Events
+///
+/// Each click is sent as a `click` event whose data is the click as JSON, without
+/// the fields identifying its visitor. Takes the access token of the link's owner.
+#[api_operation(tag = "api", summary = "Stream the clicks of a short link", skip_args = "req")]
+pub async fn stream_link_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    accounts: web::Data<Accounts>,
+    stream: web::Data<ClickStream>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let link = owned_link(&store, &accounts, &account, &path.into_inner()).await?;
+
+    Ok(event_stream(stream.subscribe(account, Some(HashSet::from([link.id])))))
+}
+
+/// Handler streaming the clicks of all short links of the account, or of the listed
+/// ones, as Server-Sent Events
+#[api_operation(tag = "api", summary = "Stream the clicks of all short links", skip_args = "req")]
+pub async fn stream_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    accounts: web::Data<Accounts>,
+    stream: web::Data<ClickStream>,
+    query: web::Query<ClickStreamQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let links = match &query.links {
+        Some(short_codes) => {
+            let mut links = HashSet::new();
+            for short_code in short_codes.split(',').map(str::trim).filter(|code| !code.is_empty()) {
+                links.insert(owned_link(&store, &accounts, &account, short_code).await?.id);
+            }
+            Some(links)
+        }
+        None => None,
+    };
+
+    Ok(event_stream(stream.subscribe(account, links)))
+}
+
+/// Handler exporting the clicks or rollup rows of a short link over a time range as
+/// CSV or NDJSON, streamed as they are read
+///
+/// Columns are the fields of the click event or rollup row, in a fixed order. Takes
+/// the access token of the link's owner.
+#[api_operation(tag = "api", summary = "Export the clicks of a short link", skip_args = "req")]
+pub async fn export_link_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    clicks: web::Data<DynClickStore>,
+    accounts: web::Data<Accounts>,
+    path: web::Path<String>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let short_code = path.into_inner();
+    let link = owned_link(&store, &accounts, &account, &short_code).await?;
+
+    let export = Export::from_query(&query, Some(vec![link.id]))?;
+    Ok(export_response(export, &short_code, clicks.get_ref().clone()))
+}
+
+/// Handler exporting the clicks or rollup rows of all short links of the account
+/// over a time range as CSV or NDJSON, streamed as they are read
+#[api_operation(tag = "api", summary = "Export the clicks of all short links", skip_args = "req")]
+pub async fn export_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    clicks: web::Data<DynClickStore>,
+    accounts: web::Data<Accounts>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let links = store.list_owned(&account, None).await?.into_iter().map(|link| link.id).collect();
+    let export = Export::from_query(&query, Some(links))?;
+    Ok(export_response(export, "all", clicks.get_ref().clone()))
+}
+
+/// Handler exporting the clicks or rollup rows of the short links of the account
+/// carrying a tag over a time range as CSV or NDJSON, streamed as they are read
+#[api_operation(tag = "api", summary = "Export the clicks of tagged short links", skip_args = "req")]
+pub async fn export_tag_clicks(
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    clicks: web::Data<DynClickStore>,
+    accounts: web::Data<Accounts>,
+    path: web::Path<String>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let account = accounts.authenticate(&req)?;
+    let tag = path.into_inner();
+    let links = store.list_owned(&account, Some(&tag)).await?.into_iter().map(|link| link.id).collect();
+    let export = Export::from_query(&query, Some(links))?;
+    // Tags may hold characters a file name can't
+    let subject: String = tag
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    Ok(export_response(export, &format!("tag-{}", subject), clicks.get_ref().clone()))
+}
+
+/// The link with the short code, if the account owns it
+async fn owned_link(
+    store: &DynLinkStore,
+    accounts: &Accounts,
+    account: &str,
+    short_code: &str,
+) -> Result<Link, ServiceError> {
+    let link = store
+        .get_by_code(short_code)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Short link not found: {}", short_code)))?;
+    accounts.check_owner(account, &link)?;
+    Ok(link)
+}
+
+/// Response sending the export as an attachment, page by page
+fn export_response(export: Export, subject: &str, clicks: DynClickStore) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(export.format.content_type())
+        .insert_header(ContentDisposition::attachment(export.file_name(subject)))
+        .streaming(export.stream(clicks))
+}
+
+/// Response sending the events as they come
+fn event_stream(
+    events: impl Stream<Item = Result<Bytes, Infallible>> + 'static,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // Keeps proxies such as nginx from buffering the events
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events)
+}
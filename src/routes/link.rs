@@ -1,104 +1,120 @@
 // src/routes/link.rs
 
-use crate::models::link::{CreateLinkRequest, CreateLinkResponse, Link};
-use actix_web::error::Error as ActixError;
-use actix_web::{web, HttpResponse, Responder};
-use argon2::password_hash::Salt;
-use argon2::{Argon2, PasswordHasher};
-use chrono::{DateTime, Utc};
-use log::{debug, info};
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
-use serde_json::{json, to_string};
-use sqlx::postgres::PgRow;
-use sqlx::{Error, PgPool, Row};
-use std::env;
+use crate::errors::ServiceError;
+use crate::models::card::SocialCard;
+use crate::models::link::{
+    CreateLinkRequest, CreateLinkResponse, LinkChanges, LinkInfo, LinkList, ListLinksQuery,
+    NewLink, UpdateLinkRequest, REDIRECT_STATUSES,
+};
+use crate::models::rule::RoutingRule;
+use crate::models::variant::{validate_variants, ExperimentStats, VariantStats};
+use crate::services::{Accounts, AliasPolicy, CodeAllocator};
+use crate::store::DynLinkStore;
+use crate::utils::password::hash_password;
+use actix_web::web::Json;
+use actix_web::{web, Error as ActixError, HttpRequest, HttpResponse, ResponseError};
+use apistos::actix::NoContent;
 use apistos::api_operation;
-use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use log::info;
+
+/// Default and maximum page sizes for listing links
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+/// Most tags a link can carry, and the longest tag
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 64;
 /// Handler to create a new short link
 ///
-
-
-#[api_operation(summary = "Create a short link")]
+/// Links created with an access token are owned by its account, which can then
+/// watch and export their clicks.
+#[api_operation(summary = "Create a short link", skip_args = "req")]
 pub async fn create_link(
-    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    store: web::Data<DynLinkStore>,
+    allocator: web::Data<CodeAllocator>,
+    aliases: web::Data<AliasPolicy>,
+    accounts: web::Data<Accounts>,
     payload: web::Json<CreateLinkRequest>,
 ) -> HttpResponse {
+    let owner = match accounts.owner_of(&req) {
+        Ok(owner) => owner,
+        Err(e) => return e.error_response(),
+    };
+
     // Validate the original URL
     if !is_valid_url(&payload.original_url) {
         return HttpResponse::BadRequest().body("Invalid URL");
     }
+    if let Err(e) = check_redirect_status(payload.redirect_status) {
+        return e.error_response();
+    }
+    if let Err(e) = check_schedule(payload.starts_at, payload.expires_at, payload.fallback_url.as_deref()) {
+        return e.error_response();
+    }
+    if let Err(e) = check_rules(payload.rules.as_deref().unwrap_or_default()) {
+        return e.error_response();
+    }
+    if let Err(e) = validate_variants(payload.variants.as_deref().unwrap_or_default()) {
+        return ServiceError::BadRequest(e).error_response();
+    }
+    if let Some(Err(e)) = payload.card.as_ref().map(SocialCard::validate) {
+        return ServiceError::BadRequest(e).error_response();
+    }
+    if let Err(e) = check_tags(payload.tags.as_deref().unwrap_or_default()) {
+        return e.error_response();
+    }
 
-    // Generate a unique short code
-    let short_code = match &payload.custom_alias {
-        Some(alias) => {
-            // Check if custom alias already exists
-            let existing = sqlx::query!(
-                "SELECT id FROM links WHERE short_code = $1",
-                alias
-            )
-                .fetch_optional(pool.get_ref())
-                .await
-                .expect("Failed to execute query");
-
-            if existing.is_some() {
-                return HttpResponse::BadRequest().body("Custom alias already in use");
-            }
-
-            alias.clone()
-        }
-        None => generate_unique_short_code(pool.get_ref()).await.unwrap_or_else(|_| "abc123".to_string()),
+    // Validate the custom alias, if any
+    let custom_alias = match payload.custom_alias.as_deref().map(|alias| aliases.validate(alias)) {
+        Some(Ok(alias)) => Some(alias),
+        Some(Err(violation)) => return ServiceError::from(violation).error_response(),
+        None => None,
     };
 
     // Handle password hashing if password is provided
-    let password_hash = match &payload.password {
-        Some(pwd) => Some(hash_password(pwd)),
-        None => None,
+    let password_hash = payload.password.as_deref().map(hash_password);
+
+    let new_link = NewLink {
+        original_url: payload.original_url.clone(),
+        short_code: custom_alias.clone().unwrap_or_default(),
+        starts_at: payload.starts_at,
+        expires_at: payload.expires_at,
+        fallback_url: payload.fallback_url.clone(),
+        password_hash,
+        click_limit: payload.click_limit,
+        redirect_status: payload.redirect_status,
+        passthrough: payload.passthrough.unwrap_or_default(),
+        rules: payload.rules.clone().unwrap_or_default(),
+        variants: payload.variants.clone().unwrap_or_default(),
+        card: payload.card.clone(),
+        is_active: true,
+        owner,
+        tags: payload.tags.clone().unwrap_or_default(),
     };
 
-    // Insert into database
-    let link_result = sqlx::query(
-        r#"
-        INSERT INTO links (
-            original_url, short_code, expires_at, password_hash, click_limit, is_active
-        ) VALUES (
-            $1, $2, $3, $4, $5, $6
-        )
-        RETURNING id, original_url, short_code, created_at, expires_at as "expires_at!: Option<DateTime<Utc>>", password_hash, click_limit, is_active
-        "#).bind(&payload.original_url).bind(&short_code)
-        .bind(&payload.expires_at)
-        .bind(&password_hash)
-        .bind(payload.click_limit)
-        .bind(true)
-        .map(|row: PgRow| {
-            Link {
-                id: row.get(0),
-                original_url: row.get(1),
-                short_code: row.get(2),
-                created_at: row.get(3),
-                expires_at: row.get(4),
-                password_hash: row.get(5),
-                click_limit: row.get(6),
-                is_active: row.get(7),
-            }
-        })
-        .fetch_one(pool.get_ref())
-        .await;
+    // Insert into the link store; the store's unique constraint rejects taken codes
+    let link_result = match &custom_alias {
+        Some(_) => store.create(new_link).await,
+        None => allocator.create(store.as_ref().as_ref(), new_link).await,
+    };
 
     match link_result {
         Ok(link) => {
-            println!("Link: {:?}", link.short_code);
-            let short_url = format!("https://ktvr.cc/{}", link.short_code);
+            info!("Created short link: {}", link.short_code);
+            let short_url = short_url(&link.short_code);
             let response = CreateLinkResponse {
                 short_url,
                 expires_at: link.expires_at,
             };
             HttpResponse::Ok().json(response)
         }
+        Err(ServiceError::Conflict(_)) => {
+            HttpResponse::BadRequest().body("Custom alias already in use")
+        }
         Err(e) => {
             log::error!("Failed to create short link: {}", e);
-            HttpResponse::InternalServerError().json("Failed")
-            // Err(ActixError::from(e))
+            e.error_response()
         }
     }
 }
@@ -108,46 +124,229 @@ fn is_valid_url(url: &str) -> bool {
     url::Url::parse(url).is_ok()
 }
 
-/// Generates a unique short code
-async fn generate_unique_short_code(pool: &PgPool) -> Result<String, sqlx::Error> {
-    loop {
-        let code = generate_random_code(6);
-        let exists = sqlx::query!(
-            "SELECT id FROM links WHERE short_code = $1",
-            code
-        )
-            .fetch_optional(pool)
-            .await?;
-
-        if exists.is_none() {
-            return Ok(code);
+/// Rejects redirect statuses other than 301, 302, 307 and 308
+fn check_redirect_status(status: Option<i16>) -> Result<(), ServiceError> {
+    match status {
+        Some(status) if !REDIRECT_STATUSES.contains(&status) => Err(ServiceError::BadRequest(
+            format!("Unsupported redirect status: {}", status),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects schedules ending before they start and invalid fallback URLs
+fn check_schedule(
+    starts_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    fallback_url: Option<&str>,
+) -> Result<(), ServiceError> {
+    if let (Some(starts_at), Some(expires_at)) = (starts_at, expires_at) {
+        if starts_at >= expires_at {
+            return Err(ServiceError::BadRequest(
+                "starts_at must be before expires_at".to_string(),
+            ));
         }
     }
+    if let Some(fallback_url) = fallback_url {
+        url::Url::parse(fallback_url).map_err(ServiceError::from)?;
+    }
+    Ok(())
+}
+
+/// Rejects routing rules with invalid destinations, country codes or time zones
+fn check_rules(rules: &[RoutingRule]) -> Result<(), ServiceError> {
+    rules
+        .iter()
+        .try_for_each(RoutingRule::validate)
+        .map_err(ServiceError::BadRequest)
+}
+
+/// Rejects empty or overlong tags, and too many of them
+fn check_tags(tags: &[String]) -> Result<(), ServiceError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ServiceError::BadRequest(format!("A link can have at most {} tags", MAX_TAGS)));
+    }
+    match tags.iter().find(|tag| tag.trim().is_empty() || tag.chars().count() > MAX_TAG_LENGTH) {
+        Some(tag) => Err(ServiceError::BadRequest(format!(
+            "Tags must be 1 to {} characters: {:?}",
+            MAX_TAG_LENGTH, tag
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Handler to fetch the details of a short link; the destinations of
+/// password-protected links are left out
+#[api_operation(tag = "api", summary = "Get a short link")]
+pub async fn get_link(
+    store: web::Data<DynLinkStore>,
+    path: web::Path<String>,
+) -> Result<Json<LinkInfo>, ActixError> {
+    let short_code = path.into_inner();
+    let link = store
+        .get_by_code(&short_code)
+        .await?
+        .ok_or_else(|| not_found(&short_code))?;
+
+    let short_url = short_url(&link.short_code);
+    Ok(Json(LinkInfo::new(link, short_url)))
 }
 
-/// Generates a random alphanumeric string of given length
-fn generate_random_code(length: usize) -> String {
-    use rand::{distributions::Alphanumeric, thread_rng, Rng};
-    thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(length)
-        .map(char::from)
-        .collect()
+/// Handler to report the clicks each variant of a link's split received
+#[api_operation(tag = "api", summary = "Get the experiment stats of a short link")]
+pub async fn get_experiment(
+    store: web::Data<DynLinkStore>,
+    path: web::Path<String>,
+) -> Result<Json<ExperimentStats>, ActixError> {
+    let short_code = path.into_inner();
+    let link = store
+        .get_by_code(&short_code)
+        .await?
+        .ok_or_else(|| not_found(&short_code))?;
+    let clicks = store.variant_clicks(&short_code).await?;
+
+    let protected = link.password_hash.is_some();
+    let clicks_of = |name: &str| clicks.get(name).copied().unwrap_or(0);
+    let total_clicks: i64 = link.variants.iter().map(|variant| clicks_of(&variant.name)).sum();
+    let variants = link
+        .variants
+        .into_iter()
+        .map(|variant| {
+            let clicks = clicks_of(&variant.name);
+            VariantStats {
+                share: if total_clicks > 0 {
+                    clicks as f64 / total_clicks as f64
+                } else {
+                    0.0
+                },
+                clicks,
+                name: variant.name,
+                destination: Some(variant.destination).filter(|_| !protected),
+                weight: variant.weight,
+            }
+        })
+        .collect();
+
+    Ok(Json(ExperimentStats {
+        short_code: link.short_code,
+        total_clicks,
+        variants,
+    }))
 }
 
-/// Hashes the given password using Argon2
-fn hash_password(password: &str) -> String {
-    let salt: String = thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(16)
-        .map(char::from)
+/// Handler to list short links, newest first
+#[api_operation(tag = "api", summary = "List short links")]
+pub async fn list_links(
+    store: web::Data<DynLinkStore>,
+    query: web::Query<ListLinksQuery>,
+) -> Result<Json<LinkList>, ActixError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    // Fetch one extra link to tell whether another page follows
+    let mut links = store.list((page - 1) * per_page, per_page + 1).await?;
+    let has_more = links.len() as i64 > per_page;
+    links.truncate(per_page as usize);
+
+    let items = links
+        .into_iter()
+        .map(|link| {
+            let short_url = short_url(&link.short_code);
+            LinkInfo::new(link, short_url)
+        })
         .collect();
 
-    // Initialize Argon2 instance with default parameters
-    let argon2 = Argon2::default();
+    Ok(Json(LinkList {
+        items,
+        page,
+        per_page,
+        has_more,
+    }))
+}
+
+/// Handler to update the destination, schedule, fallback, click limit, password, redirect
+/// behavior, rules, split, card or state
+/// of a short link
+///
+/// Changes take effect immediately as the cached copy of the link is evicted. Only the
+/// fields sent are written, so concurrent updates of other fields are kept.
+#[api_operation(tag = "api", summary = "Update a short link")]
+pub async fn update_link(
+    store: web::Data<DynLinkStore>,
+    path: web::Path<String>,
+    payload: web::Json<UpdateLinkRequest>,
+) -> Result<Json<LinkInfo>, ActixError> {
+    let short_code = path.into_inner();
+    let mut link = store
+        .get_by_code(&short_code)
+        .await?
+        .ok_or_else(|| not_found(&short_code))?;
+
+    let payload = payload.into_inner();
+    if let Some(original_url) = &payload.original_url {
+        url::Url::parse(original_url).map_err(ServiceError::from)?;
+    }
+    if let Some(redirect_status) = payload.redirect_status {
+        check_redirect_status(redirect_status)?;
+    }
+    if let Some(rules) = &payload.rules {
+        check_rules(rules)?;
+    }
+    if let Some(variants) = &payload.variants {
+        validate_variants(variants).map_err(ServiceError::BadRequest)?;
+    }
+    if let Some(Some(card)) = &payload.card {
+        card.validate().map_err(ServiceError::BadRequest)?;
+    }
+    let changes = LinkChanges {
+        original_url: payload.original_url,
+        starts_at: payload.starts_at,
+        expires_at: payload.expires_at,
+        fallback_url: payload.fallback_url,
+        password_hash: payload.password.map(|password| password.as_deref().map(hash_password)),
+        click_limit: payload.click_limit,
+        redirect_status: payload.redirect_status,
+        passthrough: payload.passthrough,
+        rules: payload.rules,
+        variants: payload.variants,
+        card: payload.card,
+        is_active: payload.is_active,
+    };
+
+    // The schedule is checked against the link as just read
+    changes.apply_to(&mut link);
+    check_schedule(link.starts_at, link.expires_at, link.fallback_url.as_deref())?;
+
+    let link = store
+        .update(&short_code, &changes)
+        .await?
+        .ok_or_else(|| not_found(&short_code))?;
+    info!("Updated short link: {}", link.short_code);
 
-    // Hash the password with the generated salt
-    let password_hash = argon2.hash_password(password.as_bytes(), Salt::from_b64(&salt).unwrap()).unwrap().to_string();
+    let short_url = short_url(&link.short_code);
+    Ok(Json(LinkInfo::new(link, short_url)))
+}
+
+/// Handler to delete a short link
+#[api_operation(tag = "api", summary = "Delete a short link")]
+pub async fn delete_link(
+    store: web::Data<DynLinkStore>,
+    path: web::Path<String>,
+) -> Result<NoContent, ActixError> {
+    let short_code = path.into_inner();
+    if !store.delete(&short_code).await? {
+        return Err(not_found(&short_code).into());
+    }
+    info!("Deleted short link: {}", short_code);
+
+    Ok(NoContent)
+}
+
+/// Builds the public URL of a short code
+fn short_url(short_code: &str) -> String {
+    format!("https://ktvr.cc/{}", short_code)
+}
 
-    password_hash
-}
\ No newline at end of file
+fn not_found(short_code: &str) -> ServiceError {
+    ServiceError::NotFound(format!("Short link not found: {}", short_code))
+}
@@ -1,9 +1,10 @@
+mod clicks;
 mod link;
 
 use crate::handlers::{
-    health_check, redirect,
+    health_check, preview, redirect, redirect_with_path, unlock, unlock_form,
 };
-use apistos::web::{get, post, resource, scope, Scope};
+use apistos::web::{delete, get, head, patch, post, resource, scope};
 
 // src/routes/mod.rs
 //
@@ -16,22 +17,130 @@ use apistos::web::{get, post, resource, scope, Scope};
 use apistos::web::ServiceConfig;
 // use apistos::web::{resource, get, scope, post};
 //
+
+/// Path of the generated OpenAPI document
+pub const OPENAPI_PATH: &str = "/openapi.json/";
+/// Path of the Swagger UI
+pub const DOCS_PATH: &str = "/docs/";
+const API_SCOPE: &str = "/api";
+const HEALTH_CHECK_PATH: &str = "health_check";
+
+/// Registers routes under the given path
+type RegisterRoutes = fn(&mut ServiceConfig, &str);
+
+/// Routes starting with a literal path, served next to the `{short_id}` routes. Each
+/// is registered under its path by the function next to it.
+const LITERAL_ROUTES: [(&str, RegisterRoutes); 2] = [
+    (API_SCOPE, api_routes),
+    (HEALTH_CHECK_PATH, health_check_routes),
+];
+
+/// Paths of the OpenAPI document and Swagger UI, registered together with the routes
+const DOCUMENTATION_PATHS: [&str; 2] = [OPENAPI_PATH, DOCS_PATH];
+
+/// First path segments of the literal routes, which custom aliases must not shadow
+pub fn reserved_words() -> Vec<String> {
+    LITERAL_ROUTES
+        .iter()
+        .map(|(path, _)| *path)
+        .chain(DOCUMENTATION_PATHS)
+        .filter_map(|path| path.trim_start_matches('/').split('/').next())
+        .map(str::to_string)
+        .collect()
+}
+
 pub fn config(cfg: &mut ServiceConfig) {
+    for (path, register) in LITERAL_ROUTES {
+        register(cfg, path);
+    }
+
     cfg
         .service(
-            scope("/api")
-                .service(
-                    resource("/shorten")
-                        .route(post().to(link::create_link))
-                )
+            resource("{short_id}/unlock")
+                .route(get().to(unlock_form))
+                .route(post().to(unlock))
         )
         .service(
-            resource("health_check").route(get().to(health_check))
+            resource("{short_id}/preview")
+                .route(get().to(preview))
+        )
+        .service(
+            resource(r"{short_id:[^/]+}+")
+                .route(get().to(preview))
         )
-
-
         .service(
             resource("{short_id}")
                 .route(get().to(redirect))
+                .route(head().to(redirect))
+        )
+        // Registered last, so `{short_id}/unlock` and `{short_id}/preview` keep their own handlers
+        .service(
+            resource("{short_id}/{extra_path:.+}")
+                .route(get().to(redirect_with_path))
+                .route(head().to(redirect_with_path))
         );
 }
+
+fn api_routes(cfg: &mut ServiceConfig, path: &str) {
+    cfg
+        .service(
+            scope(path)
+                .service(
+                    resource("/shorten")
+                        .route(post().to(link::create_link))
+                )
+                .service(
+                    resource("/links")
+                        .route(get().to(link::list_links))
+                )
+                .service(
+                    resource("/links/{short_code}")
+                        .route(get().to(link::get_link))
+                        .route(patch().to(link::update_link))
+                        .route(delete().to(link::delete_link))
+                )
+                .service(
+                    resource("/links/{short_code}/experiment")
+                        .route(get().to(link::get_experiment))
+                )
+                .service(
+                    resource("/links/{short_code}/stats")
+                        .route(get().to(clicks::get_link_stats))
+                        .route(delete().to(clicks::delete_link_stats))
+                )
+                .service(
+                    resource("/links/{short_code}/events")
+                        .route(get().to(clicks::stream_link_clicks))
+                )
+                .service(
+                    resource("/links/{short_code}/export")
+                        .route(get().to(clicks::export_link_clicks))
+                )
+                .service(
+                    resource("/tags/{tag}/export")
+                        .route(get().to(clicks::export_tag_clicks))
+                )
+                .service(
+                    resource("/clicks/export")
+                        .route(get().to(clicks::export_clicks))
+                )
+                .service(
+                    resource("/clicks/events")
+                        .route(get().to(clicks::stream_clicks))
+                )
+                .service(
+                    resource("/visitors/{visitor_id}/clicks")
+                        .route(delete().to(clicks::delete_visitor_clicks))
+                )
+                .service(
+                    resource("/clicks/pipeline")
+                        .route(get().to(clicks::get_click_pipeline))
+                )
+        );
+}
+
+fn health_check_routes(cfg: &mut ServiceConfig, path: &str) {
+    cfg.service(
+        resource(path).route(get().to(health_check))
+    );
+}
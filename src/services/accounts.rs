@@ -0,0 +1,99 @@
+// src/services/accounts.rs
+
+use crate::errors::ServiceError;
+use crate::models::link::Link;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha256};
+
+/// Accounts of the API, each known by the access token it presents as an
+/// `Authorization: Bearer` header.
+///
+/// Links are owned by the account that created them, and only their owner may watch,
+/// export or delete their clicks. Without any account, nobody may.
+#[derive(Clone, Default)]
+pub struct Accounts {
+    /// SHA-256 of each account's token, compared in constant time, and its name
+    tokens: Vec<([u8; 32], String)>,
+}
+
+impl Accounts {
+    /// Creates the accounts from `account:token` entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The name and token of each account, separated by the first `:`.
+    ///   Names and tokens must not be empty, and tokens must be unique.
+    pub fn new(entries: &[String]) -> Result<Self, String> {
+        let mut tokens: Vec<([u8; 32], String)> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (account, token) = entry
+                .split_once(':')
+                .map(|(account, token)| (account.trim(), token.trim()))
+                .filter(|(account, token)| !account.is_empty() && !token.is_empty())
+                .ok_or_else(|| "access tokens must be given as account:token".to_string())?;
+            let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+            if tokens.iter().any(|(known, _)| *known == digest) {
+                return Err(format!("the access token of {} is already taken", account));
+            }
+            tokens.push((digest, account.to_string()));
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Returns true if no account is configured
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Account whose access token the request carries. Query parameters are not
+    /// accepted, as request lines end up in logs.
+    pub fn authenticate(&self, req: &HttpRequest) -> Result<String, ServiceError> {
+        if self.is_empty() {
+            return Err(ServiceError::Unauthorized("No access tokens are configured".to_string()));
+        }
+        let presented = request_token(req)
+            .ok_or_else(|| ServiceError::Unauthorized("An access token is required".to_string()))?;
+        let digest = Sha256::digest(presented.as_bytes());
+        // Every token is compared, so the time taken doesn't tell which one matched
+        let mut account = None;
+        for (expected, name) in &self.tokens {
+            if constant_time_eq(&digest, expected) {
+                account = Some(name);
+            }
+        }
+        account
+            .cloned()
+            .ok_or_else(|| ServiceError::Unauthorized("Invalid access token".to_string()))
+    }
+
+    /// Account of the request if it carries an access token, which must then be valid
+    pub fn owner_of(&self, req: &HttpRequest) -> Result<Option<String>, ServiceError> {
+        if !req.headers().contains_key(header::AUTHORIZATION) {
+            return Ok(None);
+        }
+        self.authenticate(req).map(Some)
+    }
+
+    /// Checks the account owns the link. Other links are reported as missing, so
+    /// accounts can't tell which codes other accounts use.
+    pub fn check_owner(&self, account: &str, link: &Link) -> Result<(), ServiceError> {
+        if link.owner.as_deref() == Some(account) {
+            return Ok(());
+        }
+        Err(ServiceError::NotFound(format!("Short link not found: {}", link.short_code)))
+    }
+}
+
+/// Token of the request, from its `Authorization` header
+fn request_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,147 @@
+// src/services/alias.rs
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Characters that would change how a path is routed or decoded.
+const URL_UNSAFE_CHARS: &[char] = &['/', '?', '#', '%', '\\'];
+
+/// Validation rule a custom alias failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasRule {
+    Charset,
+    MinLength,
+    MaxLength,
+    Reserved,
+}
+
+/// Why a custom alias was rejected, returned as the body of a `422 Unprocessable Entity`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AliasViolation {
+    pub message: String,
+    pub field: &'static str,
+    pub rule: AliasRule,
+}
+
+impl AliasViolation {
+    fn new(rule: AliasRule, message: String) -> Self {
+        Self {
+            message,
+            field: "custom_alias",
+            rule,
+        }
+    }
+}
+
+impl fmt::Display for AliasViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Rules custom aliases have to follow.
+///
+/// Reserved words are compared case-insensitively, so `API` is rejected as well as `api`.
+/// With `case_insensitive` set, aliases are folded to lower case before they are stored,
+/// which makes `Docs` and `docs` the same alias.
+#[derive(Debug, Clone)]
+pub struct AliasPolicy {
+    charset: HashSet<char>,
+    min_length: usize,
+    max_length: usize,
+    reserved: HashSet<String>,
+    case_insensitive: bool,
+}
+
+impl AliasPolicy {
+    /// Creates a new AliasPolicy instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `charset` - Characters aliases may contain.
+    /// * `min_length` - Minimum alias length in characters.
+    /// * `max_length` - Maximum alias length in characters.
+    /// * `reserved` - Words that can't be claimed, such as the paths of registered routes.
+    /// * `case_insensitive` - Treat aliases differing only in case as the same alias.
+    ///
+    /// Returns an error if the charset is empty or contains characters that break routing,
+    /// or if the length bounds are inconsistent.
+    pub fn new<I, S>(
+        charset: &str,
+        min_length: usize,
+        max_length: usize,
+        reserved: I,
+        case_insensitive: bool,
+    ) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        if charset.is_empty() {
+            return Err("the alias charset must not be empty".to_string());
+        }
+        if let Some(c) = charset
+            .chars()
+            .find(|c| c.is_whitespace() || c.is_control() || URL_UNSAFE_CHARS.contains(c))
+        {
+            return Err(format!("the alias charset must not contain {:?}", c));
+        }
+        if min_length == 0 || min_length > max_length {
+            return Err(format!(
+                "invalid alias length bounds: {}..={}",
+                min_length, max_length
+            ));
+        }
+
+        Ok(Self {
+            charset: charset.chars().collect(),
+            min_length,
+            max_length,
+            reserved: reserved
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+            case_insensitive,
+        })
+    }
+
+    /// Checks a custom alias against every rule.
+    ///
+    /// Returns the alias to store, which is folded to lower case for case-insensitive aliases.
+    pub fn validate(&self, alias: &str) -> Result<String, AliasViolation> {
+        let length = alias.chars().count();
+        if length < self.min_length {
+            return Err(AliasViolation::new(
+                AliasRule::MinLength,
+                format!("Alias must be at least {} characters long", self.min_length),
+            ));
+        }
+        if length > self.max_length {
+            return Err(AliasViolation::new(
+                AliasRule::MaxLength,
+                format!("Alias must be at most {} characters long", self.max_length),
+            ));
+        }
+        if let Some(c) = alias.chars().find(|c| !self.charset.contains(c)) {
+            return Err(AliasViolation::new(
+                AliasRule::Charset,
+                format!("Alias contains the disallowed character {:?}", c),
+            ));
+        }
+        if self.reserved.contains(&alias.to_lowercase()) {
+            return Err(AliasViolation::new(
+                AliasRule::Reserved,
+                format!("Alias is reserved: {}", alias),
+            ));
+        }
+
+        Ok(if self.case_insensitive {
+            alias.to_lowercase()
+        } else {
+            alias.to_string()
+        })
+    }
+}
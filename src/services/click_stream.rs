@@ -0,0 +1,177 @@
+// src/services/click_stream.rs
+
+use crate::errors::ServiceError;
+use crate::models::click::StreamedClick;
+use actix_web::web::Bytes;
+use futures_util::{Stream, StreamExt};
+use log::{error, warn};
+use redis::aio::MultiplexedConnection;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Clicks buffered for each subscriber; subscribers falling further behind skip clicks
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Longest time a subscriber goes without a message, keeping proxies from closing
+/// idle connections
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Time waited before subscribing to the Redis channel again after losing it
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Publishes clicks to the Redis channel shared by all instances
+#[derive(Clone)]
+struct RedisPublisher {
+    redis_conn: Arc<Mutex<MultiplexedConnection>>,
+    channel: String,
+}
+
+/// Delivers recorded clicks to live subscribers as they happen.
+///
+/// Clicks reach the subscribers of this process directly or, once fanned out through
+/// Redis, of every instance publishing to the same channel. Delivery is best effort:
+/// clicks recorded while a subscriber is disconnected or too slow are not replayed.
+/// Subscribers only receive the clicks of the links their account owns.
+#[derive(Clone)]
+pub struct ClickStream {
+    local: broadcast::Sender<Arc<StreamedClick>>,
+    publisher: Option<RedisPublisher>,
+}
+
+impl ClickStream {
+    /// Creates a ClickStream delivering clicks to the subscribers of this process.
+    pub fn new() -> Self {
+        let (local, _) = broadcast::channel(SUBSCRIBER_BUFFER);
+        Self {
+            local,
+            publisher: None,
+        }
+    }
+
+    /// Fans clicks out through the Redis channel, spawning a task on the current
+    /// runtime that relays the channel's clicks to the subscribers of this process.
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_url` - The Redis server shared by all instances.
+    /// * `channel` - The pub/sub channel clicks are published to.
+    pub async fn with_redis(self, redis_url: &str, channel: String) -> Result<Self, ServiceError> {
+        let client = redis::Client::open(redis_url)?;
+        let redis_conn = Arc::new(Mutex::new(client.get_multiplexed_tokio_connection().await?));
+        tokio::spawn(relay(client, channel.clone(), self.local.clone()));
+
+        Ok(Self {
+            publisher: Some(RedisPublisher { redis_conn, channel }),
+            ..self
+        })
+    }
+
+    /// Sends the clicks to the subscribers
+    pub async fn publish(&self, clicks: Vec<StreamedClick>) -> Result<(), ServiceError> {
+        let Some(publisher) = &self.publisher else {
+            for click in clicks {
+                // Fails only when nobody is subscribed
+                let _ = self.local.send(Arc::new(click));
+            }
+            return Ok(());
+        };
+        if clicks.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for click in &clicks {
+            pipe.publish(&publisher.channel, serde_json::to_string(click)?).ignore();
+        }
+        let mut conn = publisher.redis_conn.lock().await;
+        pipe.query_async::<_, ()>(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Subscribes to the clicks of the account's links, of only the given ones if
+    /// any, as Server-Sent Events
+    pub fn subscribe(
+        &self,
+        owner: String,
+        links: Option<HashSet<Uuid>>,
+    ) -> impl Stream<Item = Result<Bytes, Infallible>> {
+        events(self.local.subscribe(), owner, links)
+    }
+}
+
+impl Default for ClickStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the received clicks of the owner's links as Server-Sent Events, starting
+/// with a comment so the response is sent right away
+fn events(
+    receiver: broadcast::Receiver<Arc<StreamedClick>>,
+    owner: String,
+    links: Option<HashSet<Uuid>>,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let connected = futures_util::stream::once(async { Ok(Bytes::from_static(b": connected\n\n")) });
+    let clicks = futures_util::stream::unfold(receiver, move |mut receiver| {
+        let owner = owner.clone();
+        let links = links.clone();
+        async move {
+            loop {
+                let event = match tokio::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+                    Err(_) => ": keep-alive\n\n".to_string(),
+                    Ok(Ok(click)) => {
+                        if click.owner.as_deref() != Some(owner.as_str())
+                            || links.as_ref().is_some_and(|links| !links.contains(&click.link_id))
+                        {
+                            continue;
+                        }
+                        let data = serde_json::to_string(click.as_ref()).unwrap_or_default();
+                        format!("event: click\ndata: {}\n\n", data)
+                    }
+                    // Subscribers learn how many clicks they missed
+                    Ok(Err(RecvError::Lagged(skipped))) => format!("event: lagged\ndata: {}\n\n", skipped),
+                    Ok(Err(RecvError::Closed)) => return None,
+                };
+                return Some((Ok(Bytes::from(event)), receiver));
+            }
+        }
+    });
+    connected.chain(clicks)
+}
+
+/// Relays the clicks published to the Redis channel to the local subscribers,
+/// subscribing again whenever the connection is lost
+async fn relay(client: redis::Client, channel: String, local: broadcast::Sender<Arc<StreamedClick>>) {
+    loop {
+        match forward(&client, &channel, &local).await {
+            Ok(()) => warn!("Lost the click stream channel {}, subscribing again", channel),
+            Err(e) => error!("Failed to subscribe to the click stream channel {}: {}", channel, e),
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+async fn forward(
+    client: &redis::Client,
+    channel: &str,
+    local: &broadcast::Sender<Arc<StreamedClick>>,
+) -> Result<(), ServiceError> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(channel).await?;
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        match serde_json::from_slice::<StreamedClick>(message.get_payload_bytes()) {
+            Ok(click) => {
+                let _ = local.send(Arc::new(click));
+            }
+            Err(e) => warn!("Ignoring malformed click on channel {}: {}", channel, e),
+        }
+    }
+    Ok(())
+}
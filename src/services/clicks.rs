@@ -0,0 +1,322 @@
+// src/services/clicks.rs
+
+use crate::models::click::{ClickEvent, StreamedClick};
+use crate::models::link::Link;
+use crate::services::{ClickPrivacy, ClickStream, GeoIp};
+use crate::store::{DynClickStore, DynVisitorStore};
+use crate::utils::bot::BotSignal;
+use crate::utils::visitor::{browser_name, classify_user_agent, client_ip, do_not_track};
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use apistos::ApiComponent;
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{error, warn};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use uuid::Uuid;
+
+/// Largest batch written at once, keeping multi-row INSERTs under Postgres' bind limit
+pub const MAX_BATCH_SIZE: usize = 4096;
+
+/// Longest referrer and User-Agent kept, in characters
+const MAX_HEADER_LENGTH: usize = 1024;
+
+/// A redirect as captured by the handler, before the worker enriches it
+struct PendingClick {
+    link_id: Uuid,
+    short_code: String,
+    /// Account owning the link, which the click is streamed to
+    owner: Option<String>,
+    occurred_at: DateTime<Utc>,
+    referrer: Option<String>,
+    user_agent: Option<String>,
+    ip: Option<IpAddr>,
+    variant: Option<String>,
+    bot: Option<BotSignal>,
+    /// The request sent `DNT: 1` or `Sec-GPC: 1`
+    do_not_track: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicU64,
+    dropped: AtomicU64,
+    written: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Counters of the click pipeline since the process started
+#[derive(Debug, Serialize, JsonSchema, ApiComponent)]
+pub struct ClickPipelineStats {
+    pub enabled: bool,
+    /// Clicks accepted into the queue
+    pub queued: u64,
+    /// Clicks dropped because the queue was full
+    pub dropped: u64,
+    /// Clicks stored by the click store
+    pub written: u64,
+    /// Clicks lost because the click store failed to store their batch
+    pub failed: u64,
+    /// Clicks waiting in the queue
+    pub pending: usize,
+}
+
+/// Records a click event for every redirect without delaying it.
+///
+/// Handlers hand clicks to a bounded queue and return immediately. A background
+/// worker identifies the visitor as far as the privacy settings allow, resolves the
+/// country, classifies the referrer and User-Agent, and writes the clicks to the
+/// click store in batches of up to `batch_size`, waiting at most `flush_interval`
+/// for a batch to fill. The visitors
+/// of each batch, bots left out, are then added to the visitor store. While the
+/// stores are slow the queue fills up, and clicks that don't fit are dropped and
+/// counted rather than holding up redirects.
+#[derive(Clone)]
+pub struct ClickRecorder {
+    sender: Option<mpsc::Sender<PendingClick>>,
+    capacity: usize,
+    counters: Arc<Counters>,
+}
+
+impl ClickRecorder {
+    /// Creates a new ClickRecorder and spawns its worker on the current runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store the clicks are written to.
+    /// * `visitors` - The store counting the distinct visitors of the clicks.
+    /// * `stream` - Delivers the clicks to live subscribers.
+    /// * `geoip` - Resolves the country of each click.
+    /// * `privacy` - What clicks keep about their visitors.
+    /// * `capacity` - Clicks the queue holds before new ones are dropped.
+    /// * `batch_size` - Most clicks written at once, up to `MAX_BATCH_SIZE`.
+    /// * `flush_interval` - Longest time a click waits for its batch to fill.
+    ///
+    /// Returns the recorder together with the worker, which stores the remaining
+    /// clicks and finishes once every clone of the recorder is dropped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        store: DynClickStore,
+        visitors: DynVisitorStore,
+        stream: ClickStream,
+        geoip: GeoIp,
+        privacy: ClickPrivacy,
+        capacity: usize,
+        batch_size: usize,
+        flush_interval: Duration,
+    ) -> Result<(Self, JoinHandle<()>), String> {
+        if capacity == 0 {
+            return Err("the click queue capacity must be positive".to_string());
+        }
+        if batch_size == 0 || batch_size > MAX_BATCH_SIZE {
+            return Err(format!("the click batch size must be between 1 and {}", MAX_BATCH_SIZE));
+        }
+
+        let (sender, receiver) = mpsc::channel(capacity);
+        let counters = Arc::new(Counters::default());
+        let worker = Worker {
+            store,
+            visitors,
+            stream,
+            geoip,
+            privacy,
+            daily_salt: None,
+            batch_size,
+            flush_interval,
+            counters: counters.clone(),
+        };
+        let handle = tokio::spawn(worker.run(receiver));
+
+        let recorder = Self {
+            sender: Some(sender),
+            capacity,
+            counters,
+        };
+        Ok((recorder, handle))
+    }
+
+    /// A ClickRecorder discarding every click.
+    pub fn disabled() -> Self {
+        Self {
+            sender: None,
+            capacity: 0,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Queues a click of the link, sent to the given variant of its split if any and
+    /// classified as a bot's if `bot` tells why
+    pub fn record(
+        &self,
+        req: &HttpRequest,
+        link: &Link,
+        variant: Option<&str>,
+        bot: Option<BotSignal>,
+    ) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        let click = PendingClick {
+            link_id: link.id,
+            short_code: link.short_code.clone(),
+            owner: link.owner.clone(),
+            occurred_at: Utc::now(),
+            referrer: header_value(req, header::REFERER),
+            user_agent: header_value(req, header::USER_AGENT),
+            ip: client_ip(req),
+            variant: variant.map(str::to_string),
+            bot,
+            do_not_track: do_not_track(req),
+        };
+
+        match sender.try_send(click) {
+            Ok(()) => {
+                self.counters.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                // Logged at exponentially growing intervals to keep overload quiet
+                if dropped.is_power_of_two() {
+                    warn!("Click queue is full, {} clicks dropped so far", dropped);
+                }
+            }
+        }
+    }
+
+    /// Current counters of the pipeline
+    pub fn stats(&self) -> ClickPipelineStats {
+        let pending = self
+            .sender
+            .as_ref()
+            .map(|sender| self.capacity - sender.capacity())
+            .unwrap_or_default();
+        ClickPipelineStats {
+            enabled: self.sender.is_some(),
+            queued: self.counters.queued.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            written: self.counters.written.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            pending,
+        }
+    }
+}
+
+struct Worker {
+    store: DynClickStore,
+    visitors: DynVisitorStore,
+    stream: ClickStream,
+    geoip: GeoIp,
+    privacy: ClickPrivacy,
+    /// Salt of the visitor hashes of the day it was read on, if they are daily
+    daily_salt: Option<(NaiveDate, Vec<u8>)>,
+    batch_size: usize,
+    flush_interval: Duration,
+    counters: Arc<Counters>,
+}
+
+impl Worker {
+    async fn run(mut self, mut receiver: mpsc::Receiver<PendingClick>) {
+        let mut pending = Vec::with_capacity(self.batch_size);
+
+        // An empty receive means every sender is gone and the queue is drained
+        while receiver.recv_many(&mut pending, self.batch_size).await > 0 {
+            let deadline = Instant::now() + self.flush_interval;
+            while pending.len() < self.batch_size {
+                let limit = self.batch_size - pending.len();
+                match timeout_at(deadline, receiver.recv_many(&mut pending, limit)).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+
+            let salt = self.daily_salt().await;
+            let owners: Vec<Option<String>> = pending.iter().map(|click| click.owner.clone()).collect();
+            let batch: Vec<ClickEvent> =
+                pending.drain(..).map(|click| self.enrich(click, salt.as_deref())).collect();
+            match self.store.insert_clicks(&batch).await {
+                Ok(()) => {
+                    self.counters.written.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    self.counters.failed.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    error!("Failed to store {} clicks: {}", batch.len(), e);
+                }
+            }
+
+            let streamed = batch.iter().zip(owners).map(|(click, owner)| StreamedClick::new(click, owner));
+            if let Err(e) = self.stream.publish(streamed.collect()).await {
+                error!("Failed to publish {} clicks to the click stream: {}", batch.len(), e);
+            }
+
+            let people: Vec<ClickEvent> = batch.into_iter().filter(|click| !click.is_bot()).collect();
+            if let Err(e) = self.visitors.add_visitors(&people).await {
+                error!("Failed to count the visitors of {} clicks: {}", people.len(), e);
+            }
+        }
+    }
+
+    /// Salt of today's visitor hashes if they are daily, read from the store once a
+    /// day. Clicks are left without their visitor when it can't be read.
+    async fn daily_salt(&mut self) -> Option<Vec<u8>> {
+        if !self.privacy.hashes_daily() {
+            return None;
+        }
+        let today = Utc::now().date_naive();
+        if !matches!(&self.daily_salt, Some((day, _)) if *day == today) {
+            match self.store.daily_salt(today).await {
+                Ok(salt) => self.daily_salt = Some((today, salt)),
+                Err(e) => {
+                    error!("Failed to read the salt of the visitor hashes: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.daily_salt.as_ref().map(|(_, salt)| salt.clone())
+    }
+
+    fn enrich(&self, click: PendingClick, daily_salt: Option<&[u8]>) -> ClickEvent {
+        // Visitors asking not to be tracked are only counted in aggregate: nothing read
+        // from their request is kept, including what is derived from it, such as their
+        // country or browser. Their bot classification is, so stats still leave bots out.
+        let tracked = !(click.do_not_track && self.privacy.honors_do_not_track());
+        let referrer = click.referrer.filter(|_| tracked);
+        let user_agent = click.user_agent.filter(|_| tracked);
+        let ip = click.ip.filter(|_| tracked);
+        let (os, device) = classify_user_agent(user_agent.as_deref().unwrap_or_default());
+        ClickEvent {
+            link_id: click.link_id,
+            short_code: click.short_code,
+            occurred_at: click.occurred_at,
+            referrer_host: referrer.as_deref().and_then(referrer_host),
+            browser: user_agent.as_deref().and_then(browser_name),
+            os: os.map(|os| os.as_str().to_string()),
+            device: tracked.then(|| device.as_str().to_string()),
+            referrer,
+            user_agent,
+            ip_hash: ip.and_then(|ip| self.privacy.visitor_id(ip, daily_salt)),
+            country: ip.and_then(|ip| self.geoip.country(ip)),
+            variant: click.variant,
+            bot_reason: click.bot.map(|bot| bot.to_string()),
+        }
+    }
+
+}
+
+fn header_value(req: &HttpRequest, name: header::HeaderName) -> Option<String> {
+    let value = req.headers().get(name)?.to_str().ok()?;
+    Some(value.chars().take(MAX_HEADER_LENGTH).collect())
+}
+
+/// Lower-cased host of a referrer URL
+fn referrer_host(referrer: &str) -> Option<String> {
+    let url = url::Url::parse(referrer).ok()?;
+    url.host_str().map(str::to_ascii_lowercase)
+}
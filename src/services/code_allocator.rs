@@ -0,0 +1,176 @@
+// src/services/code_allocator.rs
+
+use crate::errors::ServiceError;
+use crate::models::link::{Link, NewLink};
+use crate::store::LinkStore;
+use crate::utils::short_code::{CodeNamespace, CodeRequest, DynShortCodeGenerator};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+
+/// Growth of the namespace's codes as last read from the store, and collision counts
+/// over the current observation window.
+#[derive(Default)]
+struct NamespaceState {
+    extra_length: Option<usize>,
+    attempts: usize,
+    collisions: usize,
+}
+
+/// Stores new links under generated short codes without check-then-insert races.
+///
+/// Each candidate is inserted directly and the store's unique constraint decides;
+/// conflicts are retried up to `max_attempts` times. With content-addressed codes, a
+/// conflict with a link of the same destination and settings returns that link.
+///
+/// Codes drawn at random from a namespace grow when it fills up: when more than
+/// `growth_threshold` of the last `window` insert attempts collided, every later code
+/// of the namespace gets one character longer, up to the namespace's maximum. The
+/// growth is kept in the link store, so all instances share it.
+#[derive(Clone)]
+pub struct CodeAllocator {
+    generator: DynShortCodeGenerator,
+    namespace: Option<CodeNamespace>,
+    max_attempts: u32,
+    growth_threshold: f64,
+    window: usize,
+    state: Arc<Mutex<NamespaceState>>,
+}
+
+impl CodeAllocator {
+    /// Creates a new CodeAllocator instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `generator` - The strategy producing candidate codes.
+    /// * `max_attempts` - Candidates tried per link before giving up.
+    /// * `growth_threshold` - Collision rate, between 0 and 1, above which codes grow.
+    /// * `window` - Number of insert attempts the collision rate is measured over.
+    pub fn new(
+        generator: DynShortCodeGenerator,
+        max_attempts: u32,
+        growth_threshold: f64,
+        window: usize,
+    ) -> Self {
+        Self {
+            namespace: generator.namespace(),
+            generator,
+            max_attempts: max_attempts.max(1),
+            growth_threshold,
+            window: window.max(1),
+            state: Arc::default(),
+        }
+    }
+
+    /// Characters currently added to the generator's configured length, as last read
+    /// from the store.
+    pub fn extra_length(&self) -> usize {
+        self.state.lock().unwrap().extra_length.unwrap_or(0)
+    }
+
+    /// Stores the link under a freshly generated short code.
+    ///
+    /// The `short_code` of `link` is ignored. Returns an identical link already stored
+    /// under a content-addressed code instead of a new one, and
+    /// `ServiceError::ShortCodeUnavailable` if every attempt collided.
+    pub async fn create(&self, store: &dyn LinkStore, mut link: NewLink) -> Result<Link, ServiceError> {
+        self.load_growth(store).await?;
+        for attempt in 0..self.max_attempts {
+            // Codes may have grown after the previous attempt
+            let request = CodeRequest {
+                original_url: &link.original_url,
+                attempt,
+                extra_length: self.extra_length(),
+            };
+            link.short_code = self.generator.generate(&request).await?;
+
+            match store.create(link.clone()).await {
+                Ok(created) => {
+                    self.record(store, false).await;
+                    return Ok(created);
+                }
+                Err(ServiceError::Conflict(_)) => {
+                    if self.generator.is_content_addressed() {
+                        if let Some(existing) = store.get_by_code(&link.short_code).await? {
+                            if link.is_stored_as(&existing) {
+                                info!("Reusing short code of the same link: {}", existing.short_code);
+                                return Ok(existing);
+                            }
+                        }
+                    }
+                    warn!("Short code collision on attempt {}: {}", attempt + 1, link.short_code);
+                    self.record(store, true).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(ServiceError::ShortCodeUnavailable(format!(
+            "Could not allocate a unique short code after {} attempts",
+            self.max_attempts
+        )))
+    }
+
+    /// Reads the growth of the namespace's codes from the store on first use
+    async fn load_growth(&self, store: &dyn LinkStore) -> Result<(), ServiceError> {
+        let Some(namespace) = &self.namespace else {
+            return Ok(());
+        };
+        if self.state.lock().unwrap().extra_length.is_some() {
+            return Ok(());
+        }
+
+        let extra_length = store.code_growth(&namespace.name).await?;
+        self.state.lock().unwrap().extra_length = Some(extra_length);
+        Ok(())
+    }
+
+    /// Records the outcome of one insert attempt in a random namespace. At the end of
+    /// each window, grows its codes if collisions got too frequent, or else picks up
+    /// growth decided by other instances.
+    async fn record(&self, store: &dyn LinkStore, collided: bool) {
+        let Some(namespace) = &self.namespace else {
+            return;
+        };
+        let (rate, extra_length) = {
+            let mut state = self.state.lock().unwrap();
+            state.attempts += 1;
+            if collided {
+                state.collisions += 1;
+            }
+            if state.attempts < self.window {
+                return;
+            }
+
+            let rate = state.collisions as f64 / state.attempts as f64;
+            state.attempts = 0;
+            state.collisions = 0;
+            (rate, state.extra_length.unwrap_or(0))
+        };
+
+        let too_frequent = rate > self.growth_threshold;
+        let grow = too_frequent && extra_length < namespace.max_extra_length;
+        if too_frequent && !grow {
+            warn!(
+                "Short code collision rate {:.2} exceeded {:.2}, but codes already have their maximum length",
+                rate, self.growth_threshold
+            );
+        }
+        let result = if grow {
+            store.grow_codes(&namespace.name, extra_length + 1).await
+        } else {
+            store.code_growth(&namespace.name).await
+        };
+        match result {
+            Ok(now) => {
+                if grow {
+                    info!(
+                        "Short code collision rate {:.2} exceeded {:.2}, adding {} character(s) to new codes",
+                        rate, self.growth_threshold, now
+                    );
+                }
+                self.state.lock().unwrap().extra_length = Some(now);
+            }
+            Err(e) => warn!("Failed to update the growth of short codes in {}: {}", namespace.name, e),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// src/services/export.rs
+
+use crate::errors::ServiceError;
+use crate::models::export::{
+    ClickPosition, ClickRollup, ExportData, ExportFormat, ExportQuery, ExportScope,
+};
+use crate::store::DynClickStore;
+use actix_web::web::Bytes;
+use chrono::{Duration, Utc};
+use futures_util::Stream;
+use log::error;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Default range of an export, ending now
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// Rows read from the store at a time, bounding what an export holds in memory
+pub const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// Columns of exported clicks, named as the fields of `ClickEvent`.
+///
+/// Warehouse loads depend on them: columns may be added at the end, never removed,
+/// renamed or reordered.
+pub const CLICK_COLUMNS: [&str; 13] = [
+    "link_id",
+    "short_code",
+    "occurred_at",
+    "referrer",
+    "referrer_host",
+    "user_agent",
+    "browser",
+    "os",
+    "device",
+    "ip_hash",
+    "country",
+    "variant",
+    "bot_reason",
+];
+
+/// Columns of exported rollup rows, named as the fields of `ClickRollup`; stable
+/// like `CLICK_COLUMNS`
+pub const ROLLUP_COLUMNS: [&str; 7] = [
+    "link_id",
+    "bucket",
+    "is_bot",
+    "dimension",
+    "value",
+    "clicks",
+    "unique_clicks",
+];
+
+/// Clicks or rollup rows to write out, and how
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub scope: ExportScope,
+    pub data: ExportData,
+    pub format: ExportFormat,
+}
+
+impl Export {
+    /// Creates the export a query asks for.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The range, data and format asked for, defaulting to the clicks of
+    ///   the last 30 days as CSV.
+    /// * `links` - The links exported, or every link if `None`.
+    pub fn from_query(query: &ExportQuery, links: Option<Vec<Uuid>>) -> Result<Self, ServiceError> {
+        let to = query.to.unwrap_or_else(Utc::now);
+        let from = query.from.unwrap_or(to - Duration::days(DEFAULT_RANGE_DAYS));
+        if from >= to {
+            return Err(ServiceError::BadRequest("from must be before to".to_string()));
+        }
+
+        Ok(Self {
+            scope: ExportScope { links, from, to },
+            data: query.data.unwrap_or_default(),
+            format: query.format.unwrap_or_default(),
+        })
+    }
+
+    /// Name of the file the export is saved to, such as `abc123-clicks.csv`
+    pub fn file_name(&self, subject: &str) -> String {
+        format!("{}-{}.{}", subject, self.data.as_str(), self.format.extension())
+    }
+
+    /// Streams the export page by page: a CSV header first, then a chunk of rows per
+    /// page read from the store. The stream ends after the first error.
+    pub fn stream(self, clicks: DynClickStore) -> impl Stream<Item = Result<Bytes, ServiceError>> {
+        let state = ExportState {
+            export: self,
+            clicks,
+            header_written: false,
+            cursor: Cursor::Start,
+        };
+        futures_util::stream::unfold(state, |mut state| async move {
+            match state.next_chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), state)),
+                Ok(None) => None,
+                Err(e) => {
+                    error!("Failed to export clicks: {}", e);
+                    state.cursor = Cursor::Done;
+                    Some((Err(e), state))
+                }
+            }
+        })
+    }
+}
+
+/// Last row written, which the next page follows
+enum Cursor {
+    Start,
+    Click(ClickPosition),
+    Rollup(Box<ClickRollup>),
+    Done,
+}
+
+struct ExportState {
+    export: Export,
+    clicks: DynClickStore,
+    header_written: bool,
+    cursor: Cursor,
+}
+
+impl ExportState {
+    /// Next chunk of the export, or `None` once every row was written
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, ServiceError> {
+        let format = self.export.format;
+        let granularity = self.export.data.granularity();
+        if format == ExportFormat::Csv && !self.header_written {
+            self.header_written = true;
+            let columns = if granularity.is_some() { &ROLLUP_COLUMNS[..] } else { &CLICK_COLUMNS[..] };
+            return Ok(Some(Bytes::from(format!("{}\n", columns.join(",")))));
+        }
+
+        let scope = &self.export.scope;
+        let (chunk, rows, cursor) = match (granularity, &self.cursor) {
+            (_, Cursor::Done) => return Ok(None),
+            (None, cursor) => {
+                let after = match cursor {
+                    Cursor::Click(position) => Some(*position),
+                    _ => None,
+                };
+                let page = self.clicks.export_clicks(scope, after, EXPORT_PAGE_SIZE).await?;
+                let chunk = write_rows(page.iter().map(|(_, click)| click), &CLICK_COLUMNS, format)?;
+                let cursor = page.last().map(|(position, _)| Cursor::Click(*position));
+                (chunk, page.len(), cursor)
+            }
+            (Some(granularity), cursor) => {
+                let after = match cursor {
+                    Cursor::Rollup(row) => Some(row.as_ref()),
+                    _ => None,
+                };
+                let mut page = self
+                    .clicks
+                    .export_rollups(scope, granularity, after, EXPORT_PAGE_SIZE)
+                    .await?;
+                let chunk = write_rows(page.iter(), &ROLLUP_COLUMNS, format)?;
+                let rows = page.len();
+                (chunk, rows, page.pop().map(|row| Cursor::Rollup(Box::new(row))))
+            }
+        };
+
+        // A short page is the last one
+        self.cursor = match cursor {
+            Some(cursor) if rows as i64 == EXPORT_PAGE_SIZE => cursor,
+            _ => Cursor::Done,
+        };
+        if rows == 0 {
+            return Ok(None);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+/// Writes the rows in the format, one line each
+fn write_rows<'a, T: Serialize + 'a>(
+    rows: impl Iterator<Item = &'a T>,
+    columns: &[&str],
+    format: ExportFormat,
+) -> Result<Bytes, ServiceError> {
+    let mut out = String::new();
+    for row in rows {
+        match format {
+            ExportFormat::Ndjson => out.push_str(&serde_json::to_string(row)?),
+            ExportFormat::Csv => {
+                let value = serde_json::to_value(row)?;
+                let fields: Vec<String> = columns.iter().map(|column| csv_field(&value[*column])).collect();
+                out.push_str(&fields.join(","));
+            }
+        }
+        out.push('\n');
+    }
+    Ok(Bytes::from(out))
+}
+
+/// A value as a CSV field: empty for null, quoted when it holds a separator,
+/// a quote or a line break
+///
+/// Text starting like a formula, such as a referrer of `=HYPERLINK(..)`, is prefixed
+/// with `'` so spreadsheets show it rather than evaluate it. Numbers are left as is.
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(text) if text.starts_with(['=', '+', '-', '@', '\t', '\r']) => format!("'{}", text),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
@@ -0,0 +1,46 @@
+// src/services/geoip.rs
+
+use log::debug;
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Resolves visitor IP addresses to countries using a MaxMind-format database,
+/// such as GeoLite2-Country or GeoIP2-City.
+///
+/// Without a database every lookup returns `None`, so country conditions never match.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    reader: Option<Arc<Reader<Vec<u8>>>>,
+}
+
+impl GeoIp {
+    /// Loads the database file at `path` into memory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, maxminddb::MaxMindDBError> {
+        let reader = Reader::open_readfile(path)?;
+        Ok(Self {
+            reader: Some(Arc::new(reader)),
+        })
+    }
+
+    /// A GeoIp without a database.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country the address is located in
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let reader = self.reader.as_ref()?;
+        match reader.lookup::<geoip2::Country>(ip) {
+            Ok(record) => record
+                .country
+                .and_then(|country| country.iso_code)
+                .map(str::to_string),
+            Err(e) => {
+                debug!("No GeoIP record for {}: {}", ip, e);
+                None
+            }
+        }
+    }
+}
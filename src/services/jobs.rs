@@ -0,0 +1,197 @@
+// src/services/jobs.rs
+
+use crate::errors::ServiceError;
+use crate::store::{PgClickRollups, RedisVisitorStore};
+use async_trait::async_trait;
+use chrono::Utc;
+use log::{debug, error, info};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Work the server repeats in the background.
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Name the job is logged under
+    fn name(&self) -> &'static str;
+
+    /// Time waited after a run before the next one starts
+    fn interval(&self) -> Duration;
+
+    /// Does the work that is due. Runs of a job never overlap.
+    async fn run(&self) -> Result<(), ServiceError>;
+}
+
+/// Runs each job in a task of its own, right away and then after every interval.
+///
+/// A failed run is logged and retried after the interval. Jobs are expected to
+/// keep their progress outside the process, so a run cut short by a restart is
+/// picked up by the next one.
+pub struct JobRunner {
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl JobRunner {
+    /// Starts running the jobs on the current runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The jobs to run.
+    pub fn start(jobs: Vec<Arc<dyn Job>>) -> Self {
+        let (shutdown, stopped) = watch::channel(false);
+        let tasks = jobs
+            .into_iter()
+            .map(|job| {
+                let mut stopped = stopped.clone();
+                tokio::spawn(async move {
+                    info!("Starting job {}", job.name());
+                    loop {
+                        if let Err(e) = job.run().await {
+                            error!("Job {} failed: {}", job.name(), e);
+                        }
+                        tokio::select! {
+                            _ = tokio::time::sleep(job.interval()) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self { shutdown, tasks }
+    }
+
+    /// Stops the jobs, waiting for the runs in progress to finish.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for task in self.tasks {
+            if let Err(e) = task.await {
+                error!("Job task failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Rolls up the clicks of every complete hour once `settle` has passed since its
+/// end, leaving clicks still queued by the click recorder time to be stored.
+pub struct ClickRollupJob {
+    rollups: PgClickRollups,
+    settle: Duration,
+    interval: Duration,
+}
+
+impl ClickRollupJob {
+    /// Creates a new ClickRollupJob.
+    ///
+    /// # Arguments
+    ///
+    /// * `rollups` - The rollup tables and their watermark.
+    /// * `settle` - Time after the end of an hour before it is rolled up.
+    /// * `interval` - Time between runs.
+    pub fn new(rollups: PgClickRollups, settle: Duration, interval: Duration) -> Self {
+        Self {
+            rollups,
+            settle,
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for ClickRollupJob {
+    fn name(&self) -> &'static str {
+        "click_rollups"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self) -> Result<(), ServiceError> {
+        let until = Utc::now() - chrono::Duration::from_std(self.settle).unwrap_or_default();
+        let watermark = self.rollups.roll_up_to(until).await?;
+        debug!("Clicks are rolled up until {}", watermark);
+        Ok(())
+    }
+}
+
+/// Purges stored clicks once they are older than the retention period and rolled
+/// up, leaving their counts in the rollups.
+pub struct ClickRetentionJob {
+    rollups: PgClickRollups,
+    retention: Duration,
+    interval: Duration,
+}
+
+impl ClickRetentionJob {
+    /// Creates a new ClickRetentionJob.
+    ///
+    /// # Arguments
+    ///
+    /// * `rollups` - The rollup tables the purged clicks are counted in.
+    /// * `retention` - Time clicks are kept for.
+    /// * `interval` - Time between runs.
+    pub fn new(rollups: PgClickRollups, retention: Duration, interval: Duration) -> Self {
+        Self {
+            rollups,
+            retention,
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for ClickRetentionJob {
+    fn name(&self) -> &'static str {
+        "click_retention"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self) -> Result<(), ServiceError> {
+        let before = Utc::now() - chrono::Duration::from_std(self.retention).unwrap_or_default();
+        let purged = self.rollups.purge_clicks(before).await?;
+        if purged > 0 {
+            info!("Purged {} clicks older than {}", purged, before);
+        }
+        Ok(())
+    }
+}
+
+/// Saves the visitor sketches changed since the previous run to PostgreSQL.
+pub struct SketchSaveJob {
+    store: RedisVisitorStore,
+    interval: Duration,
+}
+
+impl SketchSaveJob {
+    /// Creates a new SketchSaveJob.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The visitor store whose sketches are saved.
+    /// * `interval` - Time between runs.
+    pub fn new(store: RedisVisitorStore, interval: Duration) -> Self {
+        Self { store, interval }
+    }
+}
+
+#[async_trait]
+impl Job for SketchSaveJob {
+    fn name(&self) -> &'static str {
+        "visitor_sketches"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self) -> Result<(), ServiceError> {
+        let saved = self.store.save_sketches().await?;
+        debug!("Saved {} visitor sketches", saved);
+        Ok(())
+    }
+}
@@ -1,5 +1,25 @@
 // src/services/mod.rs
 
-pub mod url_service;
+pub mod accounts;
+pub mod alias;
+pub mod click_stream;
+pub mod clicks;
+pub mod code_allocator;
+pub mod export;
+pub mod geoip;
+pub mod jobs;
+pub mod privacy;
+pub mod redirect;
+pub mod unlock;
 
-pub use url_service::UrlService;
\ No newline at end of file
+pub use accounts::Accounts;
+pub use alias::AliasPolicy;
+pub use click_stream::ClickStream;
+pub use clicks::ClickRecorder;
+pub use code_allocator::CodeAllocator;
+pub use export::Export;
+pub use geoip::GeoIp;
+pub use jobs::{ClickRetentionJob, ClickRollupJob, Job, JobRunner, SketchSaveJob};
+pub use privacy::{ClickPrivacy, IpMode};
+pub use redirect::RedirectPolicy;
+pub use unlock::UnlockTokens;
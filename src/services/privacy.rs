@@ -0,0 +1,109 @@
+// src/services/privacy.rs
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How the IP address of a click identifies its visitor in `ClickEvent::ip_hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpMode {
+    /// Nothing is kept, so clicks have no visitor and unique counts stay at zero
+    Drop,
+    /// The network of the address: its first 24 bits for IPv4, 48 for IPv6
+    Truncate,
+    /// Keyed hash of the address
+    Hash,
+    /// Keyed hash of the address under a key derived from a random salt of each
+    /// UTC day, discarded once the day is over, so a visitor can't be followed from
+    /// one day to the next nor identified again afterwards
+    DailyHash,
+}
+
+impl FromStr for IpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "truncate" => Ok(Self::Truncate),
+            "hash" => Ok(Self::Hash),
+            "daily_hash" => Ok(Self::DailyHash),
+            other => Err(format!("unknown IP mode: {}", other)),
+        }
+    }
+}
+
+/// Decides what clicks keep about their visitors.
+#[derive(Clone)]
+pub struct ClickPrivacy {
+    ip_mode: IpMode,
+    ip_secret: Vec<u8>,
+    honor_do_not_track: bool,
+}
+
+impl ClickPrivacy {
+    /// Creates a new ClickPrivacy.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip_mode` - How IP addresses identify visitors.
+    /// * `ip_secret` - Key of the IP address hashes; must be shared by all instances.
+    /// * `honor_do_not_track` - Whether clicks sending `DNT: 1` or `Sec-GPC: 1` are
+    ///   stored without their visitor, referrer and User-Agent, nor anything derived
+    ///   from them.
+    pub fn new(ip_mode: IpMode, ip_secret: &[u8], honor_do_not_track: bool) -> Self {
+        Self {
+            ip_mode,
+            ip_secret: ip_secret.to_vec(),
+            honor_do_not_track,
+        }
+    }
+
+    /// Whether visitors asking not to be tracked are only counted in aggregate
+    pub fn honors_do_not_track(&self) -> bool {
+        self.honor_do_not_track
+    }
+
+    /// Whether visitors are identified with the salt of the day, from `ClickStore::daily_salt`
+    pub fn hashes_daily(&self) -> bool {
+        self.ip_mode == IpMode::DailyHash
+    }
+
+    /// Identifies the visitor at the address. Daily hashes need the salt of the day,
+    /// without which the visitor is left unidentified.
+    pub fn visitor_id(&self, ip: IpAddr, daily_salt: Option<&[u8]>) -> Option<String> {
+        let ip = ip.to_canonical();
+        match self.ip_mode {
+            IpMode::Drop => None,
+            IpMode::Truncate => Some(truncate(ip).to_string()),
+            IpMode::Hash => Some(hash(&self.ip_secret, ip)),
+            IpMode::DailyHash => {
+                let mut mac = HmacSha256::new_from_slice(&self.ip_secret)
+                    .expect("HMAC accepts keys of any size");
+                mac.update(daily_salt?);
+                Some(hash(&mac.finalize().into_bytes(), ip))
+            }
+        }
+    }
+}
+
+/// The address with the bits after its network prefix zeroed
+fn truncate(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => IpAddr::V4((u32::from(ip) & 0xffff_ff00).into()),
+        IpAddr::V6(ip) => IpAddr::V6((u128::from(ip) & !((1u128 << 80) - 1)).into()),
+    }
+}
+
+/// First 128 bits of the HMAC-SHA256 of the address, in hex
+fn hash(key: &[u8], ip: IpAddr) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(ip.to_string().as_bytes());
+    mac.finalize().into_bytes()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,115 @@
+// src/services/redirect.rs
+
+use crate::models::link::{Link, REDIRECT_STATUSES};
+use actix_web::http::header::{CacheControl, CacheDirective, Expires, HttpDate, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use chrono::Utc;
+use std::time::{Duration, SystemTime};
+
+/// Builds the redirect of a link together with its caching headers.
+///
+/// Temporary redirects (302, 307) are revalidated on every visit, so destination
+/// edits apply immediately. Permanent redirects (301, 308) may be cached for at
+/// most `max_age_seconds` and never past the link's expiry, since every link can
+/// still be edited or deactivated. Links with routing rules or a split are
+/// revalidated too, as their destination depends on the visitor. Links with a click limit or a
+/// password are never stored, as each visit has to reach the server.
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    default_status: i16,
+    max_age_seconds: u64,
+}
+
+impl RedirectPolicy {
+    /// Creates a new RedirectPolicy instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `default_status` - Status of links without their own redirect status.
+    /// * `max_age_seconds` - Longest time clients may cache a permanent redirect.
+    ///
+    /// Returns an error if `default_status` is not a redirect status.
+    pub fn new(default_status: i16, max_age_seconds: u64) -> Result<Self, String> {
+        if !REDIRECT_STATUSES.contains(&default_status) {
+            return Err(format!("unsupported redirect status: {}", default_status));
+        }
+        Ok(Self {
+            default_status,
+            max_age_seconds,
+        })
+    }
+
+    /// Status the link redirects with
+    pub fn status_for(&self, link: &Link) -> StatusCode {
+        let status = link
+            .redirect_status
+            .filter(|status| REDIRECT_STATUSES.contains(status))
+            .unwrap_or(self.default_status);
+        StatusCode::from_u16(status as u16).unwrap_or(StatusCode::FOUND)
+    }
+
+    /// Redirects to `location`, the destination of the link
+    pub fn redirect(&self, link: &Link, location: &str) -> HttpResponse {
+        let status = self.status_for(link);
+        let mut response = HttpResponse::build(status);
+        response.insert_header((LOCATION, location));
+
+        // Cache-Control ages are 32-bit
+        let max_age = u32::try_from(self.max_age(link, status)).unwrap_or(u32::MAX);
+        if max_age == 0 {
+            let directives = if link.click_limit.is_some() || link.password_hash.is_some() {
+                vec![CacheDirective::NoStore]
+            } else {
+                vec![CacheDirective::Private, CacheDirective::NoCache]
+            };
+            response
+                .insert_header(CacheControl(directives))
+                .insert_header(Expires(HttpDate::from(SystemTime::UNIX_EPOCH)));
+        } else {
+            let expires = SystemTime::now() + Duration::from_secs(max_age.into());
+            response
+                .insert_header(CacheControl(vec![
+                    CacheDirective::Public,
+                    CacheDirective::MaxAge(max_age),
+                ]))
+                .insert_header(Expires(HttpDate::from(expires)));
+        }
+
+        response.finish()
+    }
+
+    /// Temporarily redirects to the fallback URL of a link that can't be followed,
+    /// revalidated on every visit as the link may become available
+    pub fn fallback(&self, fallback_url: &str) -> HttpResponse {
+        HttpResponse::Found()
+            .insert_header((LOCATION, fallback_url))
+            .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+            .insert_header(Expires(HttpDate::from(SystemTime::UNIX_EPOCH)))
+            .finish()
+    }
+
+    /// Seconds clients may cache the redirect, 0 if it must not be reused
+    fn max_age(&self, link: &Link, status: StatusCode) -> u64 {
+        let permanent = matches!(
+            status,
+            StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT
+        );
+        if !permanent
+            || !link.rules.is_empty()
+            || !link.variants.is_empty()
+            || link.click_limit.is_some()
+            || link.password_hash.is_some()
+        {
+            return 0;
+        }
+
+        match link.expires_at {
+            Some(expires_at) => {
+                let remaining = (expires_at - Utc::now()).num_seconds().max(0) as u64;
+                remaining.min(self.max_age_seconds)
+            }
+            None => self.max_age_seconds,
+        }
+    }
+}
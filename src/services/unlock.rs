@@ -0,0 +1,91 @@
+// src/services/unlock.rs
+
+use crate::models::link::Link;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Name of the cookie holding an unlock token; it is scoped to `/{short_code}`.
+pub const UNLOCK_COOKIE: &str = "ktvr_unlock";
+
+/// Header API clients can use to pass the password of a protected link.
+pub const PASSWORD_HEADER: &str = "X-Link-Password";
+
+/// Issues and verifies signed tokens proving a visitor unlocked a protected link.
+///
+/// A token is `{expires_at}.{signature}`, where the HMAC-SHA256 signature covers the
+/// short code, the expiry and the current password hash, so changing the password
+/// invalidates outstanding tokens.
+#[derive(Clone)]
+pub struct UnlockTokens {
+    secret: Vec<u8>,
+    ttl_seconds: i64,
+}
+
+impl UnlockTokens {
+    /// Creates a new UnlockTokens instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The key used to sign tokens; must be shared by all instances.
+    /// * `ttl_seconds` - Time in seconds a token stays valid.
+    pub fn new(secret: &[u8], ttl_seconds: i64) -> Self {
+        Self {
+            secret: secret.to_vec(),
+            ttl_seconds,
+        }
+    }
+
+    /// Time in seconds a token stays valid.
+    pub fn ttl_seconds(&self) -> i64 {
+        self.ttl_seconds
+    }
+
+    /// Issues a token unlocking the given link.
+    pub fn issue(&self, link: &Link) -> String {
+        let expires_at = Utc::now().timestamp() + self.ttl_seconds;
+        let signature = self.mac(link, expires_at).finalize().into_bytes();
+        format!("{}.{}", expires_at, to_hex(&signature))
+    }
+
+    /// Returns true if the token was issued for the link and has not expired.
+    pub fn verify(&self, link: &Link, token: &str) -> bool {
+        let Some((expires_at, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let (Ok(expires_at), Some(signature)) = (expires_at.parse::<i64>(), from_hex(signature)) else {
+            return false;
+        };
+        if expires_at <= Utc::now().timestamp() {
+            return false;
+        }
+
+        self.mac(link, expires_at).verify_slice(&signature).is_ok()
+    }
+
+    fn mac(&self, link: &Link, expires_at: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(link.short_code.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires_at.to_string().as_bytes());
+        mac.update(b"\n");
+        mac.update(link.password_hash.as_deref().unwrap_or_default().as_bytes());
+        mac
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
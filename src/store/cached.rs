@@ -0,0 +1,108 @@
+// src/store/cached.rs
+
+use crate::errors::ServiceError;
+use crate::models::link::{Link, LinkChanges, NewLink};
+use crate::store::{DynLinkStore, LinkStore, RedisLinkStore};
+use async_trait::async_trait;
+use log::{debug, warn};
+use std::collections::HashMap;
+
+/// Link store that reads through a Redis cache in front of a primary store.
+///
+/// The primary store stays the single source of truth: writes, including consumed
+/// clicks, go to it and evict the cached entry, and cache failures are logged rather
+/// than failing the request.
+#[derive(Clone)]
+pub struct CachedLinkStore {
+    store: DynLinkStore,
+    cache: RedisLinkStore,
+}
+
+impl CachedLinkStore {
+    /// Creates a new CachedLinkStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The primary store, e.g. `PgLinkStore`.
+    /// * `cache` - The Redis store used as a cache, usually with an expiration set.
+    pub fn new(store: DynLinkStore, cache: RedisLinkStore) -> Self {
+        Self { store, cache }
+    }
+
+    async fn evict(&self, short_code: &str) {
+        if let Err(e) = self.cache.evict(short_code).await {
+            warn!("Failed to evict cached short code {}: {}", short_code, e);
+        }
+    }
+}
+
+#[async_trait]
+impl LinkStore for CachedLinkStore {
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError> {
+        self.store.create(link).await
+    }
+
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError> {
+        match self.cache.get_by_code(short_code).await {
+            Ok(Some(link)) => {
+                debug!("Cache hit for short code: {}", short_code);
+                return Ok(Some(link));
+            }
+            Ok(None) => debug!("Cache miss for short code: {}", short_code),
+            Err(e) => warn!("Cache lookup failed for {}: {}", short_code, e),
+        }
+
+        let link = self.store.get_by_code(short_code).await?;
+        if let Some(link) = &link {
+            if let Err(e) = self.cache.put(link).await {
+                warn!("Failed to cache short code {}: {}", short_code, e);
+            }
+        }
+        Ok(link)
+    }
+
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError> {
+        let updated = self.store.update(short_code, changes).await?;
+        self.evict(short_code).await;
+        Ok(updated)
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError> {
+        let deleted = self.store.delete(short_code).await?;
+        self.evict(short_code).await;
+        Ok(deleted)
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError> {
+        // Counters live in the primary store only. Evicting after every attempt, not
+        // just successful ones, also drops copies cached by a concurrent read just
+        // before another instance consumed the last click.
+        let consumed = self.store.consume_click(short_code).await?;
+        self.evict(short_code).await;
+        Ok(consumed)
+    }
+
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError> {
+        self.store.list(offset, limit).await
+    }
+
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError> {
+        self.store.list_owned(owner, tag).await
+    }
+
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError> {
+        self.store.record_variant_click(short_code, variant).await
+    }
+
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError> {
+        self.store.variant_clicks(short_code).await
+    }
+
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError> {
+        self.store.code_growth(namespace).await
+    }
+
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError> {
+        self.store.grow_codes(namespace, extra_length).await
+    }
+}
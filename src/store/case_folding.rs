@@ -0,0 +1,83 @@
+// src/store/case_folding.rs
+
+use crate::errors::ServiceError;
+use crate::models::link::{Link, LinkChanges, NewLink};
+use crate::store::{DynLinkStore, LinkStore};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// Link store treating short codes differing only in case as the same code.
+///
+/// Every code is folded to lower case before it reaches the wrapped store, whether it
+/// is a custom alias or the code of a request, so the wrapped store's unique
+/// constraint and exact lookups apply to the folded codes. Generated codes are
+/// expected to be lower case already, drawn from a folded alphabet, so the allocator
+/// knows how many codes there are. Links stored with upper case letters before the
+/// wrapper was put in place are no longer found.
+#[derive(Clone)]
+pub struct CaseFoldingLinkStore {
+    store: DynLinkStore,
+}
+
+impl CaseFoldingLinkStore {
+    /// Creates a new CaseFoldingLinkStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store keeping the links under their folded codes.
+    pub fn new(store: DynLinkStore) -> Self {
+        Self { store }
+    }
+}
+
+fn fold(short_code: &str) -> String {
+    short_code.to_lowercase()
+}
+
+#[async_trait]
+impl LinkStore for CaseFoldingLinkStore {
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError> {
+        let short_code = fold(&link.short_code);
+        self.store.create(NewLink { short_code, ..link }).await
+    }
+
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError> {
+        self.store.get_by_code(&fold(short_code)).await
+    }
+
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError> {
+        self.store.update(&fold(short_code), changes).await
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError> {
+        self.store.delete(&fold(short_code)).await
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError> {
+        self.store.consume_click(&fold(short_code)).await
+    }
+
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError> {
+        self.store.list(offset, limit).await
+    }
+
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError> {
+        self.store.list_owned(owner, tag).await
+    }
+
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError> {
+        self.store.record_variant_click(&fold(short_code), variant).await
+    }
+
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError> {
+        self.store.variant_clicks(&fold(short_code)).await
+    }
+
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError> {
+        self.store.code_growth(namespace).await
+    }
+
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError> {
+        self.store.grow_codes(namespace, extra_length).await
+    }
+}
@@ -0,0 +1,424 @@
+// src/store/memory.rs
+
+use crate::errors::ServiceError;
+use crate::models::click::{
+    ClickBucket, ClickCount, ClickDimension, ClickEvent, ClickQuery, Interval, TopValue,
+};
+use crate::models::export::{ClickPosition, ClickRollup, ExportScope};
+use crate::models::link::{Link, LinkChanges, NewLink};
+use crate::store::{ClickStore, LinkStore, VisitorStore};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Link store kept in process memory.
+///
+/// Nothing is persisted; intended for tests and local development.
+#[derive(Default)]
+pub struct MemoryLinkStore {
+    links: RwLock<HashMap<String, Link>>,
+    variant_clicks: RwLock<HashMap<String, HashMap<String, i64>>>,
+    code_growth: RwLock<HashMap<String, usize>>,
+}
+
+impl MemoryLinkStore {
+    /// Creates an empty MemoryLinkStore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LinkStore for MemoryLinkStore {
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError> {
+        let mut links = self.links.write().unwrap();
+        if links.contains_key(&link.short_code) {
+            return Err(ServiceError::Conflict(format!(
+                "Short code already in use: {}",
+                link.short_code
+            )));
+        }
+
+        let link = Link {
+            id: Uuid::new_v4(),
+            original_url: link.original_url,
+            short_code: link.short_code,
+            created_at: Utc::now(),
+            starts_at: link.starts_at,
+            expires_at: link.expires_at,
+            fallback_url: link.fallback_url,
+            password_hash: link.password_hash,
+            click_limit: link.click_limit,
+            click_count: 0,
+            redirect_status: link.redirect_status,
+            passthrough: link.passthrough,
+            rules: link.rules,
+            variants: link.variants,
+            card: link.card,
+            is_active: link.is_active,
+            owner: link.owner,
+            tags: link.tags,
+        };
+        links.insert(link.short_code.clone(), link.clone());
+        Ok(link)
+    }
+
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError> {
+        Ok(self.links.read().unwrap().get(short_code).cloned())
+    }
+
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError> {
+        let mut links = self.links.write().unwrap();
+        let Some(stored) = links.get_mut(short_code) else {
+            return Ok(None);
+        };
+
+        changes.apply_to(stored);
+        Ok(Some(stored.clone()))
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError> {
+        self.variant_clicks.write().unwrap().remove(short_code);
+        Ok(self.links.write().unwrap().remove(short_code).is_some())
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError> {
+        let mut links = self.links.write().unwrap();
+        match links.get_mut(short_code) {
+            Some(link) if !link.is_exhausted() => {
+                link.click_count += 1;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError> {
+        let mut links: Vec<Link> = self.links.read().unwrap().values().cloned().collect();
+        links.sort_by(|a, b| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then_with(|| a.short_code.cmp(&b.short_code))
+        });
+
+        Ok(links
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError> {
+        let mut links: Vec<Link> = self
+            .links
+            .read()
+            .unwrap()
+            .values()
+            .filter(|link| link.owner.as_deref() == Some(owner))
+            .filter(|link| tag.into_iter().all(|tag| link.is_tagged(tag)))
+            .cloned()
+            .collect();
+        links.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.short_code.cmp(&b.short_code))
+        });
+        Ok(links)
+    }
+
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError> {
+        if !self.links.read().unwrap().contains_key(short_code) {
+            return Ok(());
+        }
+        *self
+            .variant_clicks
+            .write()
+            .unwrap()
+            .entry(short_code.to_string())
+            .or_default()
+            .entry(variant.to_string())
+            .or_default() += 1;
+        Ok(())
+    }
+
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError> {
+        Ok(self
+            .variant_clicks
+            .read()
+            .unwrap()
+            .get(short_code)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError> {
+        Ok(self.code_growth.read().unwrap().get(namespace).copied().unwrap_or(0))
+    }
+
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError> {
+        let mut code_growth = self.code_growth.write().unwrap();
+        let growth = code_growth.entry(namespace.to_string()).or_default();
+        *growth = (*growth).max(extra_length);
+        Ok(*growth)
+    }
+}
+
+/// Click store kept in process memory.
+///
+/// Nothing is persisted; intended for tests and local development.
+#[derive(Default)]
+pub struct MemoryClickStore {
+    clicks: RwLock<Vec<ClickEvent>>,
+    daily_salts: RwLock<BTreeMap<NaiveDate, Vec<u8>>>,
+}
+
+impl MemoryClickStore {
+    /// Creates an empty MemoryClickStore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clicks stored so far, oldest first.
+    pub fn clicks(&self) -> Vec<ClickEvent> {
+        self.clicks.read().unwrap().clone()
+    }
+
+    /// Clicks matching the query
+    fn matching(&self, query: &ClickQuery) -> Vec<ClickEvent> {
+        self.clicks
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|click| query.matches(click))
+            .cloned()
+            .collect()
+    }
+
+    /// Deletes the clicks for which the predicate holds, returning how many there were
+    fn delete_where(&self, predicate: impl Fn(&ClickEvent) -> bool) -> u64 {
+        let mut clicks = self.clicks.write().unwrap();
+        let before = clicks.len();
+        clicks.retain(|click| !predicate(click));
+        (before - clicks.len()) as u64
+    }
+}
+
+fn count(clicks: &[&ClickEvent]) -> ClickCount {
+    let visitors: HashSet<&str> = clicks.iter().filter_map(|click| click.ip_hash.as_deref()).collect();
+    ClickCount {
+        clicks: clicks.len() as i64,
+        unique_clicks: visitors.len() as i64,
+    }
+}
+
+/// Returns true if the link and time are part of the export
+fn in_scope(scope: &ExportScope, link_id: Uuid, time: DateTime<Utc>) -> bool {
+    let linked = match &scope.links {
+        Some(links) => links.contains(&link_id),
+        None => true,
+    };
+    linked && scope.from <= time && time < scope.to
+}
+
+/// Returns true if the key comes after the one a page follows, if any
+fn follows<K: Ord>(key: &K, after: Option<&K>) -> bool {
+    match after {
+        Some(after) => key > after,
+        None => true,
+    }
+}
+
+#[async_trait]
+impl ClickStore for MemoryClickStore {
+    async fn insert_clicks(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError> {
+        self.clicks.write().unwrap().extend_from_slice(clicks);
+        Ok(())
+    }
+
+    async fn count_clicks(&self, query: &ClickQuery) -> Result<ClickCount, ServiceError> {
+        let clicks = self.matching(query);
+        Ok(count(&clicks.iter().collect::<Vec<_>>()))
+    }
+
+    async fn click_series(
+        &self,
+        query: &ClickQuery,
+        interval: Interval,
+    ) -> Result<Vec<ClickBucket>, ServiceError> {
+        let clicks = self.matching(query);
+        let mut buckets: BTreeMap<DateTime<Utc>, Vec<&ClickEvent>> = BTreeMap::new();
+        for click in &clicks {
+            buckets.entry(interval.truncate(click.occurred_at)).or_default().push(click);
+        }
+
+        Ok(buckets
+            .into_iter()
+            .map(|(start, clicks)| {
+                let count = count(&clicks);
+                ClickBucket {
+                    start,
+                    clicks: count.clicks,
+                    unique_clicks: count.unique_clicks,
+                }
+            })
+            .collect())
+    }
+
+    async fn top_values(
+        &self,
+        query: &ClickQuery,
+        dimension: ClickDimension,
+        limit: i64,
+    ) -> Result<Vec<TopValue>, ServiceError> {
+        let clicks = self.matching(query);
+        let mut counts: HashMap<&str, i64> = HashMap::new();
+        for value in clicks.iter().filter_map(|click| dimension.value_of(click)) {
+            *counts.entry(value).or_default() += 1;
+        }
+
+        let mut values: Vec<TopValue> = counts
+            .into_iter()
+            .map(|(value, clicks)| TopValue {
+                value: value.to_string(),
+                clicks,
+            })
+            .collect();
+        values.sort_by(|a, b| b.clicks.cmp(&a.clicks).then_with(|| a.value.cmp(&b.value)));
+        values.truncate(limit.max(0) as usize);
+        Ok(values)
+    }
+
+    async fn delete_link_clicks(&self, link_id: Uuid) -> Result<u64, ServiceError> {
+        Ok(self.delete_where(|click| click.link_id == link_id))
+    }
+
+    async fn export_clicks(
+        &self,
+        scope: &ExportScope,
+        after: Option<ClickPosition>,
+        limit: i64,
+    ) -> Result<Vec<(ClickPosition, ClickEvent)>, ServiceError> {
+        // Clicks are positioned by their index, as their order of insertion
+        let mut clicks: Vec<(ClickPosition, ClickEvent)> = self
+            .clicks
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, click)| in_scope(scope, click.link_id, click.occurred_at))
+            .map(|(index, click)| ((click.occurred_at, index as i64), click.clone()))
+            .filter(|(position, _)| follows(position, after.as_ref()))
+            .collect();
+        clicks.sort_by_key(|(position, _)| *position);
+        clicks.truncate(limit.max(0) as usize);
+        Ok(clicks)
+    }
+
+    async fn export_rollups(
+        &self,
+        scope: &ExportScope,
+        granularity: Interval,
+        after: Option<&ClickRollup>,
+        limit: i64,
+    ) -> Result<Vec<ClickRollup>, ServiceError> {
+        // Rolled up on the fly like the rollup job would, keyed in export order. The
+        // job stops at the current hour, leaving out the buckets that aren't over yet
+        let rolled_up_to = Interval::Hour.truncate(Utc::now());
+        let clicks = self.clicks();
+        let mut groups: BTreeMap<(DateTime<Utc>, Uuid, &str, bool, &str), Vec<&ClickEvent>> =
+            BTreeMap::new();
+        for click in &clicks {
+            let bucket = granularity.truncate(click.occurred_at);
+            if !in_scope(scope, click.link_id, bucket) || bucket + granularity.duration() > rolled_up_to {
+                continue;
+            }
+            let dimensions = ClickDimension::ALL
+                .iter()
+                .filter_map(|dimension| Some((dimension.column(), dimension.value_of(click)?)));
+            for (dimension, value) in std::iter::once(("total", "")).chain(dimensions) {
+                let key = (bucket, click.link_id, dimension, click.is_bot(), value);
+                groups.entry(key).or_default().push(click);
+            }
+        }
+
+        let after = after.map(|row| {
+            (row.bucket, row.link_id, row.dimension.as_str(), row.is_bot, row.value.as_str())
+        });
+        Ok(groups
+            .into_iter()
+            .filter(|(key, _)| follows(key, after.as_ref()))
+            .take(limit.max(0) as usize)
+            .map(|((bucket, link_id, dimension, is_bot, value), clicks)| {
+                let count = count(&clicks);
+                ClickRollup {
+                    link_id,
+                    bucket,
+                    is_bot,
+                    dimension: dimension.to_string(),
+                    value: value.to_string(),
+                    clicks: count.clicks,
+                    unique_clicks: count.unique_clicks,
+                }
+            })
+            .collect())
+    }
+
+    async fn delete_visitor_clicks(&self, ip_hash: &str, links: &[Uuid]) -> Result<u64, ServiceError> {
+        Ok(self.delete_where(|click| click.ip_hash.as_deref() == Some(ip_hash) && links.contains(&click.link_id)))
+    }
+
+    async fn daily_salt(&self, day: NaiveDate) -> Result<Vec<u8>, ServiceError> {
+        let mut daily_salts = self.daily_salts.write().unwrap();
+        daily_salts.retain(|salt_day, _| *salt_day >= day);
+        let salt = daily_salts.entry(day).or_insert_with(|| rand::random::<[u8; 32]>().to_vec());
+        Ok(salt.clone())
+    }
+}
+
+/// Visitor store kept in process memory, counting visitors exactly.
+///
+/// Nothing is persisted; intended for tests and local development.
+#[derive(Default)]
+pub struct MemoryVisitorStore {
+    visitors: RwLock<HashMap<(Uuid, NaiveDate), HashSet<String>>>,
+}
+
+impl MemoryVisitorStore {
+    /// Creates an empty MemoryVisitorStore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VisitorStore for MemoryVisitorStore {
+    async fn add_visitors(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError> {
+        let mut visitors = self.visitors.write().unwrap();
+        for click in clicks {
+            if let Some(ip_hash) = &click.ip_hash {
+                visitors
+                    .entry((click.link_id, click.occurred_at.date_naive()))
+                    .or_default()
+                    .insert(ip_hash.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn count_visitors(&self, link_id: Uuid, days: &[NaiveDate]) -> Result<u64, ServiceError> {
+        let visitors = self.visitors.read().unwrap();
+        let distinct: HashSet<&String> = days
+            .iter()
+            .filter_map(|day| visitors.get(&(link_id, *day)))
+            .flatten()
+            .collect();
+        Ok(distinct.len() as u64)
+    }
+
+    async fn delete_link(&self, link_id: Uuid) -> Result<(), ServiceError> {
+        self.visitors.write().unwrap().retain(|(link, _), _| *link != link_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+// src/store/mod.rs
+
+pub mod cached;
+pub mod case_folding;
+pub mod memory;
+pub mod postgres;
+pub mod redis;
+
+pub use cached::CachedLinkStore;
+pub use case_folding::CaseFoldingLinkStore;
+pub use memory::{MemoryClickStore, MemoryLinkStore, MemoryVisitorStore};
+pub use postgres::{PgClickRollups, PgClickStore, PgLinkStore, PgSketchArchive};
+pub use redis::{RedisLinkStore, RedisVisitorStore};
+
+use crate::errors::ServiceError;
+use crate::models::click::{
+    ClickBucket, ClickCount, ClickDimension, ClickEvent, ClickQuery, Interval, TopValue,
+};
+use crate::models::export::{ClickPosition, ClickRollup, ExportScope};
+use crate::models::link::{Link, LinkChanges, NewLink};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Shared handle to a link store, as registered in the application data.
+pub type DynLinkStore = Arc<dyn LinkStore>;
+
+/// Shared handle to a click store.
+pub type DynClickStore = Arc<dyn ClickStore>;
+
+/// Shared handle to a visitor store.
+pub type DynVisitorStore = Arc<dyn VisitorStore>;
+
+/// Persistence for short links.
+///
+/// Links are addressed by their short code, which is unique within a store.
+#[async_trait]
+pub trait LinkStore: Send + Sync {
+    /// Stores a new link.
+    ///
+    /// Returns `ServiceError::Conflict` if the short code is already taken.
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError>;
+
+    /// Fetches the link using the given short code, if any.
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError>;
+
+    /// Applies the changes to the link using the given short code, leaving its other
+    /// fields as they are stored, so concurrent updates of different fields all apply.
+    ///
+    /// Returns `Ok(None)` if no such link exists.
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError>;
+
+    /// Deletes the link using the given short code.
+    ///
+    /// Returns `Ok(false)` if no such link exists.
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError>;
+
+    /// Atomically consumes one click from the link's `click_limit`.
+    ///
+    /// Returns `Ok(false)` if the limit is already reached or no such link exists.
+    /// Links without a limit always succeed.
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError>;
+
+    /// Lists links, newest first.
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError>;
+
+    /// Links the account owns, only those carrying the tag if one is given, oldest first.
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError>;
+
+    /// Counts one redirect to the named variant of the link's split.
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError>;
+
+    /// Redirects counted per variant name of the link's split.
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError>;
+
+    /// Characters added to the generated codes of the namespace; 0 until it grows.
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError>;
+
+    /// Raises the characters added to the generated codes of the namespace to at
+    /// least `extra_length`, returning the number now in effect, which may be higher
+    /// if another instance raised it further.
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError>;
+}
+
+/// Persistence for click events.
+#[async_trait]
+pub trait ClickStore: Send + Sync {
+    /// Stores a batch of click events.
+    async fn insert_clicks(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError>;
+
+    /// Counts the clicks matching the query.
+    async fn count_clicks(&self, query: &ClickQuery) -> Result<ClickCount, ServiceError>;
+
+    /// Counts the clicks matching the query per bucket of the interval, oldest first.
+    ///
+    /// Buckets without clicks are left out.
+    async fn click_series(
+        &self,
+        query: &ClickQuery,
+        interval: Interval,
+    ) -> Result<Vec<ClickBucket>, ServiceError>;
+
+    /// Most frequent values of the attribute among the clicks matching the query,
+    /// most clicks first.
+    ///
+    /// Clicks without a value are left out.
+    async fn top_values(
+        &self,
+        query: &ClickQuery,
+        dimension: ClickDimension,
+        limit: i64,
+    ) -> Result<Vec<TopValue>, ServiceError>;
+
+    /// Deletes the clicks of the link together with their rollups, returning how
+    /// many clicks were deleted.
+    async fn delete_link_clicks(&self, link_id: Uuid) -> Result<u64, ServiceError>;
+
+    /// Clicks in the scope ordered by their position, in pages: up to `limit` clicks
+    /// following the position `after`, or from the first one, each with its position.
+    async fn export_clicks(
+        &self,
+        scope: &ExportScope,
+        after: Option<ClickPosition>,
+        limit: i64,
+    ) -> Result<Vec<(ClickPosition, ClickEvent)>, ServiceError>;
+
+    /// Rollup rows with buckets of the granularity, hours or days, starting in the
+    /// scope, in pages: up to `limit` rows following `after`, or from the first one.
+    /// Rows are ordered by bucket, link, dimension, bot classification and value.
+    /// Buckets the rollup job hasn't reached yet are left out.
+    async fn export_rollups(
+        &self,
+        scope: &ExportScope,
+        granularity: Interval,
+        after: Option<&ClickRollup>,
+        limit: i64,
+    ) -> Result<Vec<ClickRollup>, ServiceError>;
+
+    /// Deletes the clicks of the visitor identified as in `ClickEvent::ip_hash` on the
+    /// given links, returning how many were deleted. Rollups, which only count
+    /// visitors, are kept.
+    async fn delete_visitor_clicks(&self, ip_hash: &str, links: &[Uuid]) -> Result<u64, ServiceError>;
+
+    /// Random salt of the visitor hashes of the day, created by its first caller so
+    /// that all instances share it. Salts of earlier days are deleted, so their
+    /// hashes can't be computed again.
+    async fn daily_salt(&self, day: NaiveDate) -> Result<Vec<u8>, ServiceError>;
+}
+
+/// Distinct visitors of each link per UTC day, kept as mergeable sketches.
+///
+/// Sketches hold no identifiers, so a single visitor can't be taken out of them.
+#[async_trait]
+pub trait VisitorStore: Send + Sync {
+    /// Adds the visitors of the clicks to the sketches of their link and day.
+    ///
+    /// Clicks without an IP address hash are left out.
+    async fn add_visitors(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError>;
+
+    /// Counts the distinct visitors of the link over all the days together.
+    async fn count_visitors(&self, link_id: Uuid, days: &[NaiveDate]) -> Result<u64, ServiceError>;
+
+    /// Deletes the sketches of every day of the link.
+    async fn delete_link(&self, link_id: Uuid) -> Result<(), ServiceError>;
+}
@@ -0,0 +1,994 @@
+// src/store/postgres.rs
+
+use crate::errors::ServiceError;
+use crate::models::click::{
+    BotFilter, ClickBucket, ClickCount, ClickDimension, ClickEvent, ClickQuery, Interval, TopValue,
+    VisitorSketch,
+};
+use crate::models::export::{ClickPosition, ClickRollup, ExportScope};
+use crate::models::link::{Link, LinkChanges, NewLink};
+use crate::store::{ClickStore, LinkStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Columns selected for every `Link` read from the `links` table.
+const LINK_COLUMNS: &str = "id, original_url, short_code, created_at, starts_at, expires_at, fallback_url, \
+    password_hash, click_limit, click_count, redirect_status, passthrough, rules, variants, card, is_active, owner, \
+    tags";
+
+/// Most sketches written by one INSERT, keeping it under Postgres' bind limit
+const SKETCH_BATCH_SIZE: usize = 4096;
+
+/// Name of the click rollup job's watermark in `job_watermarks`
+const ROLLUP_JOB: &str = "click_rollups";
+
+/// Most hours rolled up in one transaction
+const ROLLUP_CHUNK_HOURS: i64 = 24;
+
+/// Name of the watermark in `job_watermarks` before which clicks may be purged
+const RETENTION_JOB: &str = "click_retention";
+
+/// Most clicks deleted by one statement when purging
+const PURGE_BATCH_SIZE: i64 = 10_000;
+
+/// Conditions selecting the clicks of a `ClickQuery`, whose link ID, start and end
+/// are bound to `$1`, `$2` and `$3`.
+fn click_conditions(bots: BotFilter) -> &'static str {
+    match bots {
+        BotFilter::Exclude => "link_id = $1 AND occurred_at >= $2 AND occurred_at < $3 AND bot_reason IS NULL",
+        BotFilter::Include => "link_id = $1 AND occurred_at >= $2 AND occurred_at < $3",
+        BotFilter::Only => "link_id = $1 AND occurred_at >= $2 AND occurred_at < $3 AND bot_reason IS NOT NULL",
+    }
+}
+
+/// Condition selecting the rollup rows of the clicks a `BotFilter` counts
+fn rollup_bot_condition(bots: BotFilter) -> &'static str {
+    match bots {
+        BotFilter::Exclude => "NOT is_bot",
+        BotFilter::Include => "TRUE",
+        BotFilter::Only => "is_bot",
+    }
+}
+
+/// Rollup table with buckets of the interval, which is hours or days
+fn rollup_table(granularity: Interval) -> &'static str {
+    match granularity {
+        Interval::Hour => "click_rollups_hourly",
+        _ => "click_rollups_daily",
+    }
+}
+
+/// Start of the first bucket of the interval beginning at or after the time
+fn next_bucket_start(granularity: Interval, time: DateTime<Utc>) -> DateTime<Utc> {
+    let start = granularity.truncate(time);
+    if start == time {
+        time
+    } else {
+        start + granularity.duration()
+    }
+}
+
+/// Columns of the `clicks` table read into a `ClickEvent`
+const CLICK_COLUMNS: &str = "link_id, short_code, occurred_at, referrer, referrer_host, user_agent, \
+    browser, os, device, ip_hash, country, variant, bot_reason";
+
+/// Order rollup rows are exported in, comparing text byte by byte whatever the
+/// database's collation
+const ROLLUP_EXPORT_ORDER: &str = r#"bucket, link_id, dimension COLLATE "C", is_bot, value COLLATE "C""#;
+
+/// Adds the conditions selecting the rows of the export scope, timed by the column
+fn push_scope_conditions(query: &mut QueryBuilder<'_, Postgres>, scope: &ExportScope, time_column: &str) {
+    query.push(format!(" WHERE {} >= ", time_column)).push_bind(scope.from);
+    query.push(format!(" AND {} < ", time_column)).push_bind(scope.to);
+    if let Some(links) = &scope.links {
+        query.push(" AND link_id = ANY(").push_bind(links.clone()).push(")");
+    }
+}
+
+/// A click read for an export, with the ID positioning it among clicks of the same time
+#[derive(FromRow)]
+struct ExportedClick {
+    id: i64,
+    #[sqlx(flatten)]
+    click: ClickEvent,
+}
+
+/// Link store backed by the PostgreSQL `links` table.
+#[derive(Clone)]
+pub struct PgLinkStore {
+    pool: Arc<PgPool>,
+}
+
+impl PgLinkStore {
+    /// Creates a new PgLinkStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The PostgreSQL connection pool holding the `links` table.
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LinkStore for PgLinkStore {
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError> {
+        let query = format!(
+            r#"
+            INSERT INTO links (
+                original_url, short_code, starts_at, expires_at, fallback_url, password_hash,
+                click_limit, redirect_status, passthrough, rules, variants, card, is_active, owner, tags
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15
+            )
+            RETURNING {}
+            "#,
+            LINK_COLUMNS
+        );
+
+        sqlx::query_as::<_, Link>(&query)
+            .bind(&link.original_url)
+            .bind(&link.short_code)
+            .bind(link.starts_at)
+            .bind(link.expires_at)
+            .bind(&link.fallback_url)
+            .bind(&link.password_hash)
+            .bind(link.click_limit)
+            .bind(link.redirect_status)
+            .bind(link.passthrough)
+            .bind(Json(&link.rules))
+            .bind(Json(&link.variants))
+            .bind(Json(&link.card))
+            .bind(link.is_active)
+            .bind(&link.owner)
+            .bind(&link.tags)
+            .fetch_one(self.pool.as_ref())
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_unique_violation() => {
+                    ServiceError::Conflict(format!("Short code already in use: {}", link.short_code))
+                }
+                _ => ServiceError::from(e),
+            })
+    }
+
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError> {
+        let query = format!("SELECT {} FROM links WHERE short_code = $1", LINK_COLUMNS);
+
+        let link = sqlx::query_as::<_, Link>(&query)
+            .bind(short_code)
+            .fetch_optional(self.pool.as_ref())
+            .await?;
+        Ok(link)
+    }
+
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError> {
+        // Only the changed columns are set, so a single UPDATE applies them atomically
+        let mut query = QueryBuilder::new("UPDATE links SET ");
+        let mut columns = query.separated(", ");
+        if let Some(original_url) = &changes.original_url {
+            columns.push("original_url = ").push_bind_unseparated(original_url);
+        }
+        if let Some(starts_at) = changes.starts_at {
+            columns.push("starts_at = ").push_bind_unseparated(starts_at);
+        }
+        if let Some(expires_at) = changes.expires_at {
+            columns.push("expires_at = ").push_bind_unseparated(expires_at);
+        }
+        if let Some(fallback_url) = &changes.fallback_url {
+            columns.push("fallback_url = ").push_bind_unseparated(fallback_url);
+        }
+        if let Some(password_hash) = &changes.password_hash {
+            columns.push("password_hash = ").push_bind_unseparated(password_hash);
+        }
+        if let Some(click_limit) = changes.click_limit {
+            columns.push("click_limit = ").push_bind_unseparated(click_limit);
+        }
+        if let Some(redirect_status) = changes.redirect_status {
+            columns.push("redirect_status = ").push_bind_unseparated(redirect_status);
+        }
+        if let Some(passthrough) = changes.passthrough {
+            columns.push("passthrough = ").push_bind_unseparated(passthrough);
+        }
+        if let Some(rules) = &changes.rules {
+            columns.push("rules = ").push_bind_unseparated(Json(rules));
+        }
+        if let Some(variants) = &changes.variants {
+            columns.push("variants = ").push_bind_unseparated(Json(variants));
+        }
+        if let Some(card) = &changes.card {
+            columns.push("card = ").push_bind_unseparated(Json(card));
+        }
+        if let Some(is_active) = changes.is_active {
+            columns.push("is_active = ").push_bind_unseparated(is_active);
+        }
+        // Without changes the link is left as is; setting its code to itself keeps the
+        // statement valid and still tells whether it exists
+        columns.push("short_code = short_code");
+
+        query
+            .push(" WHERE short_code = ")
+            .push_bind(short_code)
+            .push(format!(" RETURNING {}", LINK_COLUMNS));
+        let link = query
+            .build_query_as::<Link>()
+            .fetch_optional(self.pool.as_ref())
+            .await?;
+        Ok(link)
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError> {
+        let result = sqlx::query("DELETE FROM links WHERE short_code = $1")
+            .bind(short_code)
+            .execute(self.pool.as_ref())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError> {
+        // The row lock taken by UPDATE serializes concurrent redirects across instances
+        let result = sqlx::query(
+            r#"
+            UPDATE links
+            SET click_count = click_count + 1
+            WHERE short_code = $1 AND (click_limit IS NULL OR click_count < click_limit)
+            "#,
+        )
+            .bind(short_code)
+            .execute(self.pool.as_ref())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError> {
+        let query = format!(
+            "SELECT {} FROM links ORDER BY created_at DESC, short_code OFFSET $1 LIMIT $2",
+            LINK_COLUMNS
+        );
+
+        let links = sqlx::query_as::<_, Link>(&query)
+            .bind(offset)
+            .bind(limit)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(links)
+    }
+
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError> {
+        let query = format!(
+            r#"
+            SELECT {} FROM links
+            WHERE owner = $1 AND ($2::TEXT IS NULL OR tags @> ARRAY[$2])
+            ORDER BY created_at, short_code
+            "#,
+            LINK_COLUMNS
+        );
+
+        let links = sqlx::query_as::<_, Link>(&query)
+            .bind(owner)
+            .bind(tag)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(links)
+    }
+
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError> {
+        sqlx::query(
+            r#"
+            INSERT INTO link_variant_clicks (link_id, variant, clicks)
+            SELECT id, $2, 1 FROM links WHERE short_code = $1
+            ON CONFLICT (link_id, variant) DO UPDATE SET clicks = link_variant_clicks.clicks + 1
+            "#,
+        )
+            .bind(short_code)
+            .bind(variant)
+            .execute(self.pool.as_ref())
+            .await?;
+        Ok(())
+    }
+
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT c.variant, c.clicks
+            FROM link_variant_clicks c
+            JOIN links l ON l.id = c.link_id
+            WHERE l.short_code = $1
+            "#,
+        )
+            .bind(short_code)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError> {
+        let extra_length: Option<i32> =
+            sqlx::query_scalar("SELECT extra_length FROM short_code_namespaces WHERE name = $1")
+                .bind(namespace)
+                .fetch_optional(self.pool.as_ref())
+                .await?;
+        Ok(extra_length.unwrap_or(0) as usize)
+    }
+
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError> {
+        let extra_length: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO short_code_namespaces (name, extra_length) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE
+            SET extra_length = GREATEST(short_code_namespaces.extra_length, EXCLUDED.extra_length)
+            RETURNING extra_length
+            "#,
+        )
+            .bind(namespace)
+            .bind(extra_length as i32)
+            .fetch_one(self.pool.as_ref())
+            .await?;
+        Ok(extra_length as usize)
+    }
+}
+
+/// Click store backed by the PostgreSQL `clicks` table.
+///
+/// Counts are read from the rollup tables for the whole buckets the rollup job has
+/// completed, and from the `clicks` table for the rest of the range, typically the
+/// current bucket. Distinct visitors of a weekly bucket are summed over its days.
+#[derive(Clone)]
+pub struct PgClickStore {
+    pool: Arc<PgPool>,
+}
+
+impl PgClickStore {
+    /// Creates a new PgClickStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The PostgreSQL connection pool holding the `clicks` table.
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Part of the query's range read from the rollups with buckets of the
+    /// granularity: the whole buckets before the rollup watermark. Empty, starting
+    /// and ending at the same time, if there are none.
+    ///
+    /// Clicks of purged days are only left in the rollups, so the range is widened
+    /// to the whole buckets its ends fall in when they are purged.
+    async fn rolled_up_range(
+        &self,
+        query: &ClickQuery,
+        granularity: Interval,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), ServiceError> {
+        let watermarks: HashMap<String, DateTime<Utc>> =
+            sqlx::query_as("SELECT job, watermark FROM job_watermarks WHERE job IN ($1, $2)")
+                .bind(ROLLUP_JOB)
+                .bind(RETENTION_JOB)
+                .fetch_all(self.pool.as_ref())
+                .await?
+                .into_iter()
+                .collect();
+        let purged = watermarks.get(RETENTION_JOB).copied();
+        let is_purged = |time: DateTime<Utc>| purged.is_some_and(|purged| time < purged);
+
+        let start = if is_purged(query.from) {
+            granularity.truncate(query.from)
+        } else {
+            next_bucket_start(granularity, query.from)
+        };
+        let end = match watermarks.get(ROLLUP_JOB) {
+            Some(_) if is_purged(query.to) => next_bucket_start(granularity, query.to),
+            Some(watermark) => granularity.truncate(query.to.min(*watermark)),
+            None => start,
+        };
+        Ok((start, end.max(start)))
+    }
+}
+
+#[async_trait]
+impl ClickStore for PgClickStore {
+    async fn insert_clicks(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError> {
+        if clicks.is_empty() {
+            return Ok(());
+        }
+
+        // Shared with other inserts, but not with the rollup job, so that clicks are
+        // either seen by the run in progress or stored after it moved the watermark
+        let mut tx = self.pool.begin().await?;
+        let watermark: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT watermark FROM job_watermarks WHERE job = $1 FOR SHARE")
+                .bind(ROLLUP_JOB)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        // A single multi-row INSERT per batch
+        let mut query = QueryBuilder::new(
+            "INSERT INTO clicks (link_id, short_code, occurred_at, referrer, referrer_host, \
+             user_agent, browser, os, device, ip_hash, country, variant, bot_reason) ",
+        );
+        query.push_values(clicks, |mut row, click| {
+            row.push_bind(click.link_id)
+                .push_bind(&click.short_code)
+                .push_bind(click.occurred_at)
+                .push_bind(&click.referrer)
+                .push_bind(&click.referrer_host)
+                .push_bind(&click.user_agent)
+                .push_bind(&click.browser)
+                .push_bind(&click.os)
+                .push_bind(&click.device)
+                .push_bind(&click.ip_hash)
+                .push_bind(&click.country)
+                .push_bind(&click.variant)
+                .push_bind(&click.bot_reason);
+        });
+        query.build().execute(&mut *tx).await?;
+
+        // Hours already rolled up are rolled up again by the next run, in order so
+        // that concurrent inserts lock them in the same order
+        let late_hours: BTreeSet<DateTime<Utc>> = clicks
+            .iter()
+            .map(|click| Interval::Hour.truncate(click.occurred_at))
+            .filter(|hour| watermark.is_some_and(|watermark| *hour < watermark))
+            .collect();
+        if !late_hours.is_empty() {
+            let mut query = QueryBuilder::new("INSERT INTO late_click_hours (hour) ");
+            query.push_values(&late_hours, |mut row, hour| {
+                row.push_bind(*hour);
+            });
+            query.push(" ON CONFLICT (hour) DO NOTHING");
+            query.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn count_clicks(&self, query: &ClickQuery) -> Result<ClickCount, ServiceError> {
+        let (rolled_from, rolled_to) = self.rolled_up_range(query, Interval::Day).await?;
+        let sql = format!(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(clicks), 0)::BIGINT
+                 FROM click_rollups_daily
+                 WHERE link_id = $1 AND bucket >= $4 AND bucket < $5
+                   AND dimension = 'total' AND {rollups})
+                + (SELECT COUNT(*)
+                   FROM clicks
+                   WHERE {conditions} AND NOT (occurred_at >= $4 AND occurred_at < $5)),
+                (SELECT COUNT(DISTINCT ip_hash) FROM clicks WHERE {conditions})
+            "#,
+            rollups = rollup_bot_condition(query.bots),
+            conditions = click_conditions(query.bots)
+        );
+
+        let (clicks, unique_clicks): (i64, i64) = sqlx::query_as(&sql)
+            .bind(query.link_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(rolled_from)
+            .bind(rolled_to)
+            .fetch_one(self.pool.as_ref())
+            .await?;
+        Ok(ClickCount { clicks, unique_clicks })
+    }
+
+    async fn click_series(
+        &self,
+        query: &ClickQuery,
+        interval: Interval,
+    ) -> Result<Vec<ClickBucket>, ServiceError> {
+        let granularity = match interval {
+            Interval::Hour => Interval::Hour,
+            _ => Interval::Day,
+        };
+        let (rolled_from, rolled_to) = self.rolled_up_range(query, granularity).await?;
+        let sql = format!(
+            r#"
+            SELECT bucket, SUM(clicks)::BIGINT, SUM(unique_clicks)::BIGINT
+            FROM (
+                SELECT date_trunc($6, bucket AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket,
+                       clicks, unique_clicks
+                FROM {table}
+                WHERE link_id = $1 AND bucket >= $4 AND bucket < $5
+                  AND dimension = 'total' AND {rollups}
+                UNION ALL
+                SELECT date_trunc($6, occurred_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC',
+                       COUNT(*), COUNT(DISTINCT ip_hash)
+                FROM clicks
+                WHERE {conditions} AND NOT (occurred_at >= $4 AND occurred_at < $5)
+                GROUP BY 1
+            ) AS buckets
+            GROUP BY bucket
+            ORDER BY bucket
+            "#,
+            table = rollup_table(granularity),
+            rollups = rollup_bot_condition(query.bots),
+            conditions = click_conditions(query.bots)
+        );
+
+        let rows: Vec<(DateTime<Utc>, i64, i64)> = sqlx::query_as(&sql)
+            .bind(query.link_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(rolled_from)
+            .bind(rolled_to)
+            .bind(interval.as_str())
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(start, clicks, unique_clicks)| ClickBucket {
+                start,
+                clicks,
+                unique_clicks,
+            })
+            .collect())
+    }
+
+    async fn top_values(
+        &self,
+        query: &ClickQuery,
+        dimension: ClickDimension,
+        limit: i64,
+    ) -> Result<Vec<TopValue>, ServiceError> {
+        let (rolled_from, rolled_to) = self.rolled_up_range(query, Interval::Day).await?;
+        // The column comes from a fixed list, never from the request
+        let sql = format!(
+            r#"
+            SELECT value, SUM(clicks)::BIGINT AS clicks
+            FROM (
+                SELECT value, clicks
+                FROM click_rollups_daily
+                WHERE link_id = $1 AND bucket >= $4 AND bucket < $5
+                  AND dimension = $6 AND {rollups}
+                UNION ALL
+                SELECT {column}, COUNT(*)
+                FROM clicks
+                WHERE {conditions} AND NOT (occurred_at >= $4 AND occurred_at < $5)
+                  AND {column} IS NOT NULL
+                GROUP BY {column}
+            ) AS counts
+            GROUP BY value
+            ORDER BY clicks DESC, value
+            LIMIT $7
+            "#,
+            column = dimension.column(),
+            rollups = rollup_bot_condition(query.bots),
+            conditions = click_conditions(query.bots)
+        );
+
+        let rows: Vec<(String, i64)> = sqlx::query_as(&sql)
+            .bind(query.link_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(rolled_from)
+            .bind(rolled_to)
+            .bind(dimension.column())
+            .bind(limit)
+            .fetch_all(self.pool.as_ref())
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(value, clicks)| TopValue { value, clicks })
+            .collect())
+    }
+
+    async fn delete_link_clicks(&self, link_id: Uuid) -> Result<u64, ServiceError> {
+        let mut tx = self.pool.begin().await?;
+        for granularity in [Interval::Hour, Interval::Day] {
+            sqlx::query(&format!("DELETE FROM {} WHERE link_id = $1", rollup_table(granularity)))
+                .bind(link_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let deleted = sqlx::query("DELETE FROM clicks WHERE link_id = $1")
+            .bind(link_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn export_clicks(
+        &self,
+        scope: &ExportScope,
+        after: Option<ClickPosition>,
+        limit: i64,
+    ) -> Result<Vec<(ClickPosition, ClickEvent)>, ServiceError> {
+        let mut query = QueryBuilder::new(format!("SELECT id, {} FROM clicks", CLICK_COLUMNS));
+        push_scope_conditions(&mut query, scope, "occurred_at");
+        if let Some((occurred_at, id)) = after {
+            query
+                .push(" AND (occurred_at, id) > (")
+                .push_bind(occurred_at)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        query.push(" ORDER BY occurred_at, id LIMIT ").push_bind(limit);
+
+        let rows: Vec<ExportedClick> = query.build_query_as().fetch_all(self.pool.as_ref()).await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ((row.click.occurred_at, row.id), row.click))
+            .collect())
+    }
+
+    async fn export_rollups(
+        &self,
+        scope: &ExportScope,
+        granularity: Interval,
+        after: Option<&ClickRollup>,
+        limit: i64,
+    ) -> Result<Vec<ClickRollup>, ServiceError> {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT link_id, bucket, is_bot, dimension, value, clicks, unique_clicks FROM {}",
+            rollup_table(granularity)
+        ));
+        push_scope_conditions(&mut query, scope, "bucket");
+        if let Some(row) = after {
+            query
+                .push(format!(" AND ({}) > (", ROLLUP_EXPORT_ORDER))
+                .push_bind(row.bucket)
+                .push(", ")
+                .push_bind(row.link_id)
+                .push(", ")
+                .push_bind(row.dimension.clone())
+                .push(", ")
+                .push_bind(row.is_bot)
+                .push(", ")
+                .push_bind(row.value.clone())
+                .push(")");
+        }
+        query
+            .push(format!(" ORDER BY {} LIMIT ", ROLLUP_EXPORT_ORDER))
+            .push_bind(limit);
+
+        Ok(query.build_query_as().fetch_all(self.pool.as_ref()).await?)
+    }
+
+    async fn delete_visitor_clicks(&self, ip_hash: &str, links: &[Uuid]) -> Result<u64, ServiceError> {
+        let deleted = sqlx::query("DELETE FROM clicks WHERE ip_hash = $1 AND link_id = ANY($2)")
+            .bind(ip_hash)
+            .bind(links)
+            .execute(self.pool.as_ref())
+            .await?
+            .rows_affected();
+        Ok(deleted)
+    }
+
+    async fn daily_salt(&self, day: NaiveDate) -> Result<Vec<u8>, ServiceError> {
+        sqlx::query("DELETE FROM daily_salts WHERE day < $1")
+            .bind(day)
+            .execute(self.pool.as_ref())
+            .await?;
+        // The no-op update returns the salt another instance created first
+        let salt = sqlx::query_scalar(
+            r#"
+            INSERT INTO daily_salts (day, salt) VALUES ($1, $2)
+            ON CONFLICT (day) DO UPDATE SET day = EXCLUDED.day
+            RETURNING salt
+            "#,
+        )
+        .bind(day)
+        .bind(rand::random::<[u8; 32]>().to_vec())
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        Ok(salt)
+    }
+}
+
+/// Rolls clicks up into the `click_rollups_hourly` and `click_rollups_daily` tables.
+///
+/// Hours are rolled up in time order from a watermark kept in `job_watermarks`, each
+/// chunk in one transaction together with the watermark, so an interrupted run
+/// resumes where it stopped. Rolling up replaces the rows of the buckets, which makes
+/// it idempotent. Days are rolled up once their last hour is. Hours clicks were
+/// stored in after they were rolled up are listed in `late_click_hours` and rolled
+/// up again, along with their days.
+#[derive(Clone)]
+pub struct PgClickRollups {
+    pool: Arc<PgPool>,
+}
+
+impl PgClickRollups {
+    /// Creates a new PgClickRollups instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The PostgreSQL connection pool holding the `clicks` and rollup tables.
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Rolls up the hours from the watermark to `until`, rounded down to the hour, and
+    /// returns the new watermark. The first run starts at the hour of the oldest click.
+    ///
+    /// Hours before the watermark that clicks were stored in late are rolled up again
+    /// first, except for purged ones: their rollups are all that's left of the clicks
+    /// stored in time, so the late clicks are left to be purged.
+    pub async fn roll_up_to(&self, until: DateTime<Utc>) -> Result<DateTime<Utc>, ServiceError> {
+        let until = Interval::Hour.truncate(until);
+        sqlx::query(
+            r#"
+            INSERT INTO job_watermarks (job, watermark)
+            SELECT $1, COALESCE(date_trunc('hour', MIN(occurred_at) AT TIME ZONE 'UTC') AT TIME ZONE 'UTC', $2)
+            FROM clicks
+            ON CONFLICT (job) DO NOTHING
+            "#,
+        )
+        .bind(ROLLUP_JOB)
+        .bind(until)
+        .execute(self.pool.as_ref())
+        .await?;
+
+        loop {
+            let mut tx = self.pool.begin().await?;
+            // Locked so that instances sharing the database take turns
+            let watermark: DateTime<Utc> =
+                sqlx::query_scalar("SELECT watermark FROM job_watermarks WHERE job = $1 FOR UPDATE")
+                    .bind(ROLLUP_JOB)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            roll_up_late_hours(&mut tx, watermark).await?;
+            if watermark >= until {
+                tx.commit().await?;
+                return Ok(watermark);
+            }
+
+            let end = until.min(watermark + Duration::hours(ROLLUP_CHUNK_HOURS));
+            roll_up(&mut tx, Interval::Hour, watermark, end).await?;
+            let (first_day, end_day) = (Interval::Day.truncate(watermark), Interval::Day.truncate(end));
+            if first_day < end_day {
+                roll_up(&mut tx, Interval::Day, first_day, end_day).await?;
+            }
+
+            sqlx::query("UPDATE job_watermarks SET watermark = $2, updated_at = now() WHERE job = $1")
+                .bind(ROLLUP_JOB)
+                .bind(end)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+    }
+
+    /// Rolls up the hours and days overlapping `from` to `to` again, such as after
+    /// clicks were stored late, in one transaction. Leaves the watermark as is.
+    ///
+    /// Purged days are left out, as their rollups are all that's left of them.
+    pub async fn rebuild(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<(), ServiceError> {
+        let mut tx = self.pool.begin().await?;
+        let from = from.max(lock_retention_watermark(&mut tx).await?);
+        if from >= to {
+            tx.commit().await?;
+            return Ok(());
+        }
+        // Cleared first, so that hours clicks are stored in from now on stay listed
+        sqlx::query("DELETE FROM late_click_hours WHERE hour >= $1 AND hour < $2")
+            .bind(Interval::Hour.truncate(from))
+            .bind(next_bucket_start(Interval::Hour, to))
+            .execute(&mut *tx)
+            .await?;
+        roll_up(
+            &mut tx,
+            Interval::Hour,
+            Interval::Hour.truncate(from),
+            next_bucket_start(Interval::Hour, to),
+        )
+        .await?;
+        roll_up(
+            &mut tx,
+            Interval::Day,
+            Interval::Day.truncate(from),
+            next_bucket_start(Interval::Day, to),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the clicks before `before`, rounded down to the day, once they are
+    /// rolled up, and returns how many were deleted. Stats of the purged days are
+    /// then read from the rollups alone, and rebuilding leaves them out.
+    pub async fn purge_clicks(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        let watermark: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT watermark FROM job_watermarks WHERE job = $1")
+                .bind(ROLLUP_JOB)
+                .fetch_optional(self.pool.as_ref())
+                .await?;
+        let Some(watermark) = watermark else {
+            return Ok(0);
+        };
+        // Nor are the late clicks waiting to be rolled up purged
+        let late: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT MIN(hour) FROM late_click_hours")
+            .fetch_one(self.pool.as_ref())
+            .await?;
+        let cutoff = Interval::Day.truncate(before.min(watermark).min(late.unwrap_or(watermark)));
+
+        // Recorded first, so stats stop reading the purged days from the clicks
+        sqlx::query(
+            r#"
+            INSERT INTO job_watermarks (job, watermark)
+            VALUES ($1, $2)
+            ON CONFLICT (job) DO UPDATE
+            SET watermark = GREATEST(job_watermarks.watermark, EXCLUDED.watermark), updated_at = now()
+            "#,
+        )
+        .bind(RETENTION_JOB)
+        .bind(cutoff)
+        .execute(self.pool.as_ref())
+        .await?;
+
+        // Deleted in batches to keep transactions and locks short
+        let mut purged = 0;
+        loop {
+            let deleted = sqlx::query(
+                "DELETE FROM clicks WHERE id IN (SELECT id FROM clicks WHERE occurred_at < $1 LIMIT $2)",
+            )
+            .bind(cutoff)
+            .bind(PURGE_BATCH_SIZE)
+            .execute(self.pool.as_ref())
+            .await?
+            .rows_affected();
+            purged += deleted;
+            if deleted < PURGE_BATCH_SIZE as u64 {
+                return Ok(purged);
+            }
+        }
+    }
+}
+
+/// Rolls up the hours listed in `late_click_hours` again, and the days before the
+/// rollup watermark they fall in, then clears the list. Hours of purged days are
+/// dropped from the list as they are.
+async fn roll_up_late_hours(conn: &mut PgConnection, watermark: DateTime<Utc>) -> Result<(), ServiceError> {
+    let hours: Vec<DateTime<Utc>> = sqlx::query_scalar("DELETE FROM late_click_hours RETURNING hour")
+        .fetch_all(&mut *conn)
+        .await?;
+    if hours.is_empty() {
+        return Ok(());
+    }
+    let purged = lock_retention_watermark(conn).await?;
+    let hours: BTreeSet<DateTime<Utc>> = hours.into_iter().filter(|hour| *hour >= purged).collect();
+    let days: BTreeSet<DateTime<Utc>> = hours
+        .iter()
+        .map(|hour| Interval::Day.truncate(*hour))
+        .filter(|day| *day < Interval::Day.truncate(watermark))
+        .collect();
+    for hour in hours {
+        roll_up(conn, Interval::Hour, hour, hour + Interval::Hour.duration()).await?;
+    }
+    for day in days {
+        roll_up(conn, Interval::Day, day, day + Interval::Day.duration()).await?;
+    }
+    Ok(())
+}
+
+/// Start of the days whose clicks haven't been purged, locked until the end of the
+/// transaction so that no more are purged while they are rolled up
+async fn lock_retention_watermark(conn: &mut PgConnection) -> Result<DateTime<Utc>, ServiceError> {
+    let purged: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT watermark FROM job_watermarks WHERE job = $1 FOR SHARE")
+            .bind(RETENTION_JOB)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(purged.unwrap_or(DateTime::<Utc>::MIN_UTC))
+}
+
+/// Replaces the rollup rows of the buckets of the granularity from `from` to `to`
+/// with the counts of the clicks in them.
+async fn roll_up(
+    conn: &mut PgConnection,
+    granularity: Interval,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(), ServiceError> {
+    let table = rollup_table(granularity);
+    sqlx::query(&format!("DELETE FROM {} WHERE bucket >= $1 AND bucket < $2", table))
+        .bind(from)
+        .bind(to)
+        .execute(&mut *conn)
+        .await?;
+
+    // One row per click and dimension, the columns coming from a fixed list
+    let dimensions: Vec<String> = std::iter::once("('total', '')".to_string())
+        .chain(ClickDimension::ALL.iter().map(|dimension| {
+            format!("('{column}', {column})", column = dimension.column())
+        }))
+        .collect();
+    let sql = format!(
+        r#"
+        INSERT INTO {table} (link_id, bucket, is_bot, dimension, value, clicks, unique_clicks)
+        SELECT link_id,
+               date_trunc($3, occurred_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket,
+               bot_reason IS NOT NULL AS is_bot,
+               dimensions.dimension,
+               dimensions.value,
+               COUNT(*),
+               COUNT(DISTINCT ip_hash)
+        FROM clicks
+        CROSS JOIN LATERAL (VALUES {dimensions}) AS dimensions (dimension, value)
+        WHERE occurred_at >= $1 AND occurred_at < $2 AND dimensions.value IS NOT NULL
+        GROUP BY link_id, bucket, is_bot, dimensions.dimension, dimensions.value
+        "#,
+        table = table,
+        dimensions = dimensions.join(", ")
+    );
+    sqlx::query(&sql)
+        .bind(from)
+        .bind(to)
+        .bind(granularity.as_str())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Copies of visitor sketches in the PostgreSQL `visitor_sketches` table, which
+/// outlive the ones evicted from Redis.
+#[derive(Clone)]
+pub struct PgSketchArchive {
+    pool: Arc<PgPool>,
+}
+
+impl PgSketchArchive {
+    /// Creates a new PgSketchArchive instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The PostgreSQL connection pool holding the `visitor_sketches` table.
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Fetches the stored sketches of the given links and days; days without one
+    /// are left out.
+    pub async fn load(&self, keys: &[(Uuid, NaiveDate)]) -> Result<Vec<VisitorSketch>, ServiceError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (link_ids, days): (Vec<Uuid>, Vec<NaiveDate>) = keys.iter().copied().unzip();
+        let sketches = sqlx::query_as::<_, VisitorSketch>(
+            r#"
+            SELECT link_id, day, sketch
+            FROM visitor_sketches
+            JOIN UNNEST($1::uuid[], $2::date[]) AS requested (link_id, day) USING (link_id, day)
+            "#,
+        )
+        .bind(link_ids)
+        .bind(days)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(sketches)
+    }
+
+    /// Stores the sketches, replacing the ones of the same link and day.
+    pub async fn save(&self, sketches: &[VisitorSketch]) -> Result<(), ServiceError> {
+        for chunk in sketches.chunks(SKETCH_BATCH_SIZE) {
+            let mut query = QueryBuilder::new("INSERT INTO visitor_sketches (link_id, day, sketch) ");
+            query.push_values(chunk, |mut row, sketch| {
+                row.push_bind(sketch.link_id)
+                    .push_bind(sketch.day)
+                    .push_bind(&sketch.sketch);
+            });
+            query.push(
+                " ON CONFLICT (link_id, day) DO UPDATE SET sketch = EXCLUDED.sketch, updated_at = now()",
+            );
+            query.build().execute(self.pool.as_ref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the stored sketches of every day of the link.
+    pub async fn delete_link(&self, link_id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query("DELETE FROM visitor_sketches WHERE link_id = $1")
+            .bind(link_id)
+            .execute(self.pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,573 @@
+// src/store/redis.rs
+
+use crate::errors::ServiceError;
+use crate::models::click::{ClickEvent, VisitorSketch};
+use crate::models::link::{Link, LinkChanges, NewLink};
+use crate::store::{LinkStore, PgSketchArchive, VisitorStore};
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Consumes one click if the link exists and its limit is not reached.
+/// KEYS[1] is the link key and KEYS[2] its click counter; returns 1 on success.
+const CONSUME_CLICK_SCRIPT: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then
+    return 0
+end
+local limit = cjson.decode(value)['click_limit']
+if limit == nil or limit == cjson.null then
+    return 1
+end
+local count = tonumber(redis.call('GET', KEYS[2]) or '0')
+if count >= limit then
+    return 0
+end
+redis.call('INCR', KEYS[2])
+return 1
+"#;
+
+/// Raises a namespace's code growth, never lowering it. KEYS[1] is the hash of
+/// namespaces, ARGV[1] the namespace and ARGV[2] the wanted growth; returns the
+/// growth in effect.
+const GROW_CODES_SCRIPT: &str = r#"
+local current = tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0')
+local wanted = tonumber(ARGV[2])
+if wanted > current then
+    redis.call('HSET', KEYS[1], ARGV[1], wanted)
+    return wanted
+end
+return current
+"#;
+
+/// Merges a stored sketch into a sketch key, keeping the visitors counted there.
+/// KEYS[1] is the sketch key and KEYS[2] a scratch key; ARGV[1] is the stored sketch
+/// and ARGV[2] the expiration of the sketch key in seconds.
+const RESTORE_SKETCH_SCRIPT: &str = r#"
+redis.call('SET', KEYS[2], ARGV[1], 'EX', 60)
+redis.call('PFMERGE', KEYS[1], KEYS[1], KEYS[2])
+redis.call('DEL', KEYS[2])
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 1
+"#;
+
+/// Most sketches read from Redis at once when saving them
+const SAVE_BATCH_SIZE: usize = 1000;
+
+/// Link store keeping each link as JSON under `{key_prefix}{short_code}`.
+///
+/// Clicks consumed against `click_limit` are counted separately under
+/// `clicks:{key_prefix}{short_code}` so they can be incremented atomically, and
+/// redirects per variant in the hash `variants:{key_prefix}{short_code}`. The growth
+/// of generated codes is kept per namespace in the hash `namespaces:{key_prefix}`.
+/// A sorted set scored by creation time backs `list`, and a set of short codes per
+/// account under `owner:{key_prefix}{owner}` backs `list_owned`; only links created
+/// through the store are indexed, not copies stored with `put`. When an expiration is set the
+/// store behaves as a cache: entries vanish after the TTL, or when the link itself
+/// expires if that comes first, and are dropped from the index when listing.
+#[derive(Clone)]
+pub struct RedisLinkStore {
+    // Wrapped in Arc<Mutex<>> to allow safe concurrent mutable access
+    redis_conn: Arc<Mutex<MultiplexedConnection>>,
+    key_prefix: String,
+    expiration_seconds: Option<usize>,
+}
+
+impl RedisLinkStore {
+    /// Creates a new RedisLinkStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_conn` - An Arc-wrapped Mutex-protected MultiplexedConnection to Redis.
+    /// * `key_prefix` - A prefix for Redis keys to namespace links.
+    /// * `expiration_seconds` - Optional time in seconds after which stored links expire.
+    pub fn new(
+        redis_conn: Arc<Mutex<MultiplexedConnection>>,
+        key_prefix: String,
+        expiration_seconds: Option<usize>,
+    ) -> Self {
+        Self {
+            redis_conn,
+            key_prefix,
+            expiration_seconds,
+        }
+    }
+
+    /// Stores the link as is, overwriting any existing entry for its short code.
+    ///
+    /// The link is not indexed: copies cached in front of another store would
+    /// otherwise pile up in the index as their entries expire.
+    pub async fn put(&self, link: &Link) -> Result<(), ServiceError> {
+        let value = serde_json::to_string(link)?;
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(&link.short_code)).arg(value);
+        if let Some(seconds) = self.expiration_for(link) {
+            cmd.arg("EX").arg(seconds);
+        }
+
+        let mut conn = self.redis_conn.lock().await;
+        cmd.query_async::<_, ()>(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Removes the entry for the short code, if any.
+    pub async fn evict(&self, short_code: &str) -> Result<bool, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        let removed: usize = conn.del(self.key(short_code)).await?;
+        conn.del::<_, ()>(self.clicks_key(short_code)).await?;
+        conn.del::<_, ()>(self.variants_key(short_code)).await?;
+        conn.zrem::<_, _, ()>(self.index_key(), short_code).await?;
+        Ok(removed > 0)
+    }
+
+    /// TTL for the link's entry: the configured expiration, capped at the time left
+    /// until the link's `expires_at`. Links without a configured expiration never expire.
+    fn expiration_for(&self, link: &Link) -> Option<usize> {
+        let seconds = self.expiration_seconds?;
+        match link.expires_at {
+            Some(expires_at) => {
+                let remaining = (expires_at - Utc::now()).num_seconds().max(1) as usize;
+                Some(seconds.min(remaining))
+            }
+            None => Some(seconds),
+        }
+    }
+
+    fn key(&self, short_code: &str) -> String {
+        format!("{}{}", self.key_prefix, short_code)
+    }
+
+    fn clicks_key(&self, short_code: &str) -> String {
+        format!("clicks:{}{}", self.key_prefix, short_code)
+    }
+
+    fn variants_key(&self, short_code: &str) -> String {
+        format!("variants:{}{}", self.key_prefix, short_code)
+    }
+
+    fn index_key(&self) -> String {
+        format!("index:{}", self.key_prefix)
+    }
+
+    fn owner_key(&self, owner: &str) -> String {
+        format!("owner:{}{}", self.key_prefix, owner)
+    }
+
+    fn namespaces_key(&self) -> String {
+        format!("namespaces:{}", self.key_prefix)
+    }
+}
+
+#[async_trait]
+impl LinkStore for RedisLinkStore {
+    async fn create(&self, link: NewLink) -> Result<Link, ServiceError> {
+        let link = Link {
+            id: Uuid::new_v4(),
+            original_url: link.original_url,
+            short_code: link.short_code,
+            created_at: Utc::now(),
+            starts_at: link.starts_at,
+            expires_at: link.expires_at,
+            fallback_url: link.fallback_url,
+            password_hash: link.password_hash,
+            click_limit: link.click_limit,
+            click_count: 0,
+            redirect_status: link.redirect_status,
+            passthrough: link.passthrough,
+            rules: link.rules,
+            variants: link.variants,
+            card: link.card,
+            is_active: link.is_active,
+            owner: link.owner,
+            tags: link.tags,
+        };
+
+        let value = serde_json::to_string(&link)?;
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(&link.short_code)).arg(value).arg("NX");
+        if let Some(seconds) = self.expiration_for(&link) {
+            cmd.arg("EX").arg(seconds);
+        }
+
+        let mut conn = self.redis_conn.lock().await;
+        let stored: Option<String> = cmd.query_async(&mut *conn).await?;
+        if stored.is_none() {
+            return Err(ServiceError::Conflict(format!(
+                "Short code already in use: {}",
+                link.short_code
+            )));
+        }
+        conn.zadd::<_, _, _, ()>(self.index_key(), &link.short_code, link.created_at.timestamp_millis())
+            .await?;
+        if let Some(owner) = &link.owner {
+            conn.sadd::<_, _, ()>(self.owner_key(owner), &link.short_code).await?;
+        }
+
+        Ok(link)
+    }
+
+    async fn get_by_code(&self, short_code: &str) -> Result<Option<Link>, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        let (value, click_count): (Option<String>, Option<i32>) = redis::cmd("MGET")
+            .arg(self.key(short_code))
+            .arg(self.clicks_key(short_code))
+            .query_async(&mut *conn)
+            .await?;
+
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let mut link: Link = serde_json::from_str(&value)?;
+        if let Some(click_count) = click_count {
+            link.click_count = click_count;
+        }
+        Ok(Some(link))
+    }
+
+    async fn update(&self, short_code: &str, changes: &LinkChanges) -> Result<Option<Link>, ServiceError> {
+        let key = self.key(short_code);
+        // The connection stays locked from WATCH to EXEC, so the watch only sees writes
+        // of other connections; the entry is read again when one landed in between
+        let mut conn = self.redis_conn.lock().await;
+        loop {
+            redis::cmd("WATCH").arg(&key).query_async::<_, ()>(&mut *conn).await?;
+            let (value, click_count): (Option<String>, Option<i32>) = redis::cmd("MGET")
+                .arg(&key)
+                .arg(self.clicks_key(short_code))
+                .query_async(&mut *conn)
+                .await?;
+            let Some(value) = value else {
+                redis::cmd("UNWATCH").query_async::<_, ()>(&mut *conn).await?;
+                return Ok(None);
+            };
+
+            let mut link: Link = serde_json::from_str(&value)?;
+            changes.apply_to(&mut link);
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(&key).arg(serde_json::to_string(&link)?);
+            if let Some(seconds) = self.expiration_for(&link) {
+                cmd.arg("EX").arg(seconds);
+            }
+
+            let written: Option<()> = redis::pipe()
+                .atomic()
+                .add_command(cmd)
+                .ignore()
+                .query_async(&mut *conn)
+                .await?;
+            if written.is_some() {
+                if let Some(click_count) = click_count {
+                    link.click_count = click_count;
+                }
+                return Ok(Some(link));
+            }
+        }
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, ServiceError> {
+        self.evict(short_code).await
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        let consumed: i32 = redis::Script::new(CONSUME_CLICK_SCRIPT)
+            .key(self.key(short_code))
+            .key(self.clicks_key(short_code))
+            .invoke_async(&mut *conn)
+            .await?;
+        Ok(consumed == 1)
+    }
+
+    async fn list(&self, offset: i64, limit: i64) -> Result<Vec<Link>, ServiceError> {
+        if limit <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let short_codes: Vec<String> = {
+            let mut conn = self.redis_conn.lock().await;
+            conn.zrevrange(self.index_key(), offset as isize, (offset + limit - 1) as isize)
+                .await?
+        };
+
+        let mut links = Vec::with_capacity(short_codes.len());
+        let mut expired = Vec::new();
+        for short_code in short_codes {
+            // Entries with an expiration may be gone while still indexed
+            match self.get_by_code(&short_code).await? {
+                Some(link) => links.push(link),
+                None => expired.push(short_code),
+            }
+        }
+        if !expired.is_empty() {
+            let mut conn = self.redis_conn.lock().await;
+            conn.zrem::<_, _, ()>(self.index_key(), expired).await?;
+        }
+        Ok(links)
+    }
+
+    async fn list_owned(&self, owner: &str, tag: Option<&str>) -> Result<Vec<Link>, ServiceError> {
+        let short_codes: Vec<String> = {
+            let mut conn = self.redis_conn.lock().await;
+            conn.smembers(self.owner_key(owner)).await?
+        };
+
+        let mut links = Vec::with_capacity(short_codes.len());
+        let mut stale = Vec::new();
+        for short_code in short_codes {
+            // Codes stay listed after their link is deleted, or created again by another account
+            match self.get_by_code(&short_code).await? {
+                Some(link) if link.owner.as_deref() == Some(owner) => {
+                    if tag.into_iter().all(|tag| link.is_tagged(tag)) {
+                        links.push(link);
+                    }
+                }
+                _ => stale.push(short_code),
+            }
+        }
+        if !stale.is_empty() {
+            let mut conn = self.redis_conn.lock().await;
+            conn.srem::<_, _, ()>(self.owner_key(owner), stale).await?;
+        }
+        links.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.short_code.cmp(&b.short_code))
+        });
+        Ok(links)
+    }
+
+    async fn record_variant_click(&self, short_code: &str, variant: &str) -> Result<(), ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        conn.hincr::<_, _, _, ()>(self.variants_key(short_code), variant, 1)
+            .await?;
+        Ok(())
+    }
+
+    async fn variant_clicks(&self, short_code: &str) -> Result<HashMap<String, i64>, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        Ok(conn.hgetall(self.variants_key(short_code)).await?)
+    }
+
+    async fn code_growth(&self, namespace: &str) -> Result<usize, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        let extra_length: Option<usize> = conn.hget(self.namespaces_key(), namespace).await?;
+        Ok(extra_length.unwrap_or(0))
+    }
+
+    async fn grow_codes(&self, namespace: &str, extra_length: usize) -> Result<usize, ServiceError> {
+        let mut conn = self.redis_conn.lock().await;
+        let extra_length: usize = redis::Script::new(GROW_CODES_SCRIPT)
+            .key(self.namespaces_key())
+            .arg(namespace)
+            .arg(extra_length)
+            .invoke_async(&mut *conn)
+            .await?;
+        Ok(extra_length)
+    }
+}
+
+/// Visitor store keeping a HyperLogLog per link and UTC day under
+/// `visitors:{key_prefix}{link_id}:{day}`.
+///
+/// Sketches expire once their day has seen no clicks for the configured time. Saved
+/// to the archive by `save_sketches`, days evicted from Redis are restored when they
+/// are counted or receive new visitors.
+#[derive(Clone)]
+pub struct RedisVisitorStore {
+    redis_conn: Arc<Mutex<MultiplexedConnection>>,
+    key_prefix: String,
+    expiration_seconds: usize,
+    archive: PgSketchArchive,
+    /// Links and days whose sketch changed since it was last saved
+    unsaved: Arc<std::sync::Mutex<HashSet<(Uuid, NaiveDate)>>>,
+}
+
+impl RedisVisitorStore {
+    /// Creates a new RedisVisitorStore instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `redis_conn` - An Arc-wrapped Mutex-protected MultiplexedConnection to Redis.
+    /// * `key_prefix` - A prefix for Redis keys, shared with the link cache.
+    /// * `expiration_seconds` - Time in seconds after its last visitor a sketch is kept in Redis.
+    /// * `archive` - Where sketches are saved to and restored from.
+    pub fn new(
+        redis_conn: Arc<Mutex<MultiplexedConnection>>,
+        key_prefix: String,
+        expiration_seconds: usize,
+        archive: PgSketchArchive,
+    ) -> Self {
+        Self {
+            redis_conn,
+            key_prefix,
+            expiration_seconds,
+            archive,
+            unsaved: Arc::default(),
+        }
+    }
+
+    /// Saves the sketches changed since the last save to the archive, returning how
+    /// many were saved. On failure they are kept for the next save.
+    pub async fn save_sketches(&self) -> Result<usize, ServiceError> {
+        let keys: Vec<_> = std::mem::take(&mut *self.unsaved.lock().unwrap())
+            .into_iter()
+            .collect();
+        let result = self.copy_to_archive(&keys).await;
+        if result.is_err() {
+            self.unsaved.lock().unwrap().extend(keys);
+        }
+        result
+    }
+
+    async fn copy_to_archive(&self, keys: &[(Uuid, NaiveDate)]) -> Result<usize, ServiceError> {
+        let mut saved = 0;
+        for chunk in keys.chunks(SAVE_BATCH_SIZE) {
+            let values: Vec<Option<Vec<u8>>> = {
+                let mut pipe = redis::pipe();
+                for key in chunk {
+                    pipe.get(self.key(*key));
+                }
+                let mut conn = self.redis_conn.lock().await;
+                pipe.query_async(&mut *conn).await?
+            };
+
+            // Sketches evicted in the meantime are left as last saved
+            let sketches: Vec<VisitorSketch> = chunk
+                .iter()
+                .zip(values)
+                .filter_map(|(&(link_id, day), sketch)| {
+                    Some(VisitorSketch {
+                        link_id,
+                        day,
+                        sketch: sketch?,
+                    })
+                })
+                .collect();
+            self.archive.save(&sketches).await?;
+            saved += sketches.len();
+        }
+        Ok(saved)
+    }
+
+    /// Merges the archived sketches of the links and days into Redis
+    async fn restore(&self, keys: &[(Uuid, NaiveDate)]) -> Result<(), ServiceError> {
+        let sketches = self.archive.load(keys).await?;
+        if sketches.is_empty() {
+            return Ok(());
+        }
+
+        let script = redis::Script::new(RESTORE_SKETCH_SCRIPT);
+        let mut conn = self.redis_conn.lock().await;
+        for sketch in sketches {
+            let key = self.key((sketch.link_id, sketch.day));
+            script
+                .key(&key)
+                .key(format!("restore:{}", key))
+                .arg(sketch.sketch)
+                .arg(self.expiration_seconds)
+                .invoke_async::<_, ()>(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Links and days among the given ones without a sketch in Redis
+    async fn missing(&self, keys: &[(Uuid, NaiveDate)]) -> Result<Vec<(Uuid, NaiveDate)>, ServiceError> {
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.exists(self.key(*key));
+        }
+        let exists: Vec<bool> = {
+            let mut conn = self.redis_conn.lock().await;
+            pipe.query_async(&mut *conn).await?
+        };
+        Ok(keys
+            .iter()
+            .zip(exists)
+            .filter(|(_, exists)| !exists)
+            .map(|(key, _)| *key)
+            .collect())
+    }
+
+    fn key(&self, (link_id, day): (Uuid, NaiveDate)) -> String {
+        format!("visitors:{}{}:{}", self.key_prefix, link_id, day)
+    }
+}
+
+#[async_trait]
+impl VisitorStore for RedisVisitorStore {
+    async fn add_visitors(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError> {
+        let mut visitors: HashMap<(Uuid, NaiveDate), Vec<&str>> = HashMap::new();
+        for click in clicks {
+            if let Some(ip_hash) = &click.ip_hash {
+                visitors
+                    .entry((click.link_id, click.occurred_at.date_naive()))
+                    .or_default()
+                    .push(ip_hash);
+            }
+        }
+        if visitors.is_empty() {
+            return Ok(());
+        }
+
+        let keys: Vec<_> = visitors.keys().copied().collect();
+        let created = self.missing(&keys).await?;
+
+        let mut pipe = redis::pipe();
+        for (key, ip_hashes) in &visitors {
+            pipe.pfadd(self.key(*key), ip_hashes)
+                .ignore()
+                .expire(self.key(*key), self.expiration_seconds)
+                .ignore();
+        }
+        {
+            let mut conn = self.redis_conn.lock().await;
+            pipe.query_async::<_, ()>(&mut *conn).await?;
+        }
+
+        // A sketch created now may have been evicted before, with visitors of its own
+        self.restore(&created).await?;
+        self.unsaved.lock().unwrap().extend(keys);
+        Ok(())
+    }
+
+    async fn count_visitors(&self, link_id: Uuid, days: &[NaiveDate]) -> Result<u64, ServiceError> {
+        if days.is_empty() {
+            return Ok(0);
+        }
+
+        let keys: Vec<_> = days.iter().map(|day| (link_id, *day)).collect();
+        let missing = self.missing(&keys).await?;
+        self.restore(&missing).await?;
+
+        let names: Vec<String> = keys.into_iter().map(|key| self.key(key)).collect();
+        let mut conn = self.redis_conn.lock().await;
+        Ok(conn.pfcount(names).await?)
+    }
+
+    async fn delete_link(&self, link_id: Uuid) -> Result<(), ServiceError> {
+        self.unsaved.lock().unwrap().retain(|(link, _)| *link != link_id);
+        // Archived first, so a sketch evicted meanwhile can't be restored from there
+        self.archive.delete_link(link_id).await?;
+
+        let mut conn = self.redis_conn.lock().await;
+        let pattern = format!("visitors:{}{}:*", self.key_prefix, link_id);
+        let names: Vec<String> = {
+            let mut iter = conn.scan_match::<_, String>(pattern).await?;
+            let mut names = Vec::new();
+            while let Some(name) = iter.next_item().await {
+                names.push(name);
+            }
+            names
+        };
+        if !names.is_empty() {
+            conn.del::<_, ()>(names).await?;
+        }
+        Ok(())
+    }
+}
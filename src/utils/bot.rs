@@ -0,0 +1,83 @@
+// src/utils/bot.rs
+
+use actix_web::http::{header, Method};
+use actix_web::HttpRequest;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Maintained list of User-Agent patterns, see the file for its format
+const PATTERNS: &str = include_str!("bot_patterns.txt");
+
+/// Request headers browsers set on speculative prefetches and previews
+const PREFETCH_HEADERS: [&str; 4] = ["purpose", "sec-purpose", "x-purpose", "x-moz"];
+
+/// Why a request was classified as coming from a bot rather than a person
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotSignal {
+    /// The User-Agent contains the pattern
+    UserAgent(&'static str),
+    /// The request has no User-Agent
+    MissingUserAgent,
+    /// HEAD requests are sent by monitors and link checkers, not followed by people
+    HeadRequest,
+    /// The browser is prefetching or previewing the link, not following it
+    Prefetch,
+}
+
+impl fmt::Display for BotSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotSignal::UserAgent(pattern) => write!(f, "user_agent:{}", pattern),
+            BotSignal::MissingUserAgent => write!(f, "missing_user_agent"),
+            BotSignal::HeadRequest => write!(f, "head_request"),
+            BotSignal::Prefetch => write!(f, "prefetch"),
+        }
+    }
+}
+
+fn patterns() -> &'static [&'static str] {
+    static PARSED: OnceLock<Vec<&'static str>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        PATTERNS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    })
+}
+
+/// Pattern of the bot list the User-Agent matches, if any
+pub fn match_user_agent(user_agent: &str) -> Option<&'static str> {
+    let user_agent = user_agent.to_ascii_lowercase();
+    patterns().iter().copied().find(|pattern| user_agent.contains(pattern))
+}
+
+/// Classifies the request, returning why it comes from a bot or `None` for a person
+pub fn classify(req: &HttpRequest) -> Option<BotSignal> {
+    if req.method() == Method::HEAD {
+        return Some(BotSignal::HeadRequest);
+    }
+
+    let prefetch = PREFETCH_HEADERS.iter().any(|name| {
+        req.headers()
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                let value = value.to_ascii_lowercase();
+                value.contains("prefetch") || value.contains("preview")
+            })
+    });
+    if prefetch {
+        return Some(BotSignal::Prefetch);
+    }
+
+    match req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+    {
+        None | Some("") => Some(BotSignal::MissingUserAgent),
+        Some(user_agent) => match_user_agent(user_agent).map(BotSignal::UserAgent),
+    }
+}
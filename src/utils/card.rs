@@ -0,0 +1,76 @@
+// src/utils/card.rs
+
+use crate::models::card::SocialCard;
+use crate::utils::html;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+
+/// User-Agent tokens of the bots chats and social networks send to unfurl links
+const UNFURL_BOTS: [&str; 12] = [
+    "slackbot",
+    "slack-imgproxy",
+    "skypeuripreview",
+    "microsoftpreview",
+    "facebookexternalhit",
+    "facebot",
+    "twitterbot",
+    "linkedinbot",
+    "discordbot",
+    "telegrambot",
+    "whatsapp",
+    "pinterestbot",
+];
+
+/// Returns true if the User-Agent belongs to a known unfurl bot
+pub fn is_unfurl_bot(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_ascii_lowercase();
+    UNFURL_BOTS.iter().any(|bot| user_agent.contains(bot))
+}
+
+/// Returns true if the request comes from a known unfurl bot
+pub fn from_unfurl_bot(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(is_unfurl_bot)
+}
+
+/// Renders the Open Graph and Twitter meta tags of the card in an HTML document
+pub fn page(card: &SocialCard) -> String {
+    let mut meta = String::new();
+    let mut push = |attribute: &str, name: &str, content: &str| {
+        meta.push_str(&format!(
+            "<meta {}=\"{}\" content=\"{}\">\n",
+            attribute,
+            name,
+            html::escape(content)
+        ));
+    };
+
+    push("property", "og:type", "website");
+    if let Some(title) = &card.title {
+        push("property", "og:title", title);
+        push("name", "twitter:title", title);
+    }
+    if let Some(description) = &card.description {
+        push("property", "og:description", description);
+        push("name", "twitter:description", description);
+    }
+    let twitter_card = match &card.image {
+        Some(image) => {
+            push("property", "og:image", image);
+            push("name", "twitter:image", image);
+            "summary_large_image"
+        }
+        None => "summary",
+    };
+    push("name", "twitter:card", twitter_card);
+
+    let title = card.title.as_deref().unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{}</head>\n<body>\n<h1>{}</h1>\n</body>\n</html>\n",
+        html::escape(title),
+        meta,
+        html::escape(title)
+    )
+}
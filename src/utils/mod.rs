@@ -0,0 +1,10 @@
+// src/utils/mod.rs
+
+pub mod bot;
+pub mod card;
+pub mod html;
+pub mod passthrough;
+pub mod password;
+pub mod short_code;
+pub mod split;
+pub mod visitor;
@@ -0,0 +1,61 @@
+// src/utils/passthrough.rs
+
+use crate::models::link::Passthrough;
+use std::collections::HashSet;
+use url::form_urlencoded;
+use url::Url;
+
+/// Builds the redirect target from the link's `destination` and the parts of the
+/// request URL its passthrough mode forwards.
+///
+/// Extra path segments are appended to the destination's path. Query parameters are
+/// merged into the destination's: a key sent in the request replaces every value the
+/// destination has for it, other destination parameters keep their order and the
+/// request's parameters follow in theirs. The destination's fragment is kept.
+///
+/// Returns `None` if the request has extra path segments the link doesn't forward.
+pub fn destination(
+    destination: &str,
+    passthrough: Passthrough,
+    extra_path: Option<&str>,
+    query: &str,
+) -> Option<String> {
+    let extra_path = extra_path.filter(|path| !path.is_empty());
+    if extra_path.is_some() && passthrough != Passthrough::PathAndQuery {
+        return None;
+    }
+    if passthrough == Passthrough::None || (extra_path.is_none() && query.is_empty()) {
+        return Some(destination.to_string());
+    }
+
+    let Ok(mut url) = Url::parse(destination) else {
+        return Some(destination.to_string());
+    };
+
+    if let Some(extra_path) = extra_path {
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments
+                .pop_if_empty()
+                .extend(extra_path.split('/').filter(|segment| !segment.is_empty()));
+        }
+    }
+
+    let forwarded: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    if !forwarded.is_empty() {
+        let replaced: HashSet<&str> = forwarded.iter().map(|(key, _)| key.as_str()).collect();
+        let kept: Vec<(String, String)> = url
+            .query_pairs()
+            .into_owned()
+            .filter(|(key, _)| !replaced.contains(key.as_str()))
+            .collect();
+
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .extend_pairs(forwarded);
+    }
+
+    Some(url.to_string())
+}
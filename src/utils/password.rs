@@ -0,0 +1,33 @@
+// src/utils/password.rs
+
+use argon2::password_hash::{PasswordHash, Salt};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+/// Hashes the given password using Argon2
+pub fn hash_password(password: &str) -> String {
+    let salt: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    // Initialize Argon2 instance with default parameters
+    let argon2 = Argon2::default();
+
+    // Hash the password with the generated salt
+    let password_hash = argon2.hash_password(password.as_bytes(), Salt::from_b64(&salt).unwrap()).unwrap().to_string();
+
+    password_hash
+}
+
+/// Checks the given password against an Argon2 hash produced by `hash_password`
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,343 @@
+// src/utils/short_code.rs
+
+use crate::errors::ServiceError;
+use async_trait::async_trait;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// Digits, upper and lower case ASCII letters
+pub const BASE62_ALPHABET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Shared handle to a short code generator, as registered in the application data.
+pub type DynShortCodeGenerator = Arc<dyn ShortCodeGenerator>;
+
+/// Input of a single short code generation.
+#[derive(Debug, Clone, Copy)]
+pub struct CodeRequest<'a> {
+    /// Destination of the link the code is for.
+    pub original_url: &'a str,
+    /// Number of candidates already rejected for this link, so deterministic
+    /// strategies can derive a different code.
+    pub attempt: u32,
+    /// Characters to add to the configured length, raised when collisions get frequent.
+    pub extra_length: usize,
+}
+
+impl<'a> CodeRequest<'a> {
+    /// First attempt at a code of the configured length.
+    pub fn new(original_url: &'a str) -> Self {
+        Self {
+            original_url,
+            attempt: 0,
+            extra_length: 0,
+        }
+    }
+}
+
+/// Code space a strategy draws codes from at random, where collisions get more
+/// frequent as it fills up and longer codes make them rare again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeNamespace {
+    /// Names the strategy, alphabet and length, so changing any of them starts over
+    pub name: String,
+    /// Most characters collisions may add to the configured length
+    pub max_extra_length: usize,
+}
+
+/// Strategy producing candidate short codes for new links.
+///
+/// Candidates may already be taken; uniqueness is enforced when the link is stored.
+#[async_trait]
+pub trait ShortCodeGenerator: Send + Sync {
+    /// Generates a candidate short code.
+    async fn generate(&self, request: &CodeRequest<'_>) -> Result<String, ServiceError>;
+
+    /// Whether the first candidate only depends on the destination, so a link already
+    /// stored under it may be the one asked for.
+    fn is_content_addressed(&self) -> bool {
+        false
+    }
+
+    /// Namespace of the codes if they are drawn at random. Other strategies only
+    /// collide with custom aliases or links of the same destination, which longer
+    /// codes don't help with.
+    fn namespace(&self) -> Option<CodeNamespace> {
+        None
+    }
+}
+
+/// Parses an alphabet, rejecting empty alphabets and repeated characters
+pub fn parse_alphabet(alphabet: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = alphabet.chars().collect();
+    if chars.len() < 2 {
+        return Err("a short code alphabet needs at least two characters".to_string());
+    }
+    for (i, c) in chars.iter().enumerate() {
+        if chars[..i].contains(c) {
+            return Err(format!("short code alphabet repeats the character {:?}", c));
+        }
+    }
+    Ok(chars)
+}
+
+/// The alphabet with its letters folded to lower case, keeping the first occurrence
+/// of characters that fold together, for codes compared case-insensitively
+pub fn fold_alphabet(alphabet: &[char]) -> Vec<char> {
+    let mut folded: Vec<char> = Vec::with_capacity(alphabet.len());
+    for c in alphabet.iter().flat_map(|c| c.to_lowercase()) {
+        if !folded.contains(&c) {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+/// Random codes of a fixed length over an alphabet.
+pub struct RandomGenerator {
+    alphabet: Vec<char>,
+    length: usize,
+}
+
+impl RandomGenerator {
+    /// Creates a new RandomGenerator drawing `length` characters from `alphabet`.
+    pub fn new(alphabet: Vec<char>, length: usize) -> Self {
+        Self { alphabet, length }
+    }
+}
+
+impl Default for RandomGenerator {
+    /// Six random base62 characters
+    fn default() -> Self {
+        Self::new(BASE62_ALPHABET.chars().collect(), 6)
+    }
+}
+
+#[async_trait]
+impl ShortCodeGenerator for RandomGenerator {
+    async fn generate(&self, request: &CodeRequest<'_>) -> Result<String, ServiceError> {
+        let mut rng = thread_rng();
+        Ok((0..self.length + request.extra_length)
+            .map(|_| self.alphabet[rng.gen_range(0..self.alphabet.len())])
+            .collect())
+    }
+
+    /// Codes grow until the alphabet holds 2^64 of them, past which longer codes
+    /// hardly make collisions rarer
+    fn namespace(&self) -> Option<CodeNamespace> {
+        let max_length = (64.0 / (self.alphabet.len() as f64).log2()).ceil() as usize;
+        Some(CodeNamespace {
+            name: format!("random:{}:{}", self.length, self.alphabet.iter().collect::<String>()),
+            max_extra_length: max_length.saturating_sub(self.length),
+        })
+    }
+}
+
+/// Source of monotonically increasing numbers for counter based codes.
+pub enum Sequence {
+    /// The `short_code_seq` Postgres sequence, shared by all instances.
+    Postgres(Arc<PgPool>),
+    /// An in-process counter; only suitable for a single instance.
+    Memory(AtomicI64),
+}
+
+impl Sequence {
+    /// Creates an in-process sequence whose first value is `start`.
+    pub fn memory(start: i64) -> Self {
+        Sequence::Memory(AtomicI64::new(start))
+    }
+
+    /// Returns the next value of the sequence.
+    pub async fn next(&self) -> Result<i64, ServiceError> {
+        match self {
+            Sequence::Postgres(pool) => {
+                let value: i64 = sqlx::query_scalar("SELECT nextval('short_code_seq')")
+                    .fetch_one(pool.as_ref())
+                    .await?;
+                Ok(value)
+            }
+            Sequence::Memory(counter) => Ok(counter.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Sequential codes: the next sequence value written in base62 (or another alphabet).
+pub struct SequentialGenerator {
+    sequence: Sequence,
+    alphabet: Vec<char>,
+}
+
+impl SequentialGenerator {
+    /// Creates a new SequentialGenerator writing values of `sequence` over `alphabet`.
+    pub fn new(sequence: Sequence, alphabet: Vec<char>) -> Self {
+        Self { sequence, alphabet }
+    }
+}
+
+#[async_trait]
+impl ShortCodeGenerator for SequentialGenerator {
+    /// Sequence values never repeat, so codes only grow with the sequence.
+    async fn generate(&self, _request: &CodeRequest<'_>) -> Result<String, ServiceError> {
+        let value = self.sequence.next().await?;
+        Ok(encode_number(value as u64, &self.alphabet).into_iter().collect())
+    }
+}
+
+/// Hashids-style codes: sequence values encoded over an alphabet shuffled with a salt
+/// and a per-value lottery character, so consecutive values look unrelated.
+pub struct HashidsGenerator {
+    sequence: Sequence,
+    alphabet: Vec<char>,
+    salt: Vec<char>,
+    min_length: usize,
+}
+
+impl HashidsGenerator {
+    /// Creates a new HashidsGenerator.
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence` - The source of the numbers to encode.
+    /// * `alphabet` - The characters codes are made of.
+    /// * `salt` - A per-deployment secret making codes hard to map back to numbers.
+    /// * `min_length` - Codes shorter than this are padded.
+    pub fn new(sequence: Sequence, alphabet: Vec<char>, salt: &str, min_length: usize) -> Self {
+        let salt: Vec<char> = salt.chars().collect();
+        let mut alphabet = alphabet;
+        consistent_shuffle(&mut alphabet, &salt);
+        Self {
+            sequence,
+            alphabet,
+            salt,
+            min_length,
+        }
+    }
+
+    /// Encodes a single number.
+    pub fn encode(&self, value: u64) -> String {
+        self.encode_with_min_length(value, self.min_length)
+    }
+
+    fn encode_with_min_length(&self, value: u64, min_length: usize) -> String {
+        let mut alphabet = self.alphabet.clone();
+        let lottery = alphabet[(value % 100) as usize % alphabet.len()];
+
+        let buffer: Vec<char> = std::iter::once(lottery)
+            .chain(self.salt.iter().copied())
+            .chain(alphabet.iter().copied())
+            .take(alphabet.len())
+            .collect();
+        consistent_shuffle(&mut alphabet, &buffer);
+
+        let mut code: Vec<char> = std::iter::once(lottery)
+            .chain(encode_number(value, &alphabet))
+            .collect();
+
+        let half = alphabet.len() / 2;
+        while code.len() < min_length {
+            let salt = alphabet.clone();
+            consistent_shuffle(&mut alphabet, &salt);
+            code = alphabet[half..]
+                .iter()
+                .chain(code.iter())
+                .chain(alphabet[..half].iter())
+                .copied()
+                .collect();
+
+            let excess = code.len().saturating_sub(min_length);
+            if excess > 0 {
+                let start = excess / 2;
+                code = code[start..start + min_length].to_vec();
+            }
+        }
+
+        code.into_iter().collect()
+    }
+}
+
+#[async_trait]
+impl ShortCodeGenerator for HashidsGenerator {
+    async fn generate(&self, request: &CodeRequest<'_>) -> Result<String, ServiceError> {
+        let value = self.sequence.next().await?;
+        Ok(self.encode_with_min_length(value as u64, self.min_length + request.extra_length))
+    }
+}
+
+/// Content-addressed codes: a SHA-256 digest of the destination, so the same URL
+/// maps to the same code and shortening it again finds its link. Retries, for other
+/// destinations with the same digest, mix the attempt number into the digest.
+pub struct ContentHashGenerator {
+    alphabet: Vec<char>,
+    length: usize,
+}
+
+impl ContentHashGenerator {
+    /// Creates a new ContentHashGenerator writing `length` characters over `alphabet`.
+    pub fn new(alphabet: Vec<char>, length: usize) -> Self {
+        Self { alphabet, length }
+    }
+}
+
+#[async_trait]
+impl ShortCodeGenerator for ContentHashGenerator {
+    async fn generate(&self, request: &CodeRequest<'_>) -> Result<String, ServiceError> {
+        let length = self.length + request.extra_length;
+        let mut hasher = Sha256::new();
+        hasher.update(request.original_url.as_bytes());
+        if request.attempt > 0 {
+            hasher.update(request.attempt.to_be_bytes());
+        }
+        let digest = hasher.finalize();
+
+        // Long codes re-hash the digest to get more bytes
+        let mut bytes = digest.to_vec();
+        while bytes.len() < length {
+            let next = Sha256::digest(&bytes);
+            bytes.extend_from_slice(&next);
+        }
+
+        Ok(bytes[..length]
+            .iter()
+            .map(|b| self.alphabet[*b as usize % self.alphabet.len()])
+            .collect())
+    }
+
+    fn is_content_addressed(&self) -> bool {
+        true
+    }
+}
+
+/// Writes a number in positional notation over the alphabet
+fn encode_number(mut value: u64, alphabet: &[char]) -> Vec<char> {
+    let base = alphabet.len() as u64;
+    let mut digits = Vec::new();
+    loop {
+        digits.push(alphabet[(value % base) as usize]);
+        value /= base;
+        if value == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    digits
+}
+
+/// Deterministically shuffles the alphabet using the salt, as done by Hashids
+fn consistent_shuffle(alphabet: &mut [char], salt: &[char]) {
+    if salt.is_empty() || alphabet.len() < 2 {
+        return;
+    }
+
+    let mut v = 0;
+    let mut p = 0;
+    for i in (1..alphabet.len()).rev() {
+        v %= salt.len();
+        let a = salt[v] as usize;
+        p += a;
+        let j = (a + v + p) % i;
+        alphabet.swap(i, j);
+        v += 1;
+    }
+}
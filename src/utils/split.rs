@@ -0,0 +1,38 @@
+// src/utils/split.rs
+
+use crate::models::link::Link;
+use crate::models::variant::{pick_weighted, Variant};
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::HttpRequest;
+
+/// Cookie remembering the variant a visitor was assigned, scoped to the link's path
+pub const VARIANT_COOKIE: &str = "ktvr_variant";
+
+/// Days a visitor keeps their variant
+const VARIANT_COOKIE_DAYS: i64 = 30;
+
+/// Assigns the visitor to a variant of the link's split.
+///
+/// Visitors keep the variant named in their cookie while the split still has it with
+/// a positive weight; everyone else gets a weighted random pick. Returns the variant
+/// and whether it was newly picked and needs to be remembered with `cookie`.
+pub fn assign<'a>(req: &HttpRequest, link: &'a Link) -> Option<(&'a Variant, bool)> {
+    if let Some(variant) = req
+        .cookie(VARIANT_COOKIE)
+        .and_then(|cookie| link.variant(cookie.value()))
+    {
+        return Some((variant, false));
+    }
+    pick_weighted(&link.variants, &mut rand::thread_rng()).map(|variant| (variant, true))
+}
+
+/// Cookie keeping the visitor on the variant of the link
+pub fn cookie(link: &Link, variant: &Variant) -> Cookie<'static> {
+    Cookie::build(VARIANT_COOKIE, variant.name.clone())
+        .path(format!("/{}", link.short_code))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::days(VARIANT_COOKIE_DAYS))
+        .finish()
+}
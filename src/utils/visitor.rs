@@ -0,0 +1,123 @@
+// src/utils/visitor.rs
+
+use crate::models::rule::{Device, Os};
+use crate::services::geoip::GeoIp;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+
+/// What routing rules know about the visitor of a short link
+#[derive(Debug, Clone)]
+pub struct Visitor {
+    pub country: Option<String>,
+    pub os: Option<Os>,
+    pub device: Device,
+    /// Most preferred language from `Accept-Language`
+    pub language: Option<String>,
+    pub time: DateTime<Utc>,
+}
+
+impl Visitor {
+    /// Describes the visitor sending the request.
+    ///
+    /// The client address honors `Forwarded` and `X-Forwarded-For`, so the service is
+    /// expected to run behind a proxy that sets them.
+    pub fn from_request(req: &HttpRequest, geoip: &GeoIp) -> Self {
+        let country = client_ip(req).and_then(|ip| geoip.country(ip));
+        let user_agent = header_value(req, header::USER_AGENT).unwrap_or_default();
+        let (os, device) = classify_user_agent(user_agent);
+        let language = header_value(req, header::ACCEPT_LANGUAGE).and_then(preferred_language);
+
+        Self {
+            country,
+            os,
+            device,
+            language,
+            time: Utc::now(),
+        }
+    }
+}
+
+fn header_value(req: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    req.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Address of the client, taken from proxy headers when present
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let connection_info = req.connection_info();
+    let address = connection_info.realip_remote_addr()?;
+    address
+        .parse()
+        .ok()
+        .or_else(|| address.parse::<std::net::SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Whether the visitor asks not to be tracked, through `DNT` or Global Privacy Control
+pub fn do_not_track(req: &HttpRequest) -> bool {
+    [header::DNT, header::HeaderName::from_static("sec-gpc")]
+        .into_iter()
+        .any(|name| header_value(req, name).map(str::trim) == Some("1"))
+}
+
+/// Derives the operating system and kind of device from a User-Agent
+pub fn classify_user_agent(user_agent: &str) -> (Option<Os>, Device) {
+    let Some(result) = woothee::parser::Parser::new().parse(user_agent) else {
+        return (None, Device::Other);
+    };
+
+    let os = match result.os {
+        "iPhone" | "iPad" | "iPod" => Some(Os::Ios),
+        "Android" => Some(Os::Android),
+        "Mac OSX" => Some(Os::Macos),
+        "Linux" => Some(Os::Linux),
+        "ChromeOS" => Some(Os::Chromeos),
+        name if name.starts_with("Windows") && !name.starts_with("Windows Phone") => {
+            Some(Os::Windows)
+        }
+        _ => None,
+    };
+
+    // Android tablets are told apart by the missing "Mobile" token
+    let tablet = result.os == "iPad" || (result.os == "Android" && !user_agent.contains("Mobile"));
+    let device = match result.category {
+        _ if tablet => Device::Tablet,
+        "smartphone" | "mobilephone" => Device::Mobile,
+        "pc" => Device::Desktop,
+        "crawler" => Device::Bot,
+        _ => Device::Other,
+    };
+
+    (os, device)
+}
+
+/// Picks the language with the highest quality from an `Accept-Language` value,
+/// preferring the earliest on ties
+pub fn preferred_language(accept_language: &str) -> Option<String> {
+    let mut best: Option<(&str, f32)> = None;
+    for entry in accept_language.split(',') {
+        let mut parts = entry.split(';');
+        let tag = parts.next().unwrap_or_default().trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if tag.is_empty() || tag == "*" || quality <= 0.0 {
+            continue;
+        }
+        if !best.is_some_and(|(_, best_quality)| quality <= best_quality) {
+            best = Some((tag, quality));
+        }
+    }
+    best.map(|(tag, _)| tag.to_string())
+}
+
+/// Browser family of a User-Agent, such as `Chrome` or `Safari`
+pub fn browser_name(user_agent: &str) -> Option<String> {
+    woothee::parser::Parser::new()
+        .parse(user_agent)
+        .map(|result| result.name)
+        .filter(|name| !name.is_empty() && *name != woothee::woothee::VALUE_UNKNOWN)
+        .map(str::to_string)
+}
@@ -0,0 +1,231 @@
+// tests/click_recorder_tests.rs
+
+use actix_web::test::TestRequest;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use url_shortener::errors::ServiceError;
+use url_shortener::models::click::{
+    ClickBucket, ClickCount, ClickDimension, ClickEvent, ClickQuery, Interval, TopValue,
+};
+use url_shortener::models::export::{ClickPosition, ClickRollup, ExportScope};
+use url_shortener::models::link::{Link, NewLink, Passthrough};
+use url_shortener::services::{ClickPrivacy, ClickRecorder, ClickStream, GeoIp, IpMode};
+use url_shortener::store::{
+    ClickStore, LinkStore, MemoryClickStore, MemoryLinkStore, MemoryVisitorStore,
+};
+
+/// In-memory click store that holds every batch until released
+struct GatedClickStore {
+    gate: Semaphore,
+    clicks: MemoryClickStore,
+}
+
+impl GatedClickStore {
+    fn closed() -> Arc<Self> {
+        Arc::new(Self {
+            gate: Semaphore::new(0),
+            clicks: MemoryClickStore::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ClickStore for GatedClickStore {
+    async fn insert_clicks(&self, clicks: &[ClickEvent]) -> Result<(), ServiceError> {
+        self.gate.acquire().await.unwrap().forget();
+        self.clicks.insert_clicks(clicks).await
+    }
+
+    async fn count_clicks(&self, query: &ClickQuery) -> Result<ClickCount, ServiceError> {
+        self.clicks.count_clicks(query).await
+    }
+
+    async fn click_series(
+        &self,
+        query: &ClickQuery,
+        interval: Interval,
+    ) -> Result<Vec<ClickBucket>, ServiceError> {
+        self.clicks.click_series(query, interval).await
+    }
+
+    async fn top_values(
+        &self,
+        query: &ClickQuery,
+        dimension: ClickDimension,
+        limit: i64,
+    ) -> Result<Vec<TopValue>, ServiceError> {
+        self.clicks.top_values(query, dimension, limit).await
+    }
+
+    async fn delete_link_clicks(&self, link_id: uuid::Uuid) -> Result<u64, ServiceError> {
+        self.clicks.delete_link_clicks(link_id).await
+    }
+
+    async fn export_clicks(
+        &self,
+        scope: &ExportScope,
+        after: Option<ClickPosition>,
+        limit: i64,
+    ) -> Result<Vec<(ClickPosition, ClickEvent)>, ServiceError> {
+        self.clicks.export_clicks(scope, after, limit).await
+    }
+
+    async fn export_rollups(
+        &self,
+        scope: &ExportScope,
+        granularity: Interval,
+        after: Option<&ClickRollup>,
+        limit: i64,
+    ) -> Result<Vec<ClickRollup>, ServiceError> {
+        self.clicks.export_rollups(scope, granularity, after, limit).await
+    }
+
+    async fn delete_visitor_clicks(&self, ip_hash: &str, links: &[uuid::Uuid]) -> Result<u64, ServiceError> {
+        self.clicks.delete_visitor_clicks(ip_hash, links).await
+    }
+
+    async fn daily_salt(&self, day: NaiveDate) -> Result<Vec<u8>, ServiceError> {
+        self.clicks.daily_salt(day).await
+    }
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_clicks_instead_of_waiting() {
+    let store = GatedClickStore::closed();
+    let (clicks, worker) = ClickRecorder::start(
+        store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickStream::new(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Hash, b"secret", false),
+        4,
+        2,
+        Duration::from_millis(1),
+    )
+    .unwrap();
+    let link = link().await;
+    let req = TestRequest::default().to_http_request();
+
+    // The worker takes the first batch and stalls; four clicks fit in the queue
+    clicks.record(&req, &link, None, None);
+    clicks.record(&req, &link, None, None);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for _ in 0..10 {
+        clicks.record(&req, &link, None, None);
+    }
+
+    let stats = clicks.stats();
+    assert_eq!(stats.queued, 6);
+    assert_eq!(stats.dropped, 6);
+    assert_eq!(stats.pending, 4);
+    assert_eq!(stats.written, 0);
+
+    // Once the store catches up, everything queued is written
+    store.gate.add_permits(10);
+    while clicks.stats().written < 6 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(clicks.stats().pending, 0);
+    assert_eq!(store.clicks.clicks().len(), 6);
+
+    drop(clicks);
+    worker.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_configuration_is_rejected() {
+    let store = GatedClickStore::closed();
+    let start = |capacity, batch_size| {
+        ClickRecorder::start(
+            store.clone(),
+            Arc::new(MemoryVisitorStore::new()),
+            ClickStream::new(),
+            GeoIp::disabled(),
+            ClickPrivacy::new(IpMode::Hash, b"secret", false),
+            capacity,
+            batch_size,
+            Duration::from_secs(1),
+        )
+        .map(|_| ())
+    };
+    assert!(start(0, 10).is_err());
+    assert!(start(10, 0).is_err());
+    assert!(start(10, 100_000).is_err());
+}
+
+#[tokio::test]
+async fn test_ip_modes() {
+    let ip = |address: &str| address.parse::<IpAddr>().unwrap();
+    let store = MemoryClickStore::new();
+    let today = NaiveDate::from_ymd_opt(2024, 9, 15).unwrap();
+    let today_salt = store.daily_salt(today).await.unwrap();
+    let salt = Some(today_salt.as_slice());
+
+    let privacy = |mode| ClickPrivacy::new(mode, b"secret", false);
+    assert_eq!(privacy(IpMode::Drop).visitor_id(ip("203.0.113.7"), salt), None);
+
+    // Networks are kept, IPv4 addresses mapped to IPv6 included
+    let truncate = privacy(IpMode::Truncate);
+    for (address, network) in [
+        ("203.0.113.7", "203.0.113.0"),
+        ("::ffff:203.0.113.7", "203.0.113.0"),
+        ("2001:db8:1:2:3:4:5:6", "2001:db8:1::"),
+    ] {
+        assert_eq!(truncate.visitor_id(ip(address), salt).as_deref(), Some(network));
+    }
+
+    let hash = privacy(IpMode::Hash);
+    let visitor = hash.visitor_id(ip("203.0.113.7"), salt).unwrap();
+    assert_eq!(visitor.len(), 32);
+    assert_eq!(hash.visitor_id(ip("203.0.113.7"), None).unwrap(), visitor);
+    assert_ne!(hash.visitor_id(ip("203.0.113.8"), salt).unwrap(), visitor);
+
+    // Daily hashes only match under the salt of the day, which is random and
+    // discarded once the next day's is read
+    let daily = privacy(IpMode::DailyHash);
+    let visitor = daily.visitor_id(ip("203.0.113.7"), salt).unwrap();
+    assert_eq!(store.daily_salt(today).await.unwrap(), today_salt);
+    let next_day = store.daily_salt(today.succ_opt().unwrap()).await.unwrap();
+    assert_ne!(daily.visitor_id(ip("203.0.113.7"), Some(&next_day)).unwrap(), visitor);
+    assert_ne!(store.daily_salt(today).await.unwrap(), today_salt);
+    assert_ne!(privacy(IpMode::Hash).visitor_id(ip("203.0.113.7"), salt).unwrap(), visitor);
+    assert_eq!(daily.visitor_id(ip("203.0.113.7"), None), None);
+
+    for (mode, expected) in [
+        ("drop", Ok(IpMode::Drop)),
+        ("truncate", Ok(IpMode::Truncate)),
+        ("hash", Ok(IpMode::Hash)),
+        ("daily_hash", Ok(IpMode::DailyHash)),
+    ] {
+        assert_eq!(mode.parse::<IpMode>(), expected);
+    }
+    assert!("anonymize".parse::<IpMode>().is_err());
+}
+
+async fn link() -> Link {
+    MemoryLinkStore::new()
+        .create(NewLink {
+            original_url: "https://www.rust-lang.org".to_string(),
+            short_code: "counted".to_string(),
+            starts_at: None,
+            expires_at: None,
+            fallback_url: None,
+            password_hash: None,
+            click_limit: None,
+            redirect_status: None,
+            passthrough: Passthrough::None,
+            rules: Vec::new(),
+            variants: Vec::new(),
+            card: None,
+            is_active: true,
+            owner: None,
+            tags: Vec::new(),
+        })
+        .await
+        .unwrap()
+}
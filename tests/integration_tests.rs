@@ -1,46 +1,38 @@
 // tests/integration_tests.rs
 
 use actix_web::{test, App};
+use apistos::app::OpenApiWrapper;
+use apistos::spec::Spec;
+use chrono::{Duration, Utc};
 use serde_json::json;
 
 use url_shortener::handlers::{health_check, shorten, redirect};
-use url_shortener::services::UrlService;
-use url_shortener::config::Config;
+use url_shortener::models::click::ClickEvent;
+use url_shortener::models::link::{LinkChanges, NewLink, Passthrough};
+use url_shortener::routes;
+use url_shortener::services::{
+    Accounts, AliasPolicy, ClickPrivacy, ClickRecorder, ClickStream, CodeAllocator, GeoIp, IpMode,
+    RedirectPolicy, UnlockTokens,
+};
+use url_shortener::store::{
+    CaseFoldingLinkStore, ClickStore, DynClickStore, DynLinkStore, DynVisitorStore, MemoryClickStore, MemoryLinkStore,
+    MemoryVisitorStore, VisitorStore,
+};
+use url_shortener::utils::short_code::{RandomGenerator, BASE62_ALPHABET};
 use actix_web::web;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use redis::aio::MultiplexedConnection;
-use redis::AsyncCommands;
 
-// Ensure your main application modules are accessible
-// Update `Cargo.toml` with `url_shortener` as a path dependency if necessary
+const CHROME_ON_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+// The handlers only depend on the `LinkStore` trait, so these tests run against
+// the in-memory store and need neither Redis nor PostgreSQL.
 
 #[actix_rt::test]
 async fn test_health_check() {
-    // Initialize environment variables
-    dotenv::dotenv().ok();
-
-    // Load configuration
-    let config = Config::from_env();
-
-    // Establish Redis connection
-    let redis_conn = match establish_redis_connection(&config.redis_url).await {
-        Ok(conn) => Arc::new(Mutex::new(conn)),
-        Err(_) => panic!("Failed to connect to Redis for testing"),
-    };
-
-    // Initialize UrlService
-    let url_service = UrlService::new(
-        redis_conn.clone(),
-        "test_short_url:".to_string(),
-        60, // 1 minute for testing
-    );
-
     // Initialize Actix app with health check handler
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(url_service))
-            .route("/health", web::get().to(health::health_check))
+            .route("/health", web::get().to(health_check::health_check))
     ).await;
 
     // Create a request to the /health endpoint
@@ -58,35 +50,20 @@ async fn test_health_check() {
     let body = test::read_body(resp).await;
 
     // Assert that the body is "OK"
-    assert_eq!(body, "OK");
+    assert_eq!(body, "OK\n");
 }
 
 #[actix_rt::test]
 async fn test_shorten_url() {
-    // Initialize environment variables
-    dotenv::dotenv().ok();
-
-    // Load configuration
-    let config = Config::from_env();
-
-    // Establish Redis connection
-    let redis_conn = match establish_redis_connection(&config.redis_url).await {
-        Ok(conn) => Arc::new(Mutex::new(conn)),
-        Err(_) => panic!("Failed to connect to Redis for testing"),
-    };
-
-    // Initialize UrlService
-    let url_service = UrlService::new(
-        redis_conn.clone(),
-        "test_short_url:".to_string(),
-        60, // 1 minute for testing
-    );
+    let store = memory_store();
+    let host_url = "http://localhost:8080".to_string();
 
     // Initialize Actix app with shorten_url handler
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(url_service))
-            .app_data(web::Data::new(config.host_url.clone()))
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(code_allocator()))
+            .app_data(web::Data::new(host_url.clone()))
             .route("/shorten", web::post().to(shorten::shorten_url))
     ).await;
 
@@ -96,63 +73,46 @@ async fn test_shorten_url() {
     // Create a POST request to the /shorten endpoint with JSON body
     let req = test::TestRequest::post()
         .uri("/shorten")
-        .set_json(&json!({ "url": original_url }))
+        .set_json(json!({ "url": original_url }))
         .to_request();
 
     // Send the request and get the response
-    let resp: serde_json::Value = test::read_response_json(&app, req).await;
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
 
-    // Assert that the response contains the "short_url" field
-    assert!(resp.get("short_url").is_some());
+    // Assert that the response contains the "shortUrl" field
+    assert!(resp.get("shortUrl").is_some());
 
     // Extract the short URL
-    let short_url = resp.get("short_url").unwrap().as_str().unwrap();
+    let short_url = resp.get("shortUrl").unwrap().as_str().unwrap();
 
-    // Optionally, verify the format of the short URL
-    assert!(short_url.starts_with(&config.host_url));
+    // Verify the format of the short URL
+    assert!(short_url.starts_with(&host_url));
 
-    // Optionally, extract the short ID
-    let short_id = short_url.trim_start_matches(&format!("{}/", config.host_url)).to_string();
+    // Extract the short ID
+    let short_id = short_url.trim_start_matches(&format!("{}/", host_url)).to_string();
 
-    // Verify that the short ID exists in Redis
-    let mut conn = redis_conn.lock().await;
-    let original_url_in_redis: Option<String> = conn.get(format!("test_short_url:{}", short_id)).await.unwrap();
-    assert_eq!(original_url_in_redis, Some(original_url.to_string()));
+    // Verify that the short ID exists in the store
+    let link = store.get_by_code(&short_id).await.unwrap().unwrap();
+    assert_eq!(link.original_url, original_url);
 }
 
 #[actix_rt::test]
 async fn test_redirect() {
-    // Initialize environment variables
-    dotenv::dotenv().ok();
-
-    // Load configuration
-    let config = Config::from_env();
-
-    // Establish Redis connection
-    let redis_conn = match establish_redis_connection(&config.redis_url).await {
-        Ok(conn) => Arc::new(Mutex::new(conn)),
-        Err(_) => panic!("Failed to connect to Redis for testing"),
-    };
-
-    // Initialize UrlService
-    let url_service = UrlService::new(
-        redis_conn.clone(),
-        "test_short_url:".to_string(),
-        60, // 1 minute for testing
-    );
+    let store = memory_store();
 
-    // Manually insert a short URL mapping into Redis for testing
+    // Manually insert a short URL mapping into the store for testing
     let short_id = "test123";
     let original_url = "https://www.rust-lang.org";
-    {
-        let mut conn = redis_conn.lock().await;
-        conn.set_ex(format!("test_short_url:{}", short_id), original_url, 60).await.unwrap();
-    }
+    store.create(new_link(short_id, original_url)).await.unwrap();
 
     // Initialize Actix app with redirect handler
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(url_service))
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(unlock_tokens()))
+            .app_data(web::Data::new(redirect_policy()))
+            .app_data(web::Data::new(GeoIp::disabled()))
+            .app_data(web::Data::new(ClickRecorder::disabled()))
             .route("/{short_id}", web::get().to(redirect::redirect))
     ).await;
 
@@ -172,8 +132,1752 @@ async fn test_redirect() {
     assert_eq!(headers.get("Location").unwrap(), original_url);
 }
 
-/// Helper function to establish a Redis connection for tests
-async fn establish_redis_connection(redis_url: &str) -> redis::RedisResult<MultiplexedConnection> {
-    let client = redis::Client::open(redis_url)?;
-    client.get_multiplexed_tokio_connection().await
+#[actix_rt::test]
+async fn test_redirect_unknown_code() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(memory_store()))
+            .app_data(web::Data::new(unlock_tokens()))
+            .app_data(web::Data::new(redirect_policy()))
+            .app_data(web::Data::new(GeoIp::disabled()))
+            .app_data(web::Data::new(ClickRecorder::disabled()))
+            .route("/{short_id}", web::get().to(redirect::redirect))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/missing").to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_create_link_then_redirect() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    // Create a link through the documented endpoint
+    let original_url = "https://www.rust-lang.org/learn";
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": original_url, "custom_alias": "learn", "tags": ["docs"] }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["short_url"], "https://ktvr.cc/learn");
+    assert_eq!(store.get_by_code("learn").await.unwrap().unwrap().tags, ["docs"]);
+
+    // Tags can't be blank
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": original_url, "tags": [" "] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // The same alias cannot be claimed twice
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": original_url, "custom_alias": "learn" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // The redirect resolves the stored link
+    let req = test::TestRequest::get().uri("/learn").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Location").unwrap(), original_url);
+}
+
+#[actix_rt::test]
+async fn test_redirect_inactive_link() {
+    let store = memory_store();
+    store.create(new_link("inactive", "https://www.rust-lang.org")).await.unwrap();
+    let changes = LinkChanges {
+        is_active: Some(false),
+        ..Default::default()
+    };
+    store.update("inactive", &changes).await.unwrap();
+
+    let app = test::init_service(api_app(store)).await;
+
+    let req = test::TestRequest::get().uri("/inactive").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_redirect_expired_link() {
+    let store = memory_store();
+    let mut link = new_link("expired", "https://www.rust-lang.org");
+    link.expires_at = Some(Utc::now() - Duration::minutes(1));
+    store.create(link).await.unwrap();
+
+    let app = test::init_service(api_app(store)).await;
+
+    // API clients get a JSON body
+    let req = test::TestRequest::get().uri("/expired").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 410);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "This short link has expired");
+
+    // Browsers get an HTML page
+    let req = test::TestRequest::get()
+        .uri("/expired")
+        .insert_header(("Accept", "text/html,application/xhtml+xml"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 410);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html; charset=utf-8");
+}
+
+#[actix_rt::test]
+async fn test_redirect_click_limit() {
+    let store = memory_store();
+    let mut link = new_link("limited", "https://www.rust-lang.org");
+    link.click_limit = Some(2);
+    store.create(link).await.unwrap();
+
+    let app = test::init_service(api_app(store.clone())).await;
+
+    for expected in [301, 301, 410, 410] {
+        let req = test::TestRequest::get()
+            .uri("/limited")
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+    }
+
+    // Rejected redirects do not consume clicks
+    let link = store.get_by_code("limited").await.unwrap().unwrap();
+    assert_eq!(link.click_count, 2);
+    assert!(link.is_exhausted());
+}
+
+#[actix_rt::test]
+async fn test_password_protected_link() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store)).await;
+
+    let original_url = "https://www.rust-lang.org/secret";
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": original_url, "custom_alias": "secret", "password": "hunter2" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    // Without credentials, API clients get a JSON error and browsers the unlock form
+    let req = test::TestRequest::get().uri("/secret").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::get()
+        .uri("/secret")
+        .insert_header(("Accept", "text/html"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    let body = test::read_body(resp).await;
+    assert!(std::str::from_utf8(&body).unwrap().contains("action=\"/secret/unlock\""));
+
+    // API clients can pass the password in a header
+    let req = test::TestRequest::get()
+        .uri("/secret")
+        .insert_header(("X-Link-Password", "wrong"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::get()
+        .uri("/secret")
+        .insert_header(("X-Link-Password", "hunter2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Location").unwrap(), original_url);
+
+    // Browsers submit the form and get a cookie scoped to the link
+    let req = test::TestRequest::post()
+        .uri("/secret/unlock")
+        .set_form([("password", "wrong")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/secret/unlock")
+        .set_form([("password", "hunter2")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 303);
+    assert_eq!(resp.headers().get("Location").unwrap(), "/secret");
+    let cookie = resp.response().cookies().next().unwrap().into_owned();
+    assert_eq!(cookie.path(), Some("/secret"));
+
+    let req = test::TestRequest::get()
+        .uri("/secret")
+        .cookie(cookie)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+
+    // A forged cookie does not unlock the link
+    let req = test::TestRequest::get()
+        .uri("/secret")
+        .cookie(actix_web::cookie::Cookie::new("ktvr_unlock", "9999999999.00"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_rt::test]
+async fn test_link_management_api() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store)).await;
+
+    for alias in ["first", "second", "third"] {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .set_json(json!({ "original_url": "https://www.rust-lang.org", "custom_alias": alias }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+
+    // Get a single link
+    let req = test::TestRequest::get().uri("/api/links/second").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["short_code"], "second");
+    assert_eq!(resp["short_url"], "https://ktvr.cc/second");
+    assert_eq!(resp["password_protected"], false);
+    assert_eq!(resp["expired"], false);
+
+    let req = test::TestRequest::get().uri("/api/links/missing").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // List with paging
+    let req = test::TestRequest::get().uri("/api/links?page=1&per_page=2").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["items"].as_array().unwrap().len(), 2);
+    assert_eq!(resp["has_more"], true);
+
+    let req = test::TestRequest::get().uri("/api/links?page=2&per_page=2").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["items"].as_array().unwrap().len(), 1);
+    assert_eq!(resp["has_more"], false);
+
+    // Update the destination and expiry; the redirect follows immediately
+    let req = test::TestRequest::patch()
+        .uri("/api/links/second")
+        .set_json(json!({ "original_url": "https://doc.rust-lang.org", "expires_at": "2000-01-01T00:00:00Z" }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["original_url"], "https://doc.rust-lang.org");
+    assert_eq!(resp["expired"], true);
+
+    let req = test::TestRequest::get().uri("/second").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 410);
+
+    // null clears the expiry
+    let req = test::TestRequest::patch()
+        .uri("/api/links/second")
+        .set_json(json!({ "expires_at": null, "password": "hunter2" }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["expires_at"], serde_json::Value::Null);
+    assert_eq!(resp["password_protected"], true);
+
+    // The destination of a password-protected link stays hidden
+    let req = test::TestRequest::get().uri("/api/links/second").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["original_url"], serde_json::Value::Null);
+    assert_eq!(resp["rules"], serde_json::Value::Null);
+    let req = test::TestRequest::get().uri("/api/links").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let second = resp["items"].as_array().unwrap().iter().find(|item| item["short_code"] == "second");
+    assert_eq!(second.unwrap()["original_url"], serde_json::Value::Null);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/links/second")
+        .set_json(json!({ "original_url": "not a url" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Deactivate
+    let req = test::TestRequest::patch()
+        .uri("/api/links/first")
+        .set_json(json!({ "is_active": false }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["is_active"], false);
+
+    let req = test::TestRequest::get().uri("/first").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Delete
+    let req = test::TestRequest::delete().uri("/api/links/third").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::delete().uri("/api/links/third").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // The endpoints are documented
+    let req = test::TestRequest::get().uri("/openapi.json/").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(resp["paths"]["/api/links"].get("get").is_some());
+    for method in ["get", "patch", "delete"] {
+        assert!(resp["paths"]["/api/links/{short_code}"].get(method).is_some());
+    }
+}
+
+#[actix_rt::test]
+async fn test_custom_alias_validation() {
+    let app = test::init_service(api_app(memory_store())).await;
+
+    let cases = [
+        ("docs", "reserved"),
+        ("API", "reserved"),
+        ("health_check", "reserved"),
+        ("openapi.json", "charset"),
+        ("a/b", "charset"),
+        ("café", "charset"),
+        ("ab", "min_length"),
+        (&"x".repeat(65), "max_length"),
+    ];
+    for (alias, rule) in cases {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .set_json(json!({ "original_url": "https://www.rust-lang.org", "custom_alias": alias }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 422, "alias {:?}", alias);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["field"], "custom_alias");
+        assert_eq!(body["rule"], rule, "alias {:?}", alias);
+        assert!(body["message"].is_string());
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": "https://www.rust-lang.org", "custom_alias": "rust-lang_2024" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[actix_rt::test]
+async fn test_registered_routes_are_reserved() {
+    let app = test::init_service(api_app(memory_store())).await;
+
+    // Every documented route starting with a literal segment must be reserved
+    let req = test::TestRequest::get().uri("/openapi.json/").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let reserved = routes::reserved_words();
+    let paths = resp["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+    for path in paths.keys() {
+        let segment = path.trim_start_matches('/').split('/').next().unwrap();
+        if !segment.starts_with('{') {
+            assert!(reserved.iter().any(|word| word == segment), "{} is not reserved", path);
+        }
+    }
+    for word in ["api", "health_check", "openapi.json", "docs"] {
+        assert!(reserved.iter().any(|reserved| reserved == word), "{}", word);
+    }
+}
+
+#[actix_rt::test]
+async fn test_case_insensitive_aliases_are_folded() {
+    let policy = AliasPolicy::new(BASE62_ALPHABET, 3, 64, ["admin"], true).unwrap();
+    assert_eq!(policy.validate("Docs").unwrap(), "docs");
+    assert!(policy.validate("Admin").is_err());
+
+    let policy = AliasPolicy::new(BASE62_ALPHABET, 3, 64, ["admin"], false).unwrap();
+    assert_eq!(policy.validate("Docs").unwrap(), "Docs");
+
+    assert!(AliasPolicy::new("abc/", 3, 64, ["admin"], false).is_err());
+    assert!(AliasPolicy::new(BASE62_ALPHABET, 8, 4, ["admin"], false).is_err());
+
+    // Codes are folded on the way in and out of the store, aliases and generated
+    // codes alike
+    let store: DynLinkStore = Arc::new(CaseFoldingLinkStore::new(memory_store()));
+    let app = test::init_service(api_app(store.clone())).await;
+    let shorten = |body: serde_json::Value| {
+        test::TestRequest::post().uri("/api/shorten").set_json(body).to_request()
+    };
+    let req = shorten(json!({ "original_url": "https://www.rust-lang.org", "custom_alias": "Rust" }));
+    assert!(test::call_service(&app, req).await.status().is_success());
+    for path in ["/Rust", "/rust", "/RUST"] {
+        let req = test::TestRequest::get().uri(path).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 301, "{}", path);
+    }
+    let req = shorten(json!({ "original_url": "https://example.com", "custom_alias": "rUST" }));
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = shorten(json!({ "original_url": "https://example.com" }));
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let short_code = resp["short_url"].as_str().unwrap().rsplit('/').next().unwrap();
+    assert_eq!(short_code, short_code.to_lowercase());
+    let link = store.get_by_code(&short_code.to_uppercase()).await.unwrap().unwrap();
+    assert_eq!(link.original_url, "https://example.com");
+}
+
+#[actix_rt::test]
+async fn test_redirect_status_and_cache_headers() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    // The deployment default is cacheable for at most REDIRECT_CACHE_MAX_AGE
+    store.create(new_link("default", "https://www.rust-lang.org")).await.unwrap();
+    let req = test::TestRequest::get().uri("/default").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "public, max-age=3600");
+    assert!(resp.headers().get("Expires").is_some());
+
+    // Permanent redirects are not cached past the link's expiry
+    let mut link = new_link("expiring", "https://www.rust-lang.org");
+    link.redirect_status = Some(308);
+    link.expires_at = Some(Utc::now() + Duration::seconds(600));
+    store.create(link).await.unwrap();
+    let req = test::TestRequest::get().uri("/expiring").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 308);
+    let cache_control = resp.headers().get("Cache-Control").unwrap().to_str().unwrap();
+    let max_age: u64 = cache_control.trim_start_matches("public, max-age=").parse().unwrap();
+    assert!((590..=600).contains(&max_age));
+
+    // Temporary redirects are revalidated on every visit
+    for status in [302, 307] {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .set_json(json!({
+                "original_url": "https://www.rust-lang.org",
+                "custom_alias": format!("temp{}", status),
+                "redirect_status": status,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get().uri(&format!("/temp{}", status)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+        assert_eq!(resp.headers().get("Cache-Control").unwrap(), "private, no-cache");
+    }
+
+    // Links counting clicks are never stored
+    let mut link = new_link("limited", "https://www.rust-lang.org");
+    link.click_limit = Some(5);
+    store.create(link).await.unwrap();
+    let req = test::TestRequest::get()
+        .uri("/limited")
+        .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "no-store");
+
+    // The status can be changed and reset to the default
+    let req = test::TestRequest::patch()
+        .uri("/api/links/default")
+        .set_json(json!({ "redirect_status": 307 }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["redirect_status"], 307);
+
+    let req = test::TestRequest::get().uri("/default").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 307);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/links/default")
+        .set_json(json!({ "redirect_status": null }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["redirect_status"], serde_json::Value::Null);
+
+    // Other statuses are rejected
+    let req = test::TestRequest::patch()
+        .uri("/api/links/default")
+        .set_json(json!({ "redirect_status": 200 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({ "original_url": "https://www.rust-lang.org", "redirect_status": 303 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Ages too long for the header are capped rather than wrapped around
+    let policy = RedirectPolicy::new(301, u64::MAX).unwrap();
+    let link = store.get_by_code("default").await.unwrap().unwrap();
+    let resp = policy.redirect(&link, &link.original_url);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "public, max-age=4294967295");
+}
+
+#[actix_rt::test]
+async fn test_path_and_query_passthrough() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    let destination = "https://www.rust-lang.org/learn?utm_source=ktvr&lang=en#top";
+    for (alias, passthrough) in [("plain", "none"), ("query", "query"), ("full", "path_and_query")] {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .set_json(json!({
+                "original_url": destination,
+                "custom_alias": alias,
+                "passthrough": passthrough,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+
+    let location = |resp: actix_web::dev::ServiceResponse| {
+        resp.headers().get("Location").unwrap().to_str().unwrap().to_string()
+    };
+
+    // Without passthrough the request's query is dropped and extra paths don't resolve
+    let req = test::TestRequest::get().uri("/plain?utm_source=x").to_request();
+    assert_eq!(location(test::call_service(&app, req).await), destination);
+    let req = test::TestRequest::get().uri("/plain/extra").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    // Request parameters replace the destination's values for the same key
+    let req = test::TestRequest::get().uri("/query?utm_source=x&utm_source=y&ref=a%20b").to_request();
+    assert_eq!(
+        location(test::call_service(&app, req).await),
+        "https://www.rust-lang.org/learn?lang=en&utm_source=x&utm_source=y&ref=a+b#top"
+    );
+    let req = test::TestRequest::get().uri("/query/extra").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    // Extra path segments are appended to the destination's path
+    let req = test::TestRequest::get().uri("/full/get-started/?utm_source=x").to_request();
+    assert_eq!(
+        location(test::call_service(&app, req).await),
+        "https://www.rust-lang.org/learn/get-started?lang=en&utm_source=x#top"
+    );
+    let req = test::TestRequest::get().uri("/full").to_request();
+    assert_eq!(location(test::call_service(&app, req).await), destination);
+
+    // The unlock page is not forwarded
+    let req = test::TestRequest::get().uri("/full/unlock").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_routing_rules() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({
+            "original_url": "https://www.rust-lang.org",
+            "custom_alias": "app",
+            "passthrough": "query",
+            "rules": [
+                { "destination": "https://apps.apple.com/app/rust", "os": ["ios"] },
+                { "destination": "https://play.google.com/store/apps/rust", "os": ["android"] },
+                { "destination": "https://www.rust-lang.de", "languages": ["de"] },
+            ],
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let visit = |user_agent: &str, language: &str| {
+        test::TestRequest::get()
+            .uri("/app?ref=qr")
+            .insert_header(("User-Agent", user_agent.to_string()))
+            .insert_header(("Accept-Language", language.to_string()))
+            .to_request()
+    };
+    let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+    let android = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36";
+    let desktop = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+    // The first matching rule wins, and the passthrough applies to its destination
+    let cases = [
+        (iphone, "de-DE", "https://apps.apple.com/app/rust?ref=qr"),
+        (android, "en", "https://play.google.com/store/apps/rust?ref=qr"),
+        (desktop, "de-DE,en;q=0.5", "https://www.rust-lang.de/?ref=qr"),
+        (desktop, "en-US,de;q=0.5", "https://www.rust-lang.org/?ref=qr"),
+    ];
+    for (user_agent, language, expected) in cases {
+        let resp = test::call_service(&app, visit(user_agent, language)).await;
+        assert_eq!(resp.headers().get("Location").unwrap(), expected);
+        assert_eq!(resp.headers().get("Cache-Control").unwrap(), "private, no-cache");
+    }
+
+    // Rules with unknown time zones are rejected
+    let req = test::TestRequest::patch()
+        .uri("/api/links/app")
+        .set_json(json!({
+            "rules": [{
+                "destination": "https://www.rust-lang.org",
+                "time": { "timezone": "Mars/Olympus", "start": "09:00", "end": "17:00" },
+            }],
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // An empty list removes the rules
+    let req = test::TestRequest::patch()
+        .uri("/api/links/app")
+        .set_json(json!({ "rules": [] }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["rules"], json!([]));
+
+    let resp = test::call_service(&app, visit(iphone, "en")).await;
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://www.rust-lang.org/?ref=qr");
+}
+
+#[actix_rt::test]
+async fn test_weighted_split() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({
+            "original_url": "https://www.rust-lang.org",
+            "custom_alias": "landing",
+            "variants": [
+                { "name": "a", "destination": "https://www.rust-lang.org/a", "weight": 1 },
+                { "name": "b", "destination": "https://www.rust-lang.org/b", "weight": 1 },
+            ],
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    // New visitors are assigned a variant and keep it
+    let req = test::TestRequest::get()
+        .uri("/landing")
+        .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "private, no-cache");
+    let location = resp.headers().get("Location").unwrap().to_str().unwrap().to_string();
+    let cookie = resp.response().cookies().next().unwrap().into_owned();
+    assert_eq!(cookie.name(), "ktvr_variant");
+    assert_eq!(cookie.path(), Some("/landing"));
+    assert_eq!(location, format!("https://www.rust-lang.org/{}", cookie.value()));
+
+    for _ in 0..5 {
+        let req = test::TestRequest::get()
+            .uri("/landing")
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .cookie(cookie.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("Location").unwrap(), location.as_str());
+        assert!(resp.response().cookies().next().is_none());
+    }
+
+    // Clicks are counted per variant
+    let req = test::TestRequest::get().uri("/api/links/landing/experiment").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["total_clicks"], 6);
+    let variants = resp["variants"].as_array().unwrap();
+    let assigned = variants.iter().find(|v| v["name"] == cookie.value()).unwrap();
+    assert_eq!(assigned["clicks"], 6);
+    assert_eq!(assigned["share"], 1.0);
+
+    // Taking a variant out of the split reassigns its visitors
+    let other = if cookie.value() == "a" { "b" } else { "a" };
+    let req = test::TestRequest::patch()
+        .uri("/api/links/landing")
+        .set_json(json!({
+            "variants": [
+                { "name": cookie.value(), "destination": location, "weight": 0 },
+                { "name": other, "destination": format!("https://www.rust-lang.org/{}", other), "weight": 1 },
+            ],
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get().uri("/landing").cookie(cookie.clone()).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("Location").unwrap().to_str().unwrap(),
+        format!("https://www.rust-lang.org/{}", other)
+    );
+    assert_eq!(resp.response().cookies().next().unwrap().value(), other);
+
+    // Invalid splits are rejected
+    for variants in [
+        json!([{ "name": "a", "destination": "https://www.rust-lang.org", "weight": 0 }]),
+        json!([
+            { "name": "a", "destination": "https://www.rust-lang.org", "weight": 1 },
+            { "name": "a", "destination": "https://doc.rust-lang.org", "weight": 1 },
+        ]),
+        json!([{ "name": "a b", "destination": "https://www.rust-lang.org", "weight": 1 }]),
+    ] {
+        let req = test::TestRequest::patch()
+            .uri("/api/links/landing")
+            .set_json(json!({ "variants": variants }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
+
+#[actix_rt::test]
+async fn test_scheduled_links_and_fallback() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+    let fallback = "https://www.rust-lang.org/campaigns";
+
+    // Links created ahead of time only redirect once started
+    let starts_at = Utc::now() + Duration::hours(1);
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({
+            "original_url": "https://www.rust-lang.org/launch",
+            "custom_alias": "launch",
+            "starts_at": starts_at,
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get().uri("/api/links/launch").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["scheduled"], true);
+
+    let req = test::TestRequest::get().uri("/launch").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    // With a fallback, visitors are sent there instead
+    let req = test::TestRequest::patch()
+        .uri("/api/links/launch")
+        .set_json(json!({ "fallback_url": fallback }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get().uri("/launch").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 302);
+    assert_eq!(resp.headers().get("Location").unwrap(), fallback);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "private, no-cache");
+
+    // Once started, the link redirects as usual
+    let req = test::TestRequest::patch()
+        .uri("/api/links/launch")
+        .set_json(json!({ "starts_at": Utc::now() - Duration::minutes(1) }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get().uri("/launch").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://www.rust-lang.org/launch");
+
+    // Expired, deactivated and exhausted links fall back too
+    let mut expired = new_link("expired-fb", "https://www.rust-lang.org");
+    expired.expires_at = Some(Utc::now() - Duration::minutes(1));
+    let mut inactive = new_link("inactive-fb", "https://www.rust-lang.org");
+    inactive.is_active = false;
+    let mut exhausted = new_link("exhausted-fb", "https://www.rust-lang.org");
+    exhausted.click_limit = Some(0);
+    for mut link in [expired, inactive, exhausted] {
+        link.fallback_url = Some(fallback.to_string());
+        let short_code = link.short_code.clone();
+        store.create(link).await.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/{}", short_code))
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 302, "{}", short_code);
+        assert_eq!(resp.headers().get("Location").unwrap(), fallback);
+    }
+
+    // Schedules must end after they start, and fallbacks must be URLs
+    for payload in [
+        json!({ "starts_at": "2030-01-02T00:00:00Z", "expires_at": "2030-01-01T00:00:00Z" }),
+        json!({ "fallback_url": "not a url" }),
+    ] {
+        let req = test::TestRequest::patch()
+            .uri("/api/links/launch")
+            .set_json(payload)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}
+
+#[actix_rt::test]
+async fn test_preview_page() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+
+    let original_url = "https://docs.rs/actix-web/latest/?q=<script>";
+    store.create(new_link("peek", original_url)).await.unwrap();
+
+    // Both forms show the destination without redirecting or counting a click
+    for uri in ["/peek+", "/peek/preview"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200, "{}", uri);
+        assert!(resp.headers().get("Location").is_none());
+        assert_eq!(resp.headers().get("Content-Security-Policy").unwrap(), "default-src 'none'");
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("https://docs.rs/actix-web/latest/?q=&lt;script&gt;"));
+        assert!(!body.contains("<script>"));
+        assert!(body.contains("<strong>docs.rs</strong>"));
+        assert!(body.contains("<dt>Password protected</dt><dd>No</dd>"));
+    }
+
+    // Password-protected links keep their destination hidden until unlocked
+    let mut locked = new_link("hidden", "https://www.rust-lang.org/secret");
+    locked.password_hash = Some(url_shortener::utils::password::hash_password("hunter2"));
+    store.create(locked).await.unwrap();
+
+    let req = test::TestRequest::get().uri("/hidden+").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(!body.contains("rust-lang.org"));
+    assert!(body.contains("<dt>Password protected</dt><dd>Yes</dd>"));
+
+    let req = test::TestRequest::get()
+        .uri("/hidden/preview")
+        .insert_header(("X-Link-Password", "hunter2"))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert!(std::str::from_utf8(&body).unwrap().contains("https://www.rust-lang.org/secret"));
+
+    let req = test::TestRequest::get().uri("/missing+").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_social_card_for_unfurl_bots() {
+    let store = memory_store();
+    let app = test::init_service(api_app(store.clone())).await;
+    let slackbot = "Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)";
+    let browser = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36";
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(json!({
+            "original_url": "https://www.rust-lang.org",
+            "custom_alias": "launch",
+            "click_limit": 1,
+            "card": {
+                "title": "Rust \"1.0\" <launch>",
+                "description": "Release notes",
+                "image": "https://www.rust-lang.org/logo.png",
+            },
+        }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    // Bots get the card and don't use up the link's clicks
+    for _ in 0..2 {
+        let req = test::TestRequest::get()
+            .uri("/launch")
+            .insert_header(("User-Agent", slackbot))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<meta property=\"og:title\" content=\"Rust &quot;1.0&quot; &lt;launch&gt;\">"));
+        assert!(body.contains("<meta property=\"og:image\" content=\"https://www.rust-lang.org/logo.png\">"));
+        assert!(body.contains("<meta name=\"twitter:card\" content=\"summary_large_image\">"));
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/launch")
+        .insert_header(("User-Agent", browser))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 301);
+
+    // Without a card, bots are redirected like everyone else
+    let req = test::TestRequest::patch()
+        .uri("/api/links/launch")
+        .set_json(json!({ "card": null, "click_limit": null }))
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(resp["card"].is_null());
+
+    let req = test::TestRequest::get()
+        .uri("/launch")
+        .insert_header(("User-Agent", slackbot))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 301);
+
+    // Cards need content and an http(s) image
+    for card in [json!({}), json!({ "image": "javascript:alert(1)" })] {
+        let req = test::TestRequest::patch()
+            .uri("/api/links/launch")
+            .set_json(json!({ "card": card }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}
+
+#[actix_rt::test]
+async fn test_clicks_are_recorded() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let (clicks, worker) = ClickRecorder::start(
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickStream::new(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Hash, b"integration-test-secret", false),
+        100,
+        10,
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        clicks.clone(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    let link = store.create(new_link("counted", "https://www.rust-lang.org")).await.unwrap();
+    let req = test::TestRequest::get()
+        .uri("/counted")
+        .insert_header(("Referer", "https://news.ycombinator.com/"))
+        .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+        .insert_header(("X-Forwarded-For", "203.0.113.7"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 301);
+
+    // Links that aren't followed record nothing
+    let req = test::TestRequest::get().uri("/counted+").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri("/missing").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    // The click is stored in the background once its batch is flushed
+    drop(app);
+    drop(clicks);
+    worker.await.unwrap();
+
+    let stored = click_store.clicks();
+    assert_eq!(stored.len(), 1);
+    let click = &stored[0];
+    assert_eq!(click.link_id, link.id);
+    assert_eq!(click.short_code, "counted");
+    assert_eq!(click.referrer.as_deref(), Some("https://news.ycombinator.com/"));
+    assert_eq!(click.referrer_host.as_deref(), Some("news.ycombinator.com"));
+    assert_eq!(click.user_agent.as_deref(), Some(CHROME_ON_WINDOWS));
+    assert_eq!(click.browser.as_deref(), Some("Chrome"));
+    assert_eq!(click.os.as_deref(), Some("windows"));
+    assert_eq!(click.device.as_deref(), Some("desktop"));
+    assert_eq!(click.variant, None);
+    let ip_hash = click.ip_hash.as_deref().unwrap();
+    assert_eq!(ip_hash.len(), 32);
+    assert!(!ip_hash.contains("203.0.113.7"));
+}
+
+#[actix_rt::test]
+async fn test_bot_clicks_are_kept_apart() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let visitors = Arc::new(MemoryVisitorStore::new());
+    let (clicks, worker) = ClickRecorder::start(
+        click_store.clone(),
+        visitors.clone(),
+        ClickStream::new(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Hash, b"integration-test-secret", false),
+        100,
+        10,
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        visitors.clone(),
+        clicks.clone(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    store.create(new_link("crawled", "https://www.rust-lang.org")).await.unwrap();
+    let mut link = new_link("limited", "https://www.rust-lang.org");
+    link.click_limit = Some(1);
+    store.create(link).await.unwrap();
+
+    let bots = |uri: &str| {
+        [
+            test::TestRequest::get().uri(uri).insert_header(("User-Agent", "curl/8.4.0")),
+            test::TestRequest::get().uri(uri),
+            test::TestRequest::default()
+                .method(actix_web::http::Method::HEAD)
+                .uri(uri)
+                .insert_header(("User-Agent", CHROME_ON_WINDOWS)),
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+                .insert_header(("Sec-Purpose", "prefetch;prerender")),
+        ]
+    };
+    for (i, req) in bots("/crawled").into_iter().enumerate() {
+        let req = req.insert_header(("X-Forwarded-For", format!("198.51.100.{}", i))).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 301);
+    }
+    let person = |uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .insert_header(("X-Forwarded-For", "203.0.113.7"))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, person("/crawled")).await.status(), 301);
+
+    // Bots don't use up a click limit, so they aren't sent to the destination either,
+    // but HEADs and prefetches still succeed
+    for req in bots("/limited") {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get("location").is_none());
+        assert_eq!(resp.headers().get("Cache-Control").unwrap(), "private, no-store");
+    }
+    for expected in [301, 410] {
+        assert_eq!(test::call_service(&app, person("/limited")).await.status(), expected);
+    }
+
+    drop(app);
+    drop(clicks);
+    worker.await.unwrap();
+
+    let clicks_of = |short_code: &str| -> Vec<ClickEvent> {
+        click_store.clicks().into_iter().filter(|click| click.short_code == short_code).collect()
+    };
+    assert_eq!(clicks_of("limited").len(), 1);
+    let mut reasons: Vec<_> = clicks_of("crawled").into_iter().map(|click| click.bot_reason).collect();
+    reasons.sort();
+    assert_eq!(
+        reasons,
+        [
+            None,
+            Some("head_request".to_string()),
+            Some("missing_user_agent".to_string()),
+            Some("prefetch".to_string()),
+            Some("user_agent:curl/".to_string()),
+        ]
+    );
+
+    // Stats leave bots out unless asked for, and never count them as visitors
+    let app = test::init_service(api_app_with_clicks(store, click_store, visitors, ClickRecorder::disabled(), ClickStream::new())).await;
+    for (bots, expected) in [("", 1), ("&bots=exclude", 1), ("&bots=include", 5), ("&bots=only", 4)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/links/crawled/stats?interval=day{}", bots))
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["total_clicks"], expected, "bots={}", bots);
+        assert_eq!(resp["unique_visitors"], 1, "bots={}", bots);
+    }
+}
+
+#[actix_rt::test]
+async fn test_click_pipeline_counters() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let (clicks, _worker) = ClickRecorder::start(
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickStream::new(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Hash, b"integration-test-secret", false),
+        100,
+        10,
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store,
+        Arc::new(MemoryVisitorStore::new()),
+        clicks,
+        ClickStream::new(),
+    ))
+    .await;
+
+    store.create(new_link("counted", "https://www.rust-lang.org")).await.unwrap();
+    let req = test::TestRequest::get().uri("/counted").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 301);
+
+    let req = test::TestRequest::get().uri("/api/clicks/pipeline").to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["enabled"], true);
+    assert_eq!(resp["queued"], 1);
+    assert_eq!(resp["dropped"], 0);
+}
+
+#[actix_rt::test]
+async fn test_link_stats() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let visitors = Arc::new(MemoryVisitorStore::new());
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        visitors.clone(),
+        ClickRecorder::disabled(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    let link = store.create(new_link("popular", "https://www.rust-lang.org")).await.unwrap();
+    let other = store.create(new_link("other", "https://www.rust-lang.org")).await.unwrap();
+    let click = |link_id, time: &str, visitor: &str, referrer: Option<&str>, country: &str| ClickEvent {
+        link_id,
+        short_code: String::new(),
+        occurred_at: time.parse().unwrap(),
+        referrer: None,
+        referrer_host: referrer.map(str::to_string),
+        user_agent: None,
+        browser: Some("Chrome".to_string()),
+        os: Some("android".to_string()),
+        device: Some("mobile".to_string()),
+        ip_hash: Some(visitor.to_string()),
+        country: Some(country.to_string()),
+        variant: None,
+        bot_reason: None,
+    };
+    let clicks = [
+        click(link.id, "2024-03-01T09:00:00Z", "a", Some("t.co"), "DE"),
+        click(link.id, "2024-03-01T17:30:00Z", "a", Some("t.co"), "DE"),
+        click(link.id, "2024-03-01T18:00:00Z", "b", None, "FR"),
+        click(link.id, "2024-03-03T08:00:00Z", "c", Some("github.com"), "DE"),
+        // Outside the range, or of another link
+        click(link.id, "2024-03-04T00:00:00Z", "d", Some("t.co"), "DE"),
+        click(other.id, "2024-03-01T10:00:00Z", "e", Some("t.co"), "DE"),
+    ];
+    click_store.insert_clicks(&clicks).await.unwrap();
+    visitors.add_visitors(&clicks).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/links/popular/stats?from=2024-03-01T00:00:00Z&to=2024-03-04T00:00:00Z&interval=day")
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["total_clicks"], 4);
+    assert_eq!(resp["unique_clicks"], 3);
+    assert_eq!(resp["unique_visitors"], 3);
+
+    // Days without clicks are part of the series
+    let series = resp["series"].as_array().unwrap();
+    assert_eq!(series.len(), 3);
+    assert_eq!(series[0], json!({ "start": "2024-03-01T00:00:00Z", "clicks": 3, "unique_clicks": 2 }));
+    assert_eq!(series[1]["clicks"], 0);
+    assert_eq!(series[2]["clicks"], 1);
+
+    assert_eq!(
+        resp["referrers"],
+        json!([{ "value": "t.co", "clicks": 2 }, { "value": "github.com", "clicks": 1 }])
+    );
+    assert_eq!(
+        resp["countries"],
+        json!([{ "value": "DE", "clicks": 3 }, { "value": "FR", "clicks": 1 }])
+    );
+    assert_eq!(resp["browsers"], json!([{ "value": "Chrome", "clicks": 4 }]));
+    assert_eq!(resp["os"], json!([{ "value": "android", "clicks": 4 }]));
+    assert_eq!(resp["devices"], json!([{ "value": "mobile", "clicks": 4 }]));
+
+    // Hourly buckets and shorter top lists
+    let req = test::TestRequest::get()
+        .uri("/api/links/popular/stats?from=2024-03-01T09:30:00Z&to=2024-03-01T18:00:00Z&interval=hour&limit=1")
+        .to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["total_clicks"], 1);
+    // Visitors are counted over whole days
+    assert_eq!(resp["unique_visitors"], 2);
+    assert_eq!(resp["series"].as_array().unwrap().len(), 9);
+    assert_eq!(resp["series"][0]["start"], "2024-03-01T09:00:00Z");
+    assert_eq!(resp["referrers"].as_array().unwrap().len(), 1);
+
+    // Ranges must be ordered and not too finely bucketed
+    for query in [
+        "from=2024-03-04T00:00:00Z&to=2024-03-01T00:00:00Z",
+        "from=2000-01-01T00:00:00Z&to=2024-01-01T00:00:00Z&interval=hour",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/links/popular/stats?{}", query))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+
+    let req = test::TestRequest::get().uri("/api/links/missing/stats").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_do_not_track_is_honored() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let (clicks, worker) = ClickRecorder::start(
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickStream::new(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Truncate, b"integration-test-secret", true),
+        100,
+        10,
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        clicks.clone(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    store.create(new_link("private", "https://www.rust-lang.org")).await.unwrap();
+    for opt_out in [None, Some(("DNT", "1")), Some(("Sec-GPC", "1")), Some(("DNT", "0"))] {
+        let mut req = test::TestRequest::get()
+            .uri("/private")
+            .insert_header(("Referer", "https://news.ycombinator.com/item?id=1"))
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .insert_header(("X-Forwarded-For", "203.0.113.7"));
+        if let Some(header) = opt_out {
+            req = req.insert_header(header);
+        }
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), 301);
+    }
+
+    drop(app);
+    drop(clicks);
+    worker.await.unwrap();
+
+    // Visitors opting out are only counted in aggregate, keeping nothing derived from
+    // their request either
+    let stored = click_store.clicks();
+    let tracked: Vec<bool> = stored.iter().map(|click| click.ip_hash.is_some()).collect();
+    assert_eq!(tracked, [true, false, false, true]);
+    for click in &stored {
+        assert_eq!(click.short_code, "private");
+        assert!(click.country.is_none());
+        if click.ip_hash.is_some() {
+            assert_eq!(click.ip_hash.as_deref(), Some("203.0.113.0"));
+            assert!(click.referrer.is_some() && click.user_agent.is_some());
+            assert_eq!(click.referrer_host.as_deref(), Some("news.ycombinator.com"));
+            assert_eq!(click.browser.as_deref(), Some("Chrome"));
+            assert_eq!(click.os.as_deref(), Some("windows"));
+            assert_eq!(click.device.as_deref(), Some("desktop"));
+        } else {
+            assert!(click.referrer.is_none() && click.user_agent.is_none());
+            assert!(click.referrer_host.is_none() && click.browser.is_none());
+            assert!(click.os.is_none() && click.device.is_none());
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_click_analytics_can_be_deleted() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let visitors = Arc::new(MemoryVisitorStore::new());
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        visitors.clone(),
+        ClickRecorder::disabled(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    let link = store.create(owned_link("forgotten", "dashboard")).await.unwrap();
+    let other = store.create(owned_link("kept", "dashboard")).await.unwrap();
+    let theirs = store.create(owned_link("theirs", "other")).await.unwrap();
+    let click = |link_id, visitor: &str| ClickEvent {
+        link_id,
+        short_code: String::new(),
+        occurred_at: Utc::now() - Duration::hours(1),
+        referrer: None,
+        referrer_host: None,
+        user_agent: None,
+        browser: None,
+        os: None,
+        device: None,
+        ip_hash: Some(visitor.to_string()),
+        country: None,
+        variant: None,
+        bot_reason: None,
+    };
+    let clicks = [
+        click(link.id, "a"),
+        click(link.id, "b"),
+        click(other.id, "a"),
+        click(other.id, "b"),
+        click(other.id, "b"),
+        click(theirs.id, "b"),
+    ];
+    click_store.insert_clicks(&clicks).await.unwrap();
+    visitors.add_visitors(&clicks).await.unwrap();
+
+    let stats = |short_code: &str| test::TestRequest::get()
+        .uri(&format!("/api/links/{}/stats", short_code))
+        .to_request();
+    let delete = |uri: &str| test::TestRequest::delete()
+        .uri(uri)
+        .insert_header(("Authorization", "Bearer dashboard-token"))
+        .to_request();
+
+    // Deleting takes the access token of the links' account
+    for (uri, authorization) in [
+        ("/api/links/forgotten/stats", None),
+        ("/api/links/forgotten/stats", Some("Bearer wrong-token")),
+        ("/api/visitors/b/clicks", None),
+        ("/api/visitors/b/clicks", Some("Bearer wrong-token")),
+    ] {
+        let mut req = test::TestRequest::delete().uri(uri);
+        if let Some(authorization) = authorization {
+            req = req.insert_header(("Authorization", authorization));
+        }
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), 401, "{}", uri);
+    }
+    assert_eq!(click_store.clicks().len(), clicks.len());
+
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, delete("/api/links/forgotten/stats")).await;
+    assert_eq!(resp, json!({ "clicks": 2 }));
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, stats("forgotten")).await;
+    assert_eq!(resp["total_clicks"], 0);
+    assert_eq!(resp["unique_visitors"], 0);
+
+    // A visitor's clicks on the account's links go, while the visitor counts stay
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, delete("/api/visitors/b/clicks")).await;
+    assert_eq!(resp, json!({ "clicks": 2 }));
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, stats("kept")).await;
+    assert_eq!(resp["total_clicks"], 1);
+    assert_eq!(resp["unique_visitors"], 2);
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, stats("theirs")).await;
+    assert_eq!(resp["total_clicks"], 1);
+
+    for uri in ["/api/links/missing/stats", "/api/links/theirs/stats"] {
+        assert_eq!(test::call_service(&app, delete(uri)).await.status(), 404, "{}", uri);
+    }
+}
+
+#[actix_rt::test]
+async fn test_clicks_are_streamed_live() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let stream = ClickStream::new();
+    let (clicks, _worker) = ClickRecorder::start(
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        stream.clone(),
+        GeoIp::disabled(),
+        ClickPrivacy::new(IpMode::Hash, b"integration-test-secret", false),
+        100,
+        10,
+        std::time::Duration::from_millis(10),
+    )
+    .unwrap();
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store,
+        Arc::new(MemoryVisitorStore::new()),
+        clicks,
+        stream,
+    ))
+    .await;
+
+    // Links created with an access token belong to its account
+    for (authorization, expected) in [("Bearer wrong-token", 401), ("Bearer dashboard-token", 200)] {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .insert_header(("Authorization", authorization))
+            .set_json(json!({ "original_url": "https://www.rust-lang.org", "custom_alias": "live" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), expected);
+    }
+    assert_eq!(store.get_by_code("live").await.unwrap().unwrap().owner.as_deref(), Some("dashboard"));
+    store.create(owned_link("quiet", "dashboard")).await.unwrap();
+    store.create(owned_link("theirs", "other")).await.unwrap();
+    store.create(new_link("anonymous", "https://www.rust-lang.org")).await.unwrap();
+
+    // Watching takes the access token, and links of its account
+    for (uri, authorization, expected) in [
+        ("/api/links/live/events", None, 401),
+        ("/api/links/live/events", Some("Bearer wrong-token"), 401),
+        ("/api/clicks/events", Some("Basic dashboard-token"), 401),
+        ("/api/clicks/events?access_token=dashboard-token", None, 401),
+        ("/api/links/missing/events", Some("Bearer dashboard-token"), 404),
+        ("/api/links/theirs/events", Some("Bearer dashboard-token"), 404),
+        ("/api/links/anonymous/events", Some("Bearer dashboard-token"), 404),
+        ("/api/clicks/events?links=live,missing", Some("Bearer dashboard-token"), 404),
+        ("/api/clicks/events?links=live,theirs", Some("Bearer dashboard-token"), 404),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(authorization) = authorization {
+            req = req.insert_header(("Authorization", authorization));
+        }
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), expected, "{}", uri);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/links/live/events")
+        .insert_header(("Authorization", "Bearer dashboard-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/event-stream");
+    let mut link_events = Box::pin(resp.into_body());
+
+    let req = test::TestRequest::get()
+        .uri("/api/clicks/events")
+        .insert_header(("Authorization", "Bearer dashboard-token"))
+        .to_request();
+    let mut all_events = Box::pin(test::call_service(&app, req).await.into_body());
+
+    for events in [&mut link_events, &mut all_events] {
+        assert_eq!(next_event(events).await, ": connected\n\n");
+    }
+
+    // Clicks of other accounts' links, and of links without an owner, aren't streamed
+    for short_code in ["theirs", "anonymous", "quiet", "live"] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/{}", short_code))
+            .insert_header(("User-Agent", CHROME_ON_WINDOWS))
+            .insert_header(("X-Forwarded-For", "203.0.113.7"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 301);
+    }
+
+    // Streamed clicks leave out who clicked
+    let click = |event: String| -> serde_json::Value {
+        let data = event.strip_prefix("event: click\ndata: ").unwrap();
+        serde_json::from_str(data.trim_end()).unwrap()
+    };
+    let streamed = click(next_event(&mut link_events).await);
+    assert_eq!(streamed["short_code"], "live");
+    assert_eq!(streamed["browser"], "Chrome");
+    assert!(streamed.get("ip_hash").is_none());
+    assert!(streamed.get("user_agent").is_none());
+
+    assert_eq!(click(next_event(&mut all_events).await)["short_code"], "quiet");
+    assert_eq!(click(next_event(&mut all_events).await)["short_code"], "live");
+
+    // Nobody may watch without accounts, and accounts need a token of their own
+    let req = test::TestRequest::get()
+        .insert_header(("Authorization", "Bearer dashboard-token"))
+        .to_http_request();
+    assert!(Accounts::default().authenticate(&req).is_err());
+    assert_eq!(accounts().authenticate(&req).unwrap(), "dashboard");
+    for entries in [&["dashboard"][..], &["dashboard:"], &["a:token", "b:token"]] {
+        let entries: Vec<String> = entries.iter().map(|entry| entry.to_string()).collect();
+        assert!(Accounts::new(&entries).is_err(), "{:?}", entries);
+    }
+}
+
+#[actix_rt::test]
+async fn test_clicks_can_be_exported() {
+    let store = memory_store();
+    let click_store = Arc::new(MemoryClickStore::new());
+    let app = test::init_service(api_app_with_clicks(
+        store.clone(),
+        click_store.clone(),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickRecorder::disabled(),
+        ClickStream::new(),
+    ))
+    .await;
+
+    let tagged = |short_code, owner| NewLink {
+        tags: vec!["spring".to_string()],
+        ..owned_link(short_code, owner)
+    };
+    let link = store.create(tagged("exported", "dashboard")).await.unwrap();
+    let other = store.create(owned_link("busy", "dashboard")).await.unwrap();
+    let theirs = store.create(tagged("theirs", "other")).await.unwrap();
+    let day = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc() - Duration::days(1);
+    let click = |link_id, short_code: &str, visitor: &str| ClickEvent {
+        link_id,
+        short_code: short_code.to_string(),
+        occurred_at: day + Duration::hours(2),
+        referrer: None,
+        referrer_host: None,
+        user_agent: None,
+        browser: None,
+        os: None,
+        device: None,
+        ip_hash: Some(visitor.to_string()),
+        country: Some("DE".to_string()),
+        variant: None,
+        bot_reason: None,
+    };
+    let quoted = ClickEvent {
+        referrer: Some("https://example.com/?q=\"a,b\"".to_string()),
+        referrer_host: Some("example.com".to_string()),
+        ..click(link.id, "exported", "a")
+    };
+    let formula = ClickEvent {
+        user_agent: Some("=HYPERLINK(\"https://evil.example\")".to_string()),
+        ..click(link.id, "exported", "b")
+    };
+    click_store.insert_clicks(&[quoted, formula]).await.unwrap();
+    // More clicks than fit in a page, all at the same time
+    let busy: Vec<ClickEvent> = (0..1001).map(|_| click(other.id, "busy", "c")).collect();
+    click_store.insert_clicks(&busy).await.unwrap();
+    click_store.insert_clicks(&[click(theirs.id, "theirs", "d")]).await.unwrap();
+
+    // Exporting takes the access token of the links' account
+    for (uri, authorization) in [
+        ("/api/links/exported/export", None),
+        ("/api/clicks/export", Some("Bearer wrong-token")),
+        ("/api/clicks/export?access_token=dashboard-token", None),
+        ("/api/tags/spring/export", None),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(authorization) = authorization {
+            req = req.insert_header(("Authorization", authorization));
+        }
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), 401, "{}", uri);
+    }
+
+    let export = |uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", "Bearer dashboard-token"))
+            .to_request()
+    };
+    let resp = test::call_service(&app, export("/api/links/exported/export")).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+    assert_eq!(
+        resp.headers().get("content-disposition").unwrap(),
+        "attachment; filename=\"exported-clicks.csv\""
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(
+        lines[0],
+        "link_id,short_code,occurred_at,referrer,referrer_host,user_agent,browser,os,device,\
+         ip_hash,country,variant,bot_reason"
+    );
+    let occurred_at = (day + Duration::hours(2)).to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    assert_eq!(
+        lines[1],
+        format!(
+            "{},exported,{},\"https://example.com/?q=\"\"a,b\"\"\",example.com,,,,,a,DE,,",
+            link.id, occurred_at
+        )
+    );
+    // Formulas are shown rather than evaluated by spreadsheets
+    assert_eq!(
+        lines[2],
+        format!(
+            "{},exported,{},,,\"'=HYPERLINK(\"\"https://evil.example\"\")\",,,,b,DE,,",
+            link.id, occurred_at
+        )
+    );
+    assert_eq!(lines.len(), 3);
+
+    // The account's links carrying a tag
+    let resp = test::call_service(&app, export("/api/tags/spring/export")).await;
+    assert_eq!(
+        resp.headers().get("content-disposition").unwrap(),
+        "attachment; filename=\"tag-spring-clicks.csv\""
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(body.lines().collect::<Vec<_>>(), lines);
+    let resp = test::call_service(&app, export("/api/tags/autumn/export")).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(body.lines().count(), 1);
+
+    // Every click of every link of the account, across pages
+    let resp = test::call_service(&app, export("/api/clicks/export?format=ndjson")).await;
+    assert_eq!(resp.headers().get("content-type").unwrap(), "application/x-ndjson");
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let clicks: Vec<serde_json::Value> =
+        body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(clicks.len(), 1003);
+    assert_eq!(clicks[0]["short_code"], "exported");
+    assert_eq!(clicks[0]["variant"], serde_json::Value::Null);
+
+    let resp = test::call_service(&app, export("/api/links/exported/export?data=daily_rollups")).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let bucket = day.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    assert_eq!(
+        body,
+        format!(
+            "link_id,bucket,is_bot,dimension,value,clicks,unique_clicks\n\
+             {id},{bucket},false,country,DE,2,2\n\
+             {id},{bucket},false,referrer_host,example.com,1,1\n\
+             {id},{bucket},false,total,,2,2\n",
+            id = link.id,
+            bucket = bucket
+        )
+    );
+
+    // Nothing outside the range
+    let from = (day + Duration::days(1)).to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    let resp = test::call_service(&app, export(&format!("/api/clicks/export?from={}", from))).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(body.lines().count(), 1);
+
+    let req = export(&format!("/api/clicks/export?from={}&to={}", from, occurred_at));
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    for uri in ["/api/links/missing/export", "/api/links/theirs/export"] {
+        assert_eq!(test::call_service(&app, export(uri)).await.status(), 404, "{}", uri);
+    }
+}
+
+/// Helper function to wait for the next chunk of a streamed response body
+async fn next_event<B>(body: &mut std::pin::Pin<Box<B>>) -> String
+where
+    B: actix_web::body::MessageBody,
+    B::Error: std::fmt::Debug,
+{
+    let chunk = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        futures_util::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+    )
+    .await
+    .expect("no event within 5 seconds")
+    .expect("the stream ended")
+    .unwrap();
+    String::from_utf8(chunk.to_vec()).unwrap()
+}
+
+/// Helper function to create an empty in-memory link store
+fn memory_store() -> DynLinkStore {
+    Arc::new(MemoryLinkStore::new())
+}
+
+/// Helper function to create the unlock token signer used by the redirect
+fn unlock_tokens() -> UnlockTokens {
+    UnlockTokens::new(b"integration-test-secret", 60)
+}
+
+/// Helper function to create the default redirect policy: 301, cached for an hour
+fn redirect_policy() -> RedirectPolicy {
+    RedirectPolicy::new(301, 3600).unwrap()
+}
+
+/// Helper function to create an allocator for default random short codes
+fn code_allocator() -> CodeAllocator {
+    CodeAllocator::new(Arc::new(RandomGenerator::default()), 8, 0.1, 100)
+}
+
+/// Helper function to create the default custom alias rules
+fn alias_policy() -> AliasPolicy {
+    let charset = format!("{}-_", BASE62_ALPHABET);
+    AliasPolicy::new(&charset, 3, 64, routes::reserved_words(), false).unwrap()
+}
+
+/// Helper function to describe an active link without options
+fn new_link(short_code: &str, original_url: &str) -> NewLink {
+    NewLink {
+        original_url: original_url.to_string(),
+        short_code: short_code.to_string(),
+        starts_at: None,
+        expires_at: None,
+        fallback_url: None,
+        password_hash: None,
+        click_limit: None,
+        redirect_status: None,
+        passthrough: Passthrough::None,
+        rules: Vec::new(),
+        variants: Vec::new(),
+        card: None,
+        is_active: true,
+        owner: None,
+        tags: Vec::new(),
+    }
+}
+
+/// Helper function to build the application with all registered routes
+fn api_app(
+    store: DynLinkStore,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    api_app_with_clicks(
+        store,
+        Arc::new(MemoryClickStore::new()),
+        Arc::new(MemoryVisitorStore::new()),
+        ClickRecorder::disabled(),
+        ClickStream::new(),
+    )
+}
+
+/// Helper function to build the accounts of the tests, each with its own token
+fn accounts() -> Accounts {
+    Accounts::new(&["dashboard:dashboard-token".to_string(), "other:other-token".to_string()]).unwrap()
+}
+
+/// Helper function to build a link owned by the account
+fn owned_link(short_code: &str, owner: &str) -> NewLink {
+    NewLink {
+        owner: Some(owner.to_string()),
+        tags: Vec::new(),
+        ..new_link(short_code, "https://www.rust-lang.org")
+    }
+}
+
+/// Helper function to build the application with all registered routes, recording
+/// clicks with the given recorder, reporting stats from the click and visitor stores
+/// and streaming clicks from the given stream
+fn api_app_with_clicks(
+    store: DynLinkStore,
+    click_store: DynClickStore,
+    visitors: DynVisitorStore,
+    clicks: ClickRecorder,
+    stream: ClickStream,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .document(Spec::default())
+        .app_data(web::Data::new(store))
+        .app_data(web::Data::new(unlock_tokens()))
+        .app_data(web::Data::new(redirect_policy()))
+        .app_data(web::Data::new(GeoIp::disabled()))
+        .app_data(web::Data::new(clicks))
+        .app_data(web::Data::new(click_store))
+        .app_data(web::Data::new(visitors))
+        .app_data(web::Data::new(stream))
+        .app_data(web::Data::new(accounts()))
+        .app_data(web::Data::new(code_allocator()))
+        .app_data(web::Data::new(alias_policy()))
+        .app_data(web::Data::new("http://localhost:8080".to_string()))
+        .configure(routes::config)
+        .build("/openapi.json/")
 }
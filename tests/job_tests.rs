@@ -0,0 +1,351 @@
+// tests/job_tests.rs
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use url_shortener::db::migrations;
+use url_shortener::errors::ServiceError;
+use url_shortener::models::click::{
+    BotFilter, ClickBucket, ClickDimension, ClickEvent, ClickQuery, Interval,
+};
+use url_shortener::models::export::{ClickRollup, ExportScope};
+use url_shortener::services::{Job, JobRunner};
+use url_shortener::store::{ClickStore, MemoryClickStore, PgClickRollups, PgClickStore};
+
+/// Job counting its runs, failing every other one
+#[derive(Default)]
+struct CountingJob {
+    runs: AtomicUsize,
+}
+
+#[async_trait]
+impl Job for CountingJob {
+    fn name(&self) -> &'static str {
+        "counting"
+    }
+
+    fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(10)
+    }
+
+    async fn run(&self) -> Result<(), ServiceError> {
+        if self.runs.fetch_add(1, Ordering::SeqCst) % 2 == 1 {
+            return Err(ServiceError::BadRequest("odd run".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_jobs_run_repeatedly_until_shutdown() {
+    let job = Arc::new(CountingJob::default());
+    let runner = JobRunner::start(vec![job.clone()]);
+
+    // Failed runs are retried
+    while job.runs.load(Ordering::SeqCst) < 4 {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    runner.shutdown().await;
+    let runs = job.runs.load(Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(job.runs.load(Ordering::SeqCst), runs);
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_rollups_match_raw_clicks() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = Arc::new(sqlx::PgPool::connect(&database_url).await.unwrap());
+    migrations::run_pending(&pool).await.unwrap();
+    let rollups = PgClickRollups::new(pool.clone());
+    let postgres = PgClickStore::new(pool);
+    let memory = MemoryClickStore::new();
+
+    // Clicks stored after the hours they belong to were rolled up, and in the
+    // current hour, which is read from the clicks table
+    let watermark = rollups.roll_up_to(Utc::now()).await.unwrap();
+    let link_id = uuid::Uuid::new_v4();
+    let click = |hours_ago: f64, visitor: &str, country: &str, bot: bool| ClickEvent {
+        link_id,
+        short_code: "rolled".to_string(),
+        occurred_at: watermark - Duration::seconds((hours_ago * 3600.0) as i64),
+        referrer: None,
+        referrer_host: Some("t.co".to_string()),
+        user_agent: None,
+        browser: Some("Chrome".to_string()),
+        os: None,
+        device: Some("desktop".to_string()),
+        ip_hash: Some(visitor.to_string()),
+        country: Some(country.to_string()),
+        variant: Some(if bot { "b" } else { "a" }.to_string()),
+        bot_reason: bot.then(|| "user_agent:curl/".to_string()),
+    };
+    let clicks = [
+        click(50.0, "a", "DE", false),
+        click(49.5, "a", "DE", false),
+        click(49.2, "b", "FR", false),
+        click(26.0, "c", "DE", true),
+        click(3.0, "a", "US", false),
+        click(-0.01, "d", "DE", false),
+    ];
+    postgres.insert_clicks(&clicks).await.unwrap();
+    memory.insert_clicks(&clicks).await.unwrap();
+
+    // Hours clicks were stored in late are rolled up by the next run, and rolling
+    // them up again is idempotent
+    for rebuild in [false, true] {
+        if rebuild {
+            rollups.rebuild(watermark - Duration::hours(51), watermark).await.unwrap();
+        } else {
+            rollups.roll_up_to(Utc::now()).await.unwrap();
+        }
+
+        for (from, interval) in [
+            (watermark - Duration::hours(72), Interval::Hour),
+            (watermark - Duration::hours(72), Interval::Day),
+            (watermark - Duration::minutes(49 * 60 + 45), Interval::Day),
+            (watermark - Duration::hours(72), Interval::Week),
+        ] {
+            for bots in [BotFilter::Exclude, BotFilter::Include, BotFilter::Only] {
+                let query = ClickQuery {
+                    link_id,
+                    from,
+                    to: watermark + Duration::hours(1),
+                    bots,
+                };
+                assert_same_stats(&postgres, &memory, &query, interval).await;
+            }
+        }
+
+        for (granularity, to) in [
+            (Interval::Hour, watermark),
+            (Interval::Day, Interval::Day.truncate(watermark)),
+        ] {
+            let scope = ExportScope {
+                links: Some(vec![link_id]),
+                from: watermark - Duration::hours(72),
+                to,
+            };
+            assert_eq!(
+                export_rollups(&postgres, &scope, granularity).await,
+                export_rollups(&memory, &scope, granularity).await,
+                "{:?}",
+                granularity
+            );
+        }
+    }
+
+    // Clicks are exported in time order, whatever order they were stored in
+    let scope = ExportScope {
+        links: Some(vec![link_id]),
+        from: watermark - Duration::hours(72),
+        to: watermark + Duration::hours(1),
+    };
+    let (mut after, mut exported) = (None, Vec::new());
+    loop {
+        let page = postgres.export_clicks(&scope, after, 4).await.unwrap();
+        after = page.last().map(|(position, _)| *position);
+        exported.extend(page.into_iter().map(|(_, click)| click.occurred_at));
+        if after.is_none() {
+            break;
+        }
+    }
+    let mut times: Vec<DateTime<Utc>> = clicks.iter().map(|click| click.occurred_at).collect();
+    times.sort();
+    assert_eq!(exported, times);
+}
+
+/// Every rollup row of the scope, read from the store in pages of a few rows
+async fn export_rollups(
+    store: &dyn ClickStore,
+    scope: &ExportScope,
+    granularity: Interval,
+) -> Vec<ClickRollup> {
+    let mut rows: Vec<ClickRollup> = Vec::new();
+    loop {
+        let page = store.export_rollups(scope, granularity, rows.last(), 3).await.unwrap();
+        if page.is_empty() {
+            return rows;
+        }
+        rows.extend(page);
+    }
+}
+
+/// Checks both stores report the same stats for the query
+async fn assert_same_stats(
+    postgres: &PgClickStore,
+    memory: &MemoryClickStore,
+    query: &ClickQuery,
+    interval: Interval,
+) {
+    let context = format!("{:?} {:?}", query, interval);
+    assert_eq!(
+        postgres.count_clicks(query).await.unwrap(),
+        memory.count_clicks(query).await.unwrap(),
+        "{}",
+        context
+    );
+
+    // Visitors of weekly buckets are counted per day in the rollups
+    let series = |buckets: Vec<ClickBucket>| -> Vec<(DateTime<Utc>, i64, Option<i64>)> {
+        buckets
+            .into_iter()
+            .map(|bucket| {
+                let unique = (interval != Interval::Week).then_some(bucket.unique_clicks);
+                (bucket.start, bucket.clicks, unique)
+            })
+            .collect()
+    };
+    assert_eq!(
+        series(postgres.click_series(query, interval).await.unwrap()),
+        series(memory.click_series(query, interval).await.unwrap()),
+        "{}",
+        context
+    );
+
+    for dimension in ClickDimension::ALL {
+        assert_eq!(
+            postgres.top_values(query, dimension, 10).await.unwrap(),
+            memory.top_values(query, dimension, 10).await.unwrap(),
+            "{} {:?}",
+            context,
+            dimension
+        );
+    }
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_purged_clicks_stay_counted_until_deleted() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = Arc::new(sqlx::PgPool::connect(&database_url).await.unwrap());
+    migrations::run_pending(&pool).await.unwrap();
+    let rollups = PgClickRollups::new(pool.clone());
+    let postgres = PgClickStore::new(pool.clone());
+    let memory = MemoryClickStore::new();
+
+    // Clicks of days long rolled up, the first two of which are purged. Earlier runs
+    // purged the same days, which would keep them from being rebuilt
+    sqlx::query("DELETE FROM job_watermarks WHERE job = 'click_retention'")
+        .execute(pool.as_ref())
+        .await
+        .unwrap();
+    rollups.roll_up_to(Utc::now()).await.unwrap();
+    let day = Interval::Day.truncate(Utc::now() - Duration::days(4000));
+    let link_id = uuid::Uuid::new_v4();
+    let click = |hours: i64, visitor: &str, country: &str| ClickEvent {
+        link_id,
+        short_code: "purged".to_string(),
+        occurred_at: day + Duration::hours(hours),
+        referrer: None,
+        referrer_host: None,
+        user_agent: None,
+        browser: None,
+        os: None,
+        device: None,
+        ip_hash: Some(format!("{}:{}", visitor, link_id)),
+        country: Some(country.to_string()),
+        variant: None,
+        bot_reason: None,
+    };
+    let clicks = [
+        click(8, "a", "DE"),
+        click(10, "b", "DE"),
+        click(29, "a", "FR"),
+        click(44, "c", "DE"),
+        click(84, "a", "US"),
+    ];
+    postgres.insert_clicks(&clicks).await.unwrap();
+    memory.insert_clicks(&clicks).await.unwrap();
+    rollups.rebuild(day, day + Duration::days(4)).await.unwrap();
+
+    rollups.purge_clicks(day + Duration::hours(54)).await.unwrap();
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM clicks WHERE link_id = $1")
+        .bind(link_id)
+        .fetch_one(pool.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 1);
+
+    // Rebuilding leaves the purged days' rollups alone
+    rollups.rebuild(day, day + Duration::days(4)).await.unwrap();
+
+    // Ranges ending or starting on a purged day count it whole
+    for (from, to) in [
+        (day + Duration::hours(6), day + Duration::days(4)),
+        (day + Duration::hours(6), day + Duration::hours(46)),
+        (day - Duration::days(1), day + Duration::hours(80)),
+    ] {
+        let query = ClickQuery {
+            link_id,
+            from,
+            to,
+            bots: BotFilter::Exclude,
+        };
+        let context = format!("{:?}", query);
+        assert_eq!(
+            postgres.count_clicks(&query).await.unwrap().clicks,
+            memory.count_clicks(&query).await.unwrap().clicks,
+            "{}",
+            context
+        );
+        for interval in [Interval::Hour, Interval::Day] {
+            let series = |buckets: Vec<ClickBucket>| -> Vec<(DateTime<Utc>, i64)> {
+                buckets.into_iter().map(|bucket| (bucket.start, bucket.clicks)).collect()
+            };
+            assert_eq!(
+                series(postgres.click_series(&query, interval).await.unwrap()),
+                series(memory.click_series(&query, interval).await.unwrap()),
+                "{} {:?}",
+                context,
+                interval
+            );
+        }
+        assert_eq!(
+            postgres.top_values(&query, ClickDimension::Country, 10).await.unwrap(),
+            memory.top_values(&query, ClickDimension::Country, 10).await.unwrap(),
+            "{}",
+            context
+        );
+    }
+
+    // Deleting the link's clicks takes their rollups along
+    let visitor = format!("a:{}", link_id);
+    assert_eq!(postgres.delete_visitor_clicks(&visitor, &[link_id]).await.unwrap(), 1);
+    assert_eq!(postgres.delete_link_clicks(link_id).await.unwrap(), 0);
+    let query = ClickQuery {
+        link_id,
+        from: day,
+        to: day + Duration::days(4),
+        bots: BotFilter::Include,
+    };
+    assert_eq!(postgres.count_clicks(&query).await.unwrap().clicks, 0);
+    assert!(postgres.click_series(&query, Interval::Hour).await.unwrap().is_empty());
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_daily_salts_are_discarded_after_their_day() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = Arc::new(sqlx::PgPool::connect(&database_url).await.unwrap());
+    migrations::run_pending(&pool).await.unwrap();
+    let instances = [PgClickStore::new(pool.clone()), PgClickStore::new(pool)];
+
+    // Instances share the salt of the day until the next day's is read
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 9, 15).unwrap();
+    let salt = instances[0].daily_salt(day).await.unwrap();
+    assert_eq!(salt.len(), 32);
+    assert_eq!(instances[1].daily_salt(day).await.unwrap(), salt);
+    let next_day = instances[1].daily_salt(day.succ_opt().unwrap()).await.unwrap();
+    assert_ne!(next_day, salt);
+    assert_ne!(instances[0].daily_salt(day).await.unwrap(), salt);
+}
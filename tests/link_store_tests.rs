@@ -0,0 +1,191 @@
+// tests/link_store_tests.rs
+
+use std::sync::Arc;
+
+use url_shortener::db::migrations;
+use url_shortener::models::link::{LinkChanges, NewLink, Passthrough};
+use url_shortener::store::{DynLinkStore, MemoryLinkStore, PgLinkStore};
+
+const CLICK_LIMIT: i32 = 10;
+const CONCURRENT_CLICKS: usize = 100;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_memory_store_click_limit_under_parallel_load() {
+    let store: DynLinkStore = Arc::new(MemoryLinkStore::new());
+    assert_click_limit_holds(store, "limited").await;
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_postgres_store_click_limit_under_parallel_load() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = sqlx::PgPool::connect(&database_url).await.unwrap();
+    migrations::run_pending(&pool).await.unwrap();
+
+    let short_code = format!("t{}", &uuid::Uuid::new_v4().simple().to_string()[..7]);
+    let store: DynLinkStore = Arc::new(PgLinkStore::new(Arc::new(pool)));
+    assert_click_limit_holds(store.clone(), &short_code).await;
+    store.delete(&short_code).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_memory_store_updates_only_the_changed_fields() {
+    let store: DynLinkStore = Arc::new(MemoryLinkStore::new());
+    assert_updates_are_partial(store, "updated").await;
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_store_updates_only_the_changed_fields() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = sqlx::PgPool::connect(&database_url).await.unwrap();
+    migrations::run_pending(&pool).await.unwrap();
+
+    let short_code = format!("t{}", &uuid::Uuid::new_v4().simple().to_string()[..7]);
+    let store: DynLinkStore = Arc::new(PgLinkStore::new(Arc::new(pool)));
+    assert_updates_are_partial(store.clone(), &short_code).await;
+    store.delete(&short_code).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_memory_store_lists_owned_links_by_tag() {
+    let store: DynLinkStore = Arc::new(MemoryLinkStore::new());
+    assert_owned_links_are_listed(store, "owned").await;
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_store_lists_owned_links_by_tag() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = sqlx::PgPool::connect(&database_url).await.unwrap();
+    migrations::run_pending(&pool).await.unwrap();
+
+    let prefix = format!("t{}", &uuid::Uuid::new_v4().simple().to_string()[..6]);
+    let store: DynLinkStore = Arc::new(PgLinkStore::new(Arc::new(pool)));
+    assert_owned_links_are_listed(store.clone(), &prefix).await;
+    for suffix in ["a", "b", "c"] {
+        store.delete(&format!("{}{}", prefix, suffix)).await.unwrap();
+    }
+}
+
+/// Creates links of two accounts, some tagged, and checks each account only lists
+/// its own, oldest first
+async fn assert_owned_links_are_listed(store: DynLinkStore, prefix: &str) {
+    // Accounts are named after the prefix, so earlier runs don't show up
+    let (account, other) = (format!("{}-account", prefix), format!("{}-other", prefix));
+    let links = [("a", &account, &["spring"][..]), ("b", &account, &[]), ("c", &other, &["spring"])];
+    for (suffix, owner, tags) in links {
+        let link = NewLink {
+            owner: Some(owner.clone()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..new_link(&format!("{}{}", prefix, suffix), None)
+        };
+        store.create(link).await.unwrap();
+    }
+
+    let codes = |links: Vec<url_shortener::models::link::Link>| -> Vec<String> {
+        links.into_iter().map(|link| link.short_code).collect()
+    };
+    assert_eq!(
+        codes(store.list_owned(&account, None).await.unwrap()),
+        [format!("{}a", prefix), format!("{}b", prefix)]
+    );
+    assert_eq!(codes(store.list_owned(&account, Some("spring")).await.unwrap()), [format!("{}a", prefix)]);
+    assert!(store.list_owned(&account, Some("autumn")).await.unwrap().is_empty());
+}
+
+/// Applies two updates of different fields, as two concurrent requests would, and
+/// checks neither undoes the other
+async fn assert_updates_are_partial(store: DynLinkStore, short_code: &str) {
+    store.create(new_link(short_code, Some(CLICK_LIMIT))).await.unwrap();
+
+    let destination = LinkChanges {
+        original_url: Some("https://doc.rust-lang.org".to_string()),
+        ..Default::default()
+    };
+    let deactivation = LinkChanges {
+        is_active: Some(false),
+        click_limit: Some(None),
+        ..Default::default()
+    };
+    store.update(short_code, &destination).await.unwrap().unwrap();
+    let link = store.update(short_code, &deactivation).await.unwrap().unwrap();
+    assert_eq!(link.original_url, "https://doc.rust-lang.org");
+    assert!(!link.is_active);
+    assert_eq!(link.click_limit, None);
+
+    // Without changes the link is returned as stored
+    let unchanged = store.update(short_code, &LinkChanges::default()).await.unwrap().unwrap();
+    assert_eq!(unchanged.original_url, link.original_url);
+    assert!(store.update("missing", &destination).await.unwrap().is_none());
+}
+
+/// Runs against PostgreSQL only when TEST_DATABASE_URL is set
+#[tokio::test]
+async fn test_postgres_store_code_growth_only_rises() {
+    let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let pool = sqlx::PgPool::connect(&database_url).await.unwrap();
+    migrations::run_pending(&pool).await.unwrap();
+
+    let store: DynLinkStore = Arc::new(PgLinkStore::new(Arc::new(pool)));
+    let namespace = format!("test:{}", uuid::Uuid::new_v4());
+    assert_eq!(store.code_growth(&namespace).await.unwrap(), 0);
+    assert_eq!(store.grow_codes(&namespace, 2).await.unwrap(), 2);
+    // A lower growth, decided on stale counts, leaves it as it is
+    assert_eq!(store.grow_codes(&namespace, 1).await.unwrap(), 2);
+    assert_eq!(store.code_growth(&namespace).await.unwrap(), 2);
+}
+
+/// Fires many concurrent clicks at a limited link and checks exactly the limit succeeds
+async fn assert_click_limit_holds(store: DynLinkStore, short_code: &str) {
+    store.create(new_link(short_code, Some(CLICK_LIMIT))).await.unwrap();
+
+    let handles: Vec<_> = (0..CONCURRENT_CLICKS)
+        .map(|_| {
+            let store = store.clone();
+            let short_code = short_code.to_string();
+            tokio::spawn(async move { store.consume_click(&short_code).await.unwrap() })
+        })
+        .collect();
+
+    let mut consumed = 0;
+    for handle in handles {
+        if handle.await.unwrap() {
+            consumed += 1;
+        }
+    }
+    assert_eq!(consumed, CLICK_LIMIT);
+
+    let link = store.get_by_code(short_code).await.unwrap().unwrap();
+    assert_eq!(link.click_count, CLICK_LIMIT);
+    assert!(link.is_exhausted());
+}
+
+/// Helper function to describe an active link with the click limit
+fn new_link(short_code: &str, click_limit: Option<i32>) -> NewLink {
+    NewLink {
+        original_url: "https://www.rust-lang.org".to_string(),
+        short_code: short_code.to_string(),
+        starts_at: None,
+        expires_at: None,
+        fallback_url: None,
+        password_hash: None,
+        click_limit,
+        redirect_status: None,
+        passthrough: Passthrough::None,
+        rules: Vec::new(),
+        variants: Vec::new(),
+        card: None,
+        is_active: true,
+        owner: None,
+        tags: Vec::new(),
+    }
+}
@@ -0,0 +1,159 @@
+// tests/routing_rule_tests.rs
+
+use chrono::{DateTime, TimeZone, Utc};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::json;
+use std::collections::HashMap;
+
+use url_shortener::models::rule::{Device, Os, RoutingRule};
+use url_shortener::models::variant::{pick_weighted, Variant};
+use url_shortener::utils::bot::match_user_agent;
+use url_shortener::utils::card::is_unfurl_bot;
+use url_shortener::utils::visitor::{classify_user_agent, preferred_language, Visitor};
+
+const IPHONE: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+const ANDROID_PHONE: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36";
+const ANDROID_TABLET: &str = "Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+const WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+const GOOGLEBOT: &str = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+
+/// Visitor without any known attributes at the given time
+fn visitor(time: DateTime<Utc>) -> Visitor {
+    Visitor {
+        country: None,
+        os: None,
+        device: Device::Other,
+        language: None,
+        time,
+    }
+}
+
+fn rule(value: serde_json::Value) -> RoutingRule {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn test_user_agents_are_classified() {
+    assert_eq!(classify_user_agent(IPHONE), (Some(Os::Ios), Device::Mobile));
+    assert_eq!(classify_user_agent(ANDROID_PHONE), (Some(Os::Android), Device::Mobile));
+    assert_eq!(classify_user_agent(ANDROID_TABLET), (Some(Os::Android), Device::Tablet));
+    assert_eq!(classify_user_agent(WINDOWS), (Some(Os::Windows), Device::Desktop));
+    assert_eq!(classify_user_agent(GOOGLEBOT).1, Device::Bot);
+    assert_eq!(classify_user_agent(""), (None, Device::Other));
+}
+
+#[test]
+fn test_unfurl_bots_are_recognized() {
+    assert!(is_unfurl_bot("Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)"));
+    assert!(is_unfurl_bot("facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)"));
+    assert!(is_unfurl_bot("Mozilla/5.0 (Windows NT 6.1; WOW64) SkypeUriPreview Preview/0.5"));
+    assert!(!is_unfurl_bot(IPHONE));
+    assert!(!is_unfurl_bot(GOOGLEBOT));
+}
+
+#[test]
+fn test_bots_are_recognized() {
+    assert_eq!(match_user_agent(GOOGLEBOT), Some("googlebot"));
+    assert_eq!(match_user_agent("curl/8.4.0"), Some("curl/"));
+    assert!(match_user_agent("UptimeRobot/2.0 (http://www.uptimerobot.com/)").is_some());
+    assert!(match_user_agent("Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)").is_some());
+    for browser in [IPHONE, ANDROID_PHONE, ANDROID_TABLET, WINDOWS] {
+        assert_eq!(match_user_agent(browser), None);
+    }
+}
+
+#[test]
+fn test_preferred_language_uses_quality() {
+    assert_eq!(preferred_language("en-US,en;q=0.9,de;q=0.8").as_deref(), Some("en-US"));
+    assert_eq!(preferred_language("fr;q=0.5, de-AT;q=0.9").as_deref(), Some("de-AT"));
+    assert_eq!(preferred_language("de;q=0, *").as_deref(), None);
+    assert_eq!(preferred_language("").as_deref(), None);
+}
+
+#[test]
+fn test_conditions_must_all_match() {
+    let rule = rule(json!({
+        "destination": "https://www.rust-lang.de",
+        "countries": ["DE", "at"],
+        "languages": ["de"],
+    }));
+    let mut visitor = visitor(Utc::now());
+    assert!(!rule.matches(&visitor));
+
+    visitor.country = Some("AT".to_string());
+    assert!(!rule.matches(&visitor));
+
+    visitor.language = Some("de-AT".to_string());
+    assert!(rule.matches(&visitor));
+
+    visitor.language = Some("dsb".to_string());
+    assert!(!rule.matches(&visitor));
+}
+
+#[test]
+fn test_time_windows_use_local_time() {
+    // Off hours in Berlin, including a window running past midnight
+    let rule = rule(json!({
+        "destination": "https://status.rust-lang.org",
+        "time": { "timezone": "Europe/Berlin", "start": "18:00", "end": "08:00" },
+    }));
+
+    // 16:30 UTC is 18:30 in Berlin during summer time
+    let evening = Utc.with_ymd_and_hms(2024, 7, 1, 16, 30, 0).unwrap();
+    assert!(rule.matches(&visitor(evening)));
+    let night = Utc.with_ymd_and_hms(2024, 7, 1, 23, 0, 0).unwrap();
+    assert!(rule.matches(&visitor(night)));
+    let noon = Utc.with_ymd_and_hms(2024, 7, 1, 10, 0, 0).unwrap();
+    assert!(!rule.matches(&visitor(noon)));
+
+    // Weekends only; 2024-07-06 is a Saturday
+    let rule = self::rule(json!({
+        "destination": "https://status.rust-lang.org",
+        "time": { "timezone": "America/New_York", "start": "00:00", "end": "23:59:59", "days": ["sat", "sun"] },
+    }));
+    assert!(rule.matches(&visitor(Utc.with_ymd_and_hms(2024, 7, 6, 15, 0, 0).unwrap())));
+    assert!(!rule.matches(&visitor(Utc.with_ymd_and_hms(2024, 7, 8, 15, 0, 0).unwrap())));
+}
+
+#[test]
+fn test_rules_are_validated() {
+    let valid = rule(json!({
+        "destination": "https://www.rust-lang.org",
+        "countries": ["DE"],
+        "time": { "timezone": "Europe/Berlin", "start": "09:00", "end": "17:00" },
+    }));
+    assert!(valid.validate().is_ok());
+
+    let invalid = [
+        json!({ "destination": "not a url" }),
+        json!({ "destination": "https://www.rust-lang.org", "countries": ["DEU"] }),
+        json!({
+            "destination": "https://www.rust-lang.org",
+            "time": { "timezone": "Mars/Olympus", "start": "09:00", "end": "17:00" },
+        }),
+    ];
+    for value in invalid {
+        assert!(rule(value).validate().is_err());
+    }
+}
+
+#[test]
+fn test_weighted_pick_follows_weights() {
+    let variants: Vec<Variant> = serde_json::from_value(json!([
+        { "name": "a", "destination": "https://www.rust-lang.org/a", "weight": 3 },
+        { "name": "b", "destination": "https://www.rust-lang.org/b", "weight": 1 },
+        { "name": "off", "destination": "https://www.rust-lang.org/off", "weight": 0 },
+    ]))
+    .unwrap();
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut picks = HashMap::new();
+    for _ in 0..4000 {
+        let variant = pick_weighted(&variants, &mut rng).unwrap();
+        *picks.entry(variant.name.as_str()).or_insert(0) += 1;
+    }
+    assert!((2800..3200).contains(&picks["a"]), "{:?}", picks);
+    assert!(!picks.contains_key("off"));
+    assert!(pick_weighted(&[], &mut rng).is_none());
+}
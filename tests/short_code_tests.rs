@@ -0,0 +1,248 @@
+// tests/short_code_tests.rs
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use url_shortener::errors::ServiceError;
+use url_shortener::models::link::{NewLink, Passthrough};
+use url_shortener::services::CodeAllocator;
+use url_shortener::store::{LinkStore, MemoryLinkStore};
+use url_shortener::utils::short_code::{
+    fold_alphabet, parse_alphabet, CodeRequest, ContentHashGenerator, HashidsGenerator,
+    RandomGenerator, Sequence, SequentialGenerator, ShortCodeGenerator, BASE62_ALPHABET,
+};
+
+fn base62() -> Vec<char> {
+    BASE62_ALPHABET.chars().collect()
+}
+
+/// Request for the given attempt at a code for `original_url`
+fn request(original_url: &str, attempt: u32) -> CodeRequest<'_> {
+    CodeRequest {
+        attempt,
+        ..CodeRequest::new(original_url)
+    }
+}
+
+/// Active link without options; allocators replace the short code
+fn new_link(short_code: &str, original_url: &str) -> NewLink {
+    NewLink {
+        original_url: original_url.to_string(),
+        short_code: short_code.to_string(),
+        starts_at: None,
+        expires_at: None,
+        fallback_url: None,
+        password_hash: None,
+        click_limit: None,
+        redirect_status: None,
+        passthrough: Passthrough::None,
+        rules: Vec::new(),
+        variants: Vec::new(),
+        card: None,
+        is_active: true,
+        owner: None,
+        tags: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_random_codes_use_alphabet_and_length() {
+    let generator = RandomGenerator::new(parse_alphabet("abc").unwrap(), 10);
+
+    for _ in 0..50 {
+        let code = generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap();
+        assert_eq!(code.len(), 10);
+        assert!(code.chars().all(|c| "abc".contains(c)));
+    }
+}
+
+#[tokio::test]
+async fn test_sequential_codes_count_in_base62() {
+    let generator = SequentialGenerator::new(Sequence::memory(61), base62());
+
+    let codes = [
+        generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap(),
+        generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap(),
+        generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap(),
+    ];
+    assert_eq!(codes, ["z", "10", "11"]);
+}
+
+#[tokio::test]
+async fn test_hashids_codes_are_unique_and_obfuscated() {
+    let generator = HashidsGenerator::new(Sequence::memory(1), base62(), "pepper", 6);
+
+    let mut codes = HashSet::new();
+    for _ in 0..1000 {
+        let code = generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap();
+        assert!(code.len() >= 6);
+        codes.insert(code);
+    }
+    assert_eq!(codes.len(), 1000);
+
+    // Consecutive values share no obvious prefix, and the salt changes the encoding
+    assert_ne!(generator.encode(1)[..3], generator.encode(2)[..3]);
+    let other = HashidsGenerator::new(Sequence::memory(1), base62(), "salt", 6);
+    assert_ne!(generator.encode(1), other.encode(1));
+}
+
+#[tokio::test]
+async fn test_content_hash_codes_are_deterministic() {
+    let generator = ContentHashGenerator::new(base62(), 8);
+
+    let first = generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap();
+    assert_eq!(first.len(), 8);
+    assert_eq!(first, generator.generate(&request("https://www.rust-lang.org", 0)).await.unwrap());
+    assert_ne!(first, generator.generate(&request("https://doc.rust-lang.org", 0)).await.unwrap());
+    assert_ne!(first, generator.generate(&request("https://www.rust-lang.org", 1)).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_allocator_retries_taken_codes() {
+    let store = MemoryLinkStore::new();
+    let generator = Arc::new(ContentHashGenerator::new(base62(), 6));
+    let original_url = "https://www.rust-lang.org";
+
+    // Taken by another destination
+    let taken = generator.generate(&request(original_url, 0)).await.unwrap();
+    store.create(new_link(&taken, "https://doc.rust-lang.org")).await.unwrap();
+
+    let allocator = CodeAllocator::new(generator.clone(), 8, 1.0, 100);
+    let link = allocator.create(&store, new_link("", original_url)).await.unwrap();
+    assert_ne!(link.short_code, taken);
+    assert_eq!(
+        link.short_code,
+        generator.generate(&request(original_url, 1)).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_allocator_reuses_content_addressed_links() {
+    let store = MemoryLinkStore::new();
+    let generator = Arc::new(ContentHashGenerator::new(base62(), 6));
+    let allocator = CodeAllocator::new(generator.clone(), 8, 1.0, 100);
+    let original_url = "https://www.rust-lang.org";
+
+    // Shortening the same destination again finds its link
+    let first = allocator.create(&store, new_link("", original_url)).await.unwrap();
+    let again = allocator.create(&store, new_link("", original_url)).await.unwrap();
+    assert_eq!(again.id, first.id);
+    assert_eq!(first.short_code, generator.generate(&request(original_url, 0)).await.unwrap());
+
+    // Unless the settings differ, which takes the next code
+    let limited = NewLink {
+        click_limit: Some(10),
+        ..new_link("", original_url)
+    };
+    let limited = allocator.create(&store, limited).await.unwrap();
+    assert_ne!(limited.id, first.id);
+    assert_eq!(limited.short_code, generator.generate(&request(original_url, 1)).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_allocator_gives_up_after_max_attempts() {
+    let store = MemoryLinkStore::new();
+    store.create(new_link("a", "https://www.rust-lang.org")).await.unwrap();
+    store.create(new_link("b", "https://www.rust-lang.org")).await.unwrap();
+
+    let generator = Arc::new(RandomGenerator::new(parse_alphabet("ab").unwrap(), 1));
+    let allocator = CodeAllocator::new(generator, 3, 1.0, 100);
+    let result = allocator
+        .create(&store, new_link("", "https://doc.rust-lang.org"))
+        .await;
+    assert!(matches!(result, Err(ServiceError::ShortCodeUnavailable(_))));
+}
+
+#[tokio::test]
+async fn test_allocator_grows_codes_when_collisions_are_frequent() {
+    let store = MemoryLinkStore::new();
+    store.create(new_link("a", "https://www.rust-lang.org")).await.unwrap();
+    store.create(new_link("b", "https://www.rust-lang.org")).await.unwrap();
+    let generator = Arc::new(RandomGenerator::new(parse_alphabet("ab").unwrap(), 1));
+
+    // Two collisions in a window of two insert attempts exceed the threshold
+    let allocator = CodeAllocator::new(generator.clone(), 8, 0.1, 2);
+    let link = allocator
+        .create(&store, new_link("", "https://doc.rust-lang.org"))
+        .await
+        .unwrap();
+    assert_eq!(link.short_code.len(), 2);
+    assert_eq!(allocator.extra_length(), 1);
+
+    // The growth is kept in the store, for every allocator of the namespace
+    let other = CodeAllocator::new(generator, 8, 0.1, 100);
+    let link = other
+        .create(&store, new_link("", "https://doc.rust-lang.org"))
+        .await
+        .unwrap();
+    assert_eq!(link.short_code.len(), 2);
+    assert_eq!(store.code_growth("random:1:ab").await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_only_random_codes_grow() {
+    let store = MemoryLinkStore::new();
+
+    // Shortening the same destination again isn't a collision
+    let generator = Arc::new(ContentHashGenerator::new(base62(), 6));
+    let allocator = CodeAllocator::new(generator, 8, 0.1, 2);
+    for _ in 0..4 {
+        let link = allocator.create(&store, new_link("", "https://www.rust-lang.org")).await.unwrap();
+        assert_eq!(link.short_code.len(), 6);
+    }
+    assert_eq!(allocator.extra_length(), 0);
+
+    // Counters only collide with custom aliases
+    let generator = Arc::new(SequentialGenerator::new(Sequence::memory(1), base62()));
+    store.create(new_link("1", "https://www.rust-lang.org")).await.unwrap();
+    let allocator = CodeAllocator::new(generator, 8, 0.1, 1);
+    let link = allocator.create(&store, new_link("", "https://doc.rust-lang.org")).await.unwrap();
+    assert_eq!(link.short_code, "2");
+    assert_eq!(allocator.extra_length(), 0);
+
+    // Random codes stop growing once the alphabet holds 2^64 of them
+    let generator = RandomGenerator::new(base62(), 6);
+    assert_eq!(generator.namespace().unwrap().max_extra_length, 5);
+    let generator = RandomGenerator::new(parse_alphabet("ab").unwrap(), 60);
+    assert_eq!(generator.namespace().unwrap().max_extra_length, 4);
+}
+
+#[tokio::test]
+async fn test_concurrent_allocations_never_share_a_code() {
+    let store: Arc<MemoryLinkStore> = Arc::new(MemoryLinkStore::new());
+    let generator = Arc::new(RandomGenerator::new(parse_alphabet("abcd").unwrap(), 3));
+    let allocator = CodeAllocator::new(generator, 64, 1.0, 100);
+
+    let tasks: Vec<_> = (0..40)
+        .map(|i| {
+            let store = store.clone();
+            let allocator = allocator.clone();
+            tokio::spawn(async move {
+                let url = format!("https://www.rust-lang.org/{}", i);
+                allocator.create(store.as_ref(), new_link("", &url)).await
+            })
+        })
+        .collect();
+
+    let mut codes = HashSet::new();
+    for task in tasks {
+        let link = task.await.unwrap().unwrap();
+        assert!(codes.insert(link.short_code));
+    }
+    assert_eq!(codes.len(), 40);
+}
+
+#[test]
+fn test_parse_alphabet_rejects_invalid_alphabets() {
+    assert!(parse_alphabet("").is_err());
+    assert!(parse_alphabet("a").is_err());
+    assert!(parse_alphabet("abca").is_err());
+    assert_eq!(parse_alphabet("xyz").unwrap(), vec!['x', 'y', 'z']);
+}
+
+#[test]
+fn test_fold_alphabet_keeps_lower_case_letters_once() {
+    let folded = fold_alphabet(&base62());
+    assert_eq!(folded.iter().collect::<String>(), "0123456789abcdefghijklmnopqrstuvwxyz");
+    assert_eq!(fold_alphabet(&parse_alphabet("xXyz").unwrap()), vec!['x', 'y', 'z']);
+}